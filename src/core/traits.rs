@@ -1,3 +1,5 @@
+use noisy_float::types::{n32, n64, N32, N64};
+
 /// When working with pixel data types may have odd bitdepths or not use the
 /// full range of the value. We can't assume every image with `u8` ranges from
 /// [0..255]. Additionally, floating point representations of pixels normally
@@ -60,6 +62,34 @@ impl PixelBound for f32 {
     }
 }
 
+impl PixelBound for N64 {
+    fn min_pixel() -> Self {
+        n64(0.0)
+    }
+
+    fn max_pixel() -> Self {
+        n64(1.0)
+    }
+
+    fn is_integral() -> bool {
+        false
+    }
+}
+
+impl PixelBound for N32 {
+    fn min_pixel() -> Self {
+        n32(0.0)
+    }
+
+    fn max_pixel() -> Self {
+        n32(1.0)
+    }
+
+    fn is_integral() -> bool {
+        false
+    }
+}
+
 impl PixelBound for u8 {
     fn min_pixel() -> Self {
         Self::min_value()
@@ -168,6 +198,7 @@ mod tests {
     fn integral_correct() {
         assert!(!f64::is_integral());
         assert!(!f32::is_integral());
+        assert!(!N64::is_integral());
         assert!(u128::is_integral());
         assert!(u64::is_integral());
         assert!(u32::is_integral());
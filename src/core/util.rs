@@ -1,3 +1,12 @@
+#[cfg(feature = "processing")]
+use crate::core::traits::PixelBound;
+#[cfg(feature = "processing")]
+use ndarray::prelude::*;
+#[cfg(feature = "processing")]
+use ndarray::Data;
+#[cfg(feature = "processing")]
+use num_traits::cast::{FromPrimitive, ToPrimitive};
+
 /// Get the centre of a kernel. Determines the pixel to be
 /// modified as a window is moved over an image
 pub fn kernel_centre(rows: usize, cols: usize) -> (usize, usize) {
@@ -5,3 +14,93 @@ pub fn kernel_centre(rows: usize, cols: usize) -> (usize, usize) {
     let col_offset = cols / 2 - ((cols % 2 == 0) as usize);
     (row_offset, col_offset)
 }
+
+/// Returns the width of the range of values a pixel type can take
+#[cfg(feature = "processing")]
+pub(crate) fn pixel_range<T>() -> f64
+where
+    T: PixelBound + ToPrimitive,
+{
+    let max = T::max_pixel().to_f64().unwrap_or(1.0f64);
+    let min = T::min_pixel().to_f64().unwrap_or(0.0f64);
+    max - min
+}
+
+/// Converts a value calculated as a float back into a pixel. The value is
+/// clamped to the pixel bounds and rounded for integral types
+#[cfg(feature = "processing")]
+pub(crate) fn pixel_from_f64<T>(x: f64) -> T
+where
+    T: PixelBound + FromPrimitive + ToPrimitive,
+{
+    let max = T::max_pixel().to_f64().unwrap_or(1.0f64);
+    let min = T::min_pixel().to_f64().unwrap_or(0.0f64);
+    let mut x = x.max(min).min(max);
+    if T::is_integral() {
+        x = x.round();
+    }
+    T::from_f64(x).unwrap_or_else(T::min_pixel)
+}
+
+/// Converts the data to floats normalised by the pixel range. Returns `None` if
+/// a value can't be represented as a float
+#[cfg(feature = "processing")]
+pub(crate) fn normalised_array<T, U>(data: &ArrayBase<U, Ix3>) -> Option<Array3<f64>>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive + PixelBound,
+{
+    let scale = pixel_range::<T>();
+    let mut result = Array3::zeros(data.dim());
+    for (r, x) in result.iter_mut().zip(data.iter()) {
+        *r = x.to_f64()? / scale;
+    }
+    Some(result)
+}
+
+/// Converts data normalised by the pixel range back into pixels
+#[cfg(feature = "processing")]
+pub(crate) fn denormalised_array<T>(data: Array3<f64>) -> Array3<T>
+where
+    T: Copy + FromPrimitive + ToPrimitive + PixelBound,
+{
+    let scale = pixel_range::<T>();
+    data.mapv(|x| pixel_from_f64(x * scale))
+}
+
+/// Small deterministic random number generator (xorshift64*) used where
+/// algorithms need reproducible random sampling
+#[cfg(feature = "processing")]
+pub(crate) struct XorShift(u64);
+
+#[cfg(feature = "processing")]
+impl XorShift {
+    pub(crate) fn new(seed: u64) -> Self {
+        // Mix the seed so small seeds give different sequences, the state can't
+        // be zero
+        let mut state = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        state = (state ^ (state >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        state = (state ^ (state >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        Self((state ^ (state >> 31)).max(1))
+    }
+
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// Uniformly distributed float in `[0, 1)`
+    #[cfg(feature = "features")]
+    pub(crate) fn next_f64(&mut self) -> f64 {
+        (self.next() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub(crate) fn shuffle<T>(&mut self, values: &mut [T]) {
+        for i in (1..values.len()).rev() {
+            let j = (self.next() % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+    }
+}
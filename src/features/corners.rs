@@ -0,0 +1,393 @@
+use crate::core::{ColourModel, ImageBase};
+use crate::processing::gradient::correlate_replicate;
+use crate::processing::*;
+use ndarray::prelude::*;
+use ndarray::{Data, Zip};
+use num_traits::ToPrimitive;
+use std::cmp::Ordering;
+
+/// The corner response function calculated from the structure tensor
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum CornerResponse {
+    /// Harris response `det(M) - k * trace(M)²`, k is typically 0.04 to 0.06
+    Harris {
+        /// Sensitivity parameter
+        k: f64,
+    },
+    /// Shi-Tomasi response, the minimum eigenvalue of the structure tensor
+    ShiTomasi,
+}
+
+/// A detected corner
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Corner {
+    /// Row of the corner, this is fractional if subpixel refinement is used
+    pub row: f64,
+    /// Column of the corner, this is fractional if subpixel refinement is used
+    pub col: f64,
+    /// Value of the corner response at the corner
+    pub score: f64,
+}
+
+/// Builder to construct the corner detection parameters, if a parameter is
+/// not selected then a sensible default is chosen
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct CornerBuilder {
+    response: Option<CornerResponse>,
+    window: Option<usize>,
+    sigma: Option<f64>,
+    quality: Option<f64>,
+    min_distance: Option<f64>,
+    max_corners: Option<usize>,
+    subpixel: bool,
+}
+
+/// Parameters for corner detection
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct CornerParameters {
+    /// The corner response function
+    pub response: CornerResponse,
+    /// Width of the Gaussian window the structure tensor is summed over
+    pub window: usize,
+    /// Standard deviation of the Gaussian window
+    pub sigma: f64,
+    /// Corners with a response below this fraction of the strongest response
+    /// are rejected
+    pub quality: f64,
+    /// Minimum distance in pixels between corners, weaker corners closer than
+    /// this to a stronger one are removed
+    pub min_distance: f64,
+    /// Maximum number of corners returned, the strongest are kept
+    pub max_corners: Option<usize>,
+    /// Whether to refine corners to subpixel positions, these are the points
+    /// closest to the lines along the edges through every pixel in the window
+    pub subpixel: bool,
+}
+
+impl CornerBuilder {
+    /// Creates a new builder with no parameters selected
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the corner response function
+    pub fn response(self, response: CornerResponse) -> Self {
+        Self {
+            response: Some(response),
+            ..self
+        }
+    }
+
+    /// Sets the width and standard deviation of the Gaussian window
+    pub fn window(self, window: usize, sigma: f64) -> Self {
+        Self {
+            window: Some(window),
+            sigma: Some(sigma),
+            ..self
+        }
+    }
+
+    /// Sets the quality level as a fraction of the strongest response
+    pub fn quality(self, quality: f64) -> Self {
+        Self {
+            quality: Some(quality),
+            ..self
+        }
+    }
+
+    /// Sets the minimum distance between corners
+    pub fn min_distance(self, min_distance: f64) -> Self {
+        Self {
+            min_distance: Some(min_distance),
+            ..self
+        }
+    }
+
+    /// Limits the number of corners returned
+    pub fn max_corners(self, max_corners: usize) -> Self {
+        Self {
+            max_corners: Some(max_corners),
+            ..self
+        }
+    }
+
+    /// Enables subpixel refinement of the corners
+    pub fn subpixel(self, subpixel: bool) -> Self {
+        Self { subpixel, ..self }
+    }
+
+    /// Creates the parameters, defaults are a Harris response with k of 0.04, a
+    /// 5x5 window with a sigma of 1, a quality of 0.01, a minimum distance of 1
+    /// and no limit on the number of corners.
+    pub fn build(self) -> CornerParameters {
+        CornerParameters {
+            response: self.response.unwrap_or(CornerResponse::Harris { k: 0.04 }),
+            window: self.window.unwrap_or(5),
+            sigma: self.sigma.unwrap_or(1.0),
+            quality: self.quality.unwrap_or(0.01),
+            min_distance: self.min_distance.unwrap_or(1.0),
+            max_corners: self.max_corners,
+            subpixel: self.subpixel,
+        }
+    }
+}
+
+/// Detects corners in an image
+pub trait CornerDetectorExt {
+    /// Calculates the corner response for every pixel. Gradients are found with
+    /// the Sobel operator and the structure tensor is weighted by a Gaussian
+    /// window of the given width and standard deviation. The output is single
+    /// channel, for multi-channel images the strongest channel gradient is used.
+    fn corner_response(
+        &self,
+        response: CornerResponse,
+        window: usize,
+        sigma: f64,
+    ) -> Result<Array3<f64>, Error>;
+
+    /// Detects corners returning them ordered by decreasing score. A corner
+    /// must be the maximum of its 3x3 neighbourhood and have a positive
+    /// response.
+    fn detect_corners(&self, params: &CornerParameters) -> Result<Vec<Corner>, Error>;
+}
+
+impl<T, U> CornerDetectorExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn corner_response(
+        &self,
+        response: CornerResponse,
+        window: usize,
+        sigma: f64,
+    ) -> Result<Array3<f64>, Error> {
+        if !(sigma > 0.0 && sigma.is_finite()) {
+            return Err(Error::InvalidParameter);
+        }
+        let kernel: Array3<f64> =
+            GaussianFilter::build_with_params((window, window, 1), [sigma * sigma, sigma * sigma])?;
+        let kernel = kernel.index_axis_move(Axis(2), 0);
+        let grad = self.gradient(GradientOperator::Sobel, GradientNorm::L2)?;
+        let dx = grad.dx.index_axis_move(Axis(2), 0);
+        let dy = grad.dy.index_axis_move(Axis(2), 0);
+        let xx = correlate_replicate((&dx * &dx).view(), kernel.view());
+        let yy = correlate_replicate((&dy * &dy).view(), kernel.view());
+        let xy = correlate_replicate((&dx * &dy).view(), kernel.view());
+
+        let result =
+            Zip::from(&xx)
+                .and(&yy)
+                .and(&xy)
+                .map_collect(|&a: &f64, &c: &f64, &b: &f64| match response {
+                    CornerResponse::Harris { k } => a * c - b * b - k * (a + c).powi(2),
+                    CornerResponse::ShiTomasi => {
+                        (a + c) / 2.0 - ((a - c).powi(2) / 4.0 + b * b).sqrt()
+                    }
+                });
+        Ok(result.insert_axis(Axis(2)))
+    }
+
+    fn detect_corners(&self, params: &CornerParameters) -> Result<Vec<Corner>, Error> {
+        if !(params.quality >= 0.0 && params.min_distance >= 0.0) {
+            return Err(Error::InvalidParameter);
+        }
+        let response = self.corner_response(params.response, params.window, params.sigma)?;
+        let response = response.index_axis(Axis(2), 0);
+        let (rows, cols) = response.dim();
+        let max = response.iter().cloned().fold(0.0, f64::max);
+        let threshold = max * params.quality;
+
+        let mut candidates = vec![];
+        for ((r, c), value) in response.indexed_iter() {
+            if *value <= 0.0 || *value < threshold {
+                continue;
+            }
+            // Ties are won by the first pixel in raster order
+            let (top, left) = (r.saturating_sub(1), c.saturating_sub(1));
+            let window = response.slice(s![top..(r + 2).min(rows), left..(c + 2).min(cols)]);
+            let is_max = window
+                .indexed_iter()
+                .all(|((wr, wc), x)| x < value || (x == value && (top + wr, left + wc) >= (r, c)));
+            if is_max {
+                candidates.push((r, c, *value));
+            }
+        }
+        candidates.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(Ordering::Equal));
+
+        let gradient = if params.subpixel {
+            Some(self.gradient(GradientOperator::Sobel, GradientNorm::L2)?)
+        } else {
+            None
+        };
+        let mut corners: Vec<Corner> = vec![];
+        for (r, c, score) in candidates {
+            if params.max_corners.is_some_and(|max| corners.len() >= max) {
+                break;
+            }
+            let (row, col) = match &gradient {
+                Some(grad) => refine_corner(grad, r, c, (params.window / 2).max(1)),
+                None => (r as f64, c as f64),
+            };
+            let too_close = corners
+                .iter()
+                .any(|other| (other.row - row).hypot(other.col - col) < params.min_distance);
+            if !too_close {
+                corners.push(Corner { row, col, score });
+            }
+        }
+        Ok(corners)
+    }
+}
+
+impl<T, U, C> CornerDetectorExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    fn corner_response(
+        &self,
+        response: CornerResponse,
+        window: usize,
+        sigma: f64,
+    ) -> Result<Array3<f64>, Error> {
+        self.data.corner_response(response, window, sigma)
+    }
+
+    fn detect_corners(&self, params: &CornerParameters) -> Result<Vec<Corner>, Error> {
+        self.data.detect_corners(params)
+    }
+}
+
+/// Refines a corner to the point which best lies on the edge through every
+/// pixel in the window, i.e. where the gradient at each pixel is orthogonal to
+/// the vector from the pixel to the corner. Corners which move out of the
+/// window are left at the pixel position
+fn refine_corner(
+    grad: &Gradient<Array3<f64>>,
+    row: usize,
+    col: usize,
+    radius: usize,
+) -> (f64, f64) {
+    let (rows, cols, _) = grad.dx.dim();
+    let (mut a, mut b, mut c) = (0.0, 0.0, 0.0);
+    let (mut br, mut bc) = (0.0, 0.0);
+    for r in row.saturating_sub(radius)..(row + radius + 1).min(rows) {
+        for cc in col.saturating_sub(radius)..(col + radius + 1).min(cols) {
+            let (gy, gx) = (grad.dy[[r, cc, 0]], grad.dx[[r, cc, 0]]);
+            let (yy, xy, xx) = (gy * gy, gx * gy, gx * gx);
+            a += yy;
+            b += xy;
+            c += xx;
+            br += yy * r as f64 + xy * cc as f64;
+            bc += xy * r as f64 + xx * cc as f64;
+        }
+    }
+    let det = a * c - b * b;
+    if det.abs() <= f64::EPSILON * (a * c).abs() {
+        return (row as f64, col as f64);
+    }
+    let refined = ((c * br - b * bc) / det, (a * bc - b * br) / det);
+    let limit = radius as f64;
+    if (refined.0 - row as f64).abs() <= limit && (refined.1 - col as f64).abs() <= limit {
+        refined
+    } else {
+        (row as f64, col as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, Image};
+
+    fn square() -> Image<f64, Gray> {
+        let mut image = Image::<f64, Gray>::new(32, 32);
+        image.data.slice_mut(s![10..20, 10..20, ..]).fill(1.0);
+        image
+    }
+
+    fn sort_corners(corners: &[Corner]) -> Vec<(f64, f64)> {
+        let mut points = corners.iter().map(|c| (c.row, c.col)).collect::<Vec<_>>();
+        points.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        points
+    }
+
+    #[test]
+    fn square_corners() {
+        let image = square();
+        for response in &[
+            CornerResponse::Harris { k: 0.04 },
+            CornerResponse::ShiTomasi,
+        ] {
+            let params = CornerBuilder::new()
+                .response(*response)
+                .quality(0.1)
+                .subpixel(true)
+                .build();
+            let corners = image.detect_corners(&params).unwrap();
+            assert_eq!(corners.len(), 4, "{:?}", response);
+            let expected = [(9.5, 9.5), (9.5, 19.5), (19.5, 9.5), (19.5, 19.5)];
+            let pixel_params = CornerBuilder::new()
+                .response(*response)
+                .quality(0.1)
+                .subpixel(false)
+                .build();
+            let pixel = sort_corners(&image.detect_corners(&pixel_params).unwrap());
+            for ((found, pixel), expected) in sort_corners(&corners)
+                .iter()
+                .zip(pixel.iter())
+                .zip(expected.iter())
+            {
+                assert!((pixel.0 - expected.0).abs() <= 1.0, "{:?}", pixel);
+                assert!((pixel.1 - expected.1).abs() <= 1.0, "{:?}", pixel);
+                assert!((found.0 - expected.0).abs() < 0.1, "{:?}", found);
+                assert!((found.1 - expected.1).abs() < 0.1, "{:?}", found);
+            }
+            // Sorted by decreasing score
+            assert!(corners.windows(2).all(|w| w[0].score >= w[1].score));
+        }
+
+        // Edges aren't corners
+        let response = image
+            .corner_response(CornerResponse::Harris { k: 0.04 }, 5, 1.0)
+            .unwrap();
+        assert!(response[[15, 10, 0]] < 0.0);
+        assert_eq!(response[[0, 0, 0]], 0.0);
+    }
+
+    #[test]
+    fn corner_limits() {
+        let image = square();
+        let params = CornerBuilder::new().quality(0.1).max_corners(2).build();
+        assert_eq!(image.detect_corners(&params).unwrap().len(), 2);
+
+        // Corners 10 pixels apart are suppressed but the diagonal is kept
+        let params = CornerBuilder::new().quality(0.1).min_distance(12.0).build();
+        let corners = image.detect_corners(&params).unwrap();
+        assert_eq!(corners.len(), 2);
+        let distance = (corners[0].row - corners[1].row).hypot(corners[0].col - corners[1].col);
+        assert!(distance > 12.0);
+
+        let params = CornerBuilder::new().quality(-1.0).build();
+        assert_eq!(image.detect_corners(&params), Err(Error::InvalidParameter));
+        let params = CornerBuilder::new().quality(f64::NAN).build();
+        assert_eq!(image.detect_corners(&params), Err(Error::InvalidParameter));
+        let params = CornerBuilder::new().window(5, f64::NAN).build();
+        assert_eq!(image.detect_corners(&params), Err(Error::InvalidParameter));
+    }
+
+    #[test]
+    fn integer_image_corners() {
+        let image = square();
+        let bytes = Image::<u8, Gray>::from_data(image.data.mapv(|x| (x * 255.0) as u8));
+        let params = CornerBuilder::new().quality(0.1).subpixel(true).build();
+        let expected = sort_corners(&image.detect_corners(&params).unwrap());
+        let found = sort_corners(&bytes.detect_corners(&params).unwrap());
+        assert_eq!(found.len(), 4);
+        for (a, b) in found.iter().zip(expected.iter()) {
+            assert!((a.0 - b.0).abs() < 1e-9 && (a.1 - b.1).abs() < 1e-9);
+        }
+    }
+}
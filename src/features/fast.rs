@@ -0,0 +1,272 @@
+use crate::core::{Gray, ImageBase};
+use crate::features::Keypoint;
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+
+/// The number of contiguous pixels on the circle needed for a corner
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum FastVariant {
+    /// 9 contiguous pixels, the most repeatable variant
+    Fast9,
+    /// 12 contiguous pixels, faster but detects fewer corners
+    Fast12,
+}
+
+/// Parameters for the FAST detector
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct FastParameters {
+    /// Segment test variant
+    pub variant: FastVariant,
+    /// A circle pixel is brighter or darker than the centre if it differs by
+    /// more than this, given in the units of the image
+    pub threshold: f64,
+    /// Remove keypoints that aren't the highest scoring in their 3x3
+    /// neighbourhood
+    pub non_max_suppression: bool,
+    /// Store the corner score in the keypoint response, if false the response
+    /// is zero
+    pub score: bool,
+}
+
+impl Default for FastParameters {
+    fn default() -> Self {
+        Self {
+            variant: FastVariant::Fast9,
+            threshold: 20.0,
+            non_max_suppression: true,
+            score: true,
+        }
+    }
+}
+
+/// Detects keypoints with the FAST (Features from Accelerated Segment Test)
+/// detector. A pixel is a corner if a contiguous arc of pixels on a circle of
+/// radius 3 around it are all brighter or all darker than it.
+pub trait FastExt {
+    /// Detect keypoints in a single channel image. Keypoints are returned in
+    /// raster order, pixels within 3 of the border aren't tested.
+    fn detect_fast(&self, params: &FastParameters) -> Result<Vec<Keypoint>, Error>;
+}
+
+/// Offsets of the Bresenham circle of radius 3 as `(row, col)` in clockwise
+/// order starting from the top
+const CIRCLE: [(isize, isize); 16] = [
+    (-3, 0),
+    (-3, 1),
+    (-2, 2),
+    (-1, 3),
+    (0, 3),
+    (1, 3),
+    (2, 2),
+    (3, 1),
+    (3, 0),
+    (3, -1),
+    (2, -2),
+    (1, -3),
+    (0, -3),
+    (-1, -3),
+    (-2, -2),
+    (-3, -1),
+];
+
+/// Returns true if the flags contain a contiguous run of at least `n` set flags
+/// allowing the run to wrap around
+fn has_arc(flags: &[bool; 16], n: usize) -> bool {
+    let mut run = 0;
+    for i in 0..(16 + n) {
+        if flags[i % 16] {
+            run += 1;
+            if run >= n {
+                return true;
+            }
+        } else {
+            run = 0;
+        }
+    }
+    false
+}
+
+/// Runs the segment test returning the score if the pixel is a corner. The
+/// score is the larger of the sums of absolute differences minus the threshold
+/// over the brighter and darker pixels
+fn segment_test(
+    image: ArrayView2<f64>,
+    r: usize,
+    c: usize,
+    n: usize,
+    threshold: f64,
+) -> Option<f64> {
+    let centre = image[[r, c]];
+    let mut values = [0.0; 16];
+    for (v, (dr, dc)) in values.iter_mut().zip(CIRCLE.iter()) {
+        *v = image[[(r as isize + dr) as usize, (c as isize + dc) as usize]];
+    }
+    // The compass points rule out most pixels, an arc of n covers at least
+    // n / 4 of them
+    let compass = [values[0], values[4], values[8], values[12]];
+    let needed = n / 4;
+    let bright_compass = compass.iter().filter(|v| **v > centre + threshold).count();
+    let dark_compass = compass.iter().filter(|v| **v < centre - threshold).count();
+    if bright_compass < needed && dark_compass < needed {
+        return None;
+    }
+
+    let mut brighter = [false; 16];
+    let mut darker = [false; 16];
+    for i in 0..16 {
+        brighter[i] = values[i] > centre + threshold;
+        darker[i] = values[i] < centre - threshold;
+    }
+    if !has_arc(&brighter, n) && !has_arc(&darker, n) {
+        return None;
+    }
+    let bright_score: f64 = values
+        .iter()
+        .filter(|v| **v > centre + threshold)
+        .map(|v| v - centre - threshold)
+        .sum();
+    let dark_score: f64 = values
+        .iter()
+        .filter(|v| **v < centre - threshold)
+        .map(|v| centre - v - threshold)
+        .sum();
+    Some(bright_score.max(dark_score))
+}
+
+impl<T, U> FastExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn detect_fast(&self, params: &FastParameters) -> Result<Vec<Keypoint>, Error> {
+        let (rows, cols, channels) = self.dim();
+        if channels != 1 {
+            return Err(Error::ChannelDimensionMismatch);
+        } else if params.threshold < 0.0 {
+            return Err(Error::InvalidParameter);
+        }
+        let n = match params.variant {
+            FastVariant::Fast9 => 9,
+            FastVariant::Fast12 => 12,
+        };
+        let image = self
+            .index_axis(Axis(2), 0)
+            .iter()
+            .map(|x| x.to_f64())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::NumericError)?;
+        let image = Array2::from_shape_vec((rows, cols), image).unwrap();
+
+        let mut scores = Array2::<f64>::zeros((rows, cols));
+        let mut corners = vec![];
+        for r in 3..rows.saturating_sub(3) {
+            for c in 3..cols.saturating_sub(3) {
+                if let Some(score) = segment_test(image.view(), r, c, n, params.threshold) {
+                    // Keep corners with a zero score distinct from non-corners
+                    scores[[r, c]] = score.max(f64::MIN_POSITIVE);
+                    corners.push((r, c));
+                }
+            }
+        }
+
+        let is_max = |r: usize, c: usize| {
+            let score = scores[[r, c]];
+            let window = scores.slice(s![r - 1..r + 2, c - 1..c + 2]);
+            // Ties are won by the first pixel in raster order
+            window
+                .indexed_iter()
+                .all(|((wr, wc), other)| *other < score || (*other == score && (wr, wc) >= (1, 1)))
+        };
+        Ok(corners
+            .into_iter()
+            .filter(|(r, c)| !params.non_max_suppression || is_max(*r, *c))
+            .map(|(r, c)| Keypoint {
+                row: r as f64,
+                col: c as f64,
+                response: if params.score { scores[[r, c]] } else { 0.0 },
+                scale: 1.0,
+                orientation: 0.0,
+            })
+            .collect())
+    }
+}
+
+impl<T, U> FastExt for ImageBase<U, Gray>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn detect_fast(&self, params: &FastParameters) -> Result<Vec<Keypoint>, Error> {
+        self.data.detect_fast(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Image;
+
+    fn square() -> Image<u8, Gray> {
+        let mut image = Image::<u8, Gray>::new(32, 32);
+        image.data.slice_mut(s![10..20, 10..20, ..]).fill(200);
+        image
+    }
+
+    #[test]
+    fn fast_square_corners() {
+        let image = square();
+        let keypoints = image.detect_fast(&FastParameters::default()).unwrap();
+        let positions = keypoints
+            .iter()
+            .map(|k| (k.row as usize, k.col as usize))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, vec![(10, 10), (10, 19), (19, 10), (19, 19)]);
+        assert!(keypoints.iter().all(|k| k.response > 0.0 && k.scale == 1.0));
+
+        // The square's corners only have an arc of 11 so FAST-12 needs a
+        // sharper feature such as a small dot
+        let params = FastParameters {
+            variant: FastVariant::Fast12,
+            ..Default::default()
+        };
+        assert!(image.detect_fast(&params).unwrap().is_empty());
+        let mut dot = Image::<u8, Gray>::new(16, 16);
+        dot.data.slice_mut(s![7..9, 7..9, ..]).fill(200);
+        let keypoints = dot.detect_fast(&params).unwrap();
+        assert_eq!(keypoints.len(), 1);
+        assert_eq!((keypoints[0].row, keypoints[0].col), (7.0, 7.0));
+
+        // A threshold above the contrast finds nothing
+        let params = FastParameters {
+            threshold: 200.0,
+            ..Default::default()
+        };
+        assert!(image.detect_fast(&params).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fast_options() {
+        let image = square();
+        let params = FastParameters {
+            non_max_suppression: false,
+            score: false,
+            ..Default::default()
+        };
+        let keypoints = image.detect_fast(&params).unwrap();
+        assert!(keypoints.len() > 4);
+        assert!(keypoints.iter().all(|k| k.response == 0.0));
+
+        // Straight edges aren't corners
+        let mut edge = Image::<u8, Gray>::new(16, 16);
+        edge.data.slice_mut(s![.., 8.., ..]).fill(255);
+        assert!(edge.detect_fast(&params).unwrap().is_empty());
+
+        let colour = Array3::<u8>::zeros((16, 16, 3));
+        assert_eq!(
+            colour.detect_fast(&params),
+            Err(Error::ChannelDimensionMismatch)
+        );
+    }
+}
@@ -0,0 +1,231 @@
+use crate::core::{ColourModel, ImageBase};
+use crate::processing::*;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+use std::f64::consts::PI;
+
+/// Parameters for histogram of oriented gradients (HOG) features
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HogParameters {
+    /// Width and height of each cell in pixels
+    pub cell_size: usize,
+    /// Width and height of each block in cells
+    pub block_size: usize,
+    /// Distance between neighbouring blocks in cells
+    pub block_stride: usize,
+    /// Number of orientation bins in each cell histogram
+    pub bins: usize,
+    /// If true the bins cover 0 to 360 degrees, otherwise gradients with
+    /// opposite directions share a bin and the bins cover 0 to 180 degrees
+    pub signed: bool,
+    /// Maximum value of a normalised block component in L2-Hys normalisation
+    pub clip: f64,
+}
+
+impl Default for HogParameters {
+    fn default() -> Self {
+        Self {
+            cell_size: 8,
+            block_size: 2,
+            block_stride: 1,
+            bins: 9,
+            signed: false,
+            clip: 0.2,
+        }
+    }
+}
+
+/// HOG features of an image
+#[derive(Clone, PartialEq, Debug)]
+pub struct Hog {
+    /// Orientation histogram of each cell indexed by `[cell_row, cell_col, bin]`
+    /// before block normalisation
+    pub cells: Array3<f64>,
+    /// Concatenated normalised block histograms, blocks are in raster order and
+    /// the cells within a block are in raster order
+    pub features: Array1<f64>,
+}
+
+/// Calculates histogram of oriented gradients features
+pub trait HogExt {
+    /// Calculates the HOG features. Gradients are found with the Sobel
+    /// operator, for multi-channel images the channel with the largest
+    /// gradient is used. Each pixel votes with its gradient magnitude into the
+    /// two nearest orientation bins and pixels beyond the last whole cell are
+    /// ignored. Blocks are normalised with L2-Hys. Pixels of any type are
+    /// converted to `f64` before calculating the gradient.
+    fn hog(&self, params: &HogParameters) -> Result<Hog, Error>;
+}
+
+/// Normalises to unit length, clips the components and normalises again
+fn l2_hys(block: &mut [f64], clip: f64) {
+    let normalise = |block: &mut [f64]| {
+        let norm = (block.iter().map(|x| x * x).sum::<f64>() + 1e-10).sqrt();
+        block.iter_mut().for_each(|x| *x /= norm);
+    };
+    normalise(block);
+    block.iter_mut().for_each(|x| *x = x.min(clip));
+    normalise(block);
+}
+
+impl<T, U> HogExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn hog(&self, params: &HogParameters) -> Result<Hog, Error> {
+        if params.cell_size == 0
+            || params.block_size == 0
+            || params.block_stride == 0
+            || params.bins == 0
+            || params.clip <= 0.0
+        {
+            return Err(Error::InvalidParameter);
+        }
+        let cell_rows = self.dim().0 / params.cell_size;
+        let cell_cols = self.dim().1 / params.cell_size;
+        if cell_rows < params.block_size || cell_cols < params.block_size {
+            return Err(Error::InvalidDimensions);
+        }
+
+        let gradient = self.gradient(GradientOperator::Sobel, GradientNorm::L2)?;
+        let magnitude = gradient.magnitude.index_axis_move(Axis(2), 0);
+        let orientation = gradient.orientation.index_axis_move(Axis(2), 0);
+
+        let range = if params.signed { 2.0 * PI } else { PI };
+        let bin_width = range / params.bins as f64;
+        let mut cells = Array3::<f64>::zeros((cell_rows, cell_cols, params.bins));
+        for r in 0..(cell_rows * params.cell_size) {
+            for c in 0..(cell_cols * params.cell_size) {
+                // Position relative to the bin centres
+                let position = orientation[[r, c]].rem_euclid(range) / bin_width - 0.5;
+                let lower = position.floor();
+                let fraction = position - lower;
+                let lower = (lower as isize).rem_euclid(params.bins as isize) as usize;
+                let upper = (lower + 1) % params.bins;
+                let mut cell = cells.slice_mut(s![r / params.cell_size, c / params.cell_size, ..]);
+                cell[lower] += magnitude[[r, c]] * (1.0 - fraction);
+                cell[upper] += magnitude[[r, c]] * fraction;
+            }
+        }
+
+        let block_len = params.block_size * params.block_size * params.bins;
+        let mut features = vec![];
+        for br in (0..=(cell_rows - params.block_size)).step_by(params.block_stride) {
+            for bc in (0..=(cell_cols - params.block_size)).step_by(params.block_stride) {
+                let start = features.len();
+                let block = cells.slice(s![
+                    br..br + params.block_size,
+                    bc..bc + params.block_size,
+                    ..
+                ]);
+                features.extend(block.iter());
+                l2_hys(&mut features[start..start + block_len], params.clip);
+            }
+        }
+        Ok(Hog {
+            cells,
+            features: Array1::from(features),
+        })
+    }
+}
+
+impl<T, U, C> HogExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    fn hog(&self, params: &HogParameters) -> Result<Hog, Error> {
+        self.data.hog(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, Image, RGB};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+
+    #[test]
+    fn hog_dimensions() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let data = Array3::random_using((128, 64, 3), Uniform::new(0.0, 1.0), &mut rng);
+        let image = Image::<f64, RGB>::from_data(data);
+        let hog = image.hog(&HogParameters::default()).unwrap();
+        assert_eq!(hog.cells.dim(), (16, 8, 9));
+        // The classic 3780 element pedestrian detection descriptor
+        assert_eq!(hog.features.len(), 3780);
+        for block in hog.features.exact_chunks(36) {
+            let norm = block.iter().map(|x| x * x).sum::<f64>().sqrt();
+            assert!((norm - 1.0).abs() < 1e-6);
+            assert!(block.iter().all(|x| *x >= 0.0 && *x <= 0.5));
+        }
+
+        let params = HogParameters {
+            block_stride: 2,
+            ..Default::default()
+        };
+        assert_eq!(image.hog(&params).unwrap().features.len(), 8 * 4 * 36);
+    }
+
+    #[test]
+    fn hog_orientations() {
+        // Dark to bright going down the image, the gradient points at 90°
+        let mut image = Image::<f64, Gray>::new(32, 32);
+        image.data.slice_mut(s![16.., .., ..]).fill(1.0);
+        let hog = image.hog(&HogParameters::default()).unwrap();
+        let cell = hog.cells.slice(s![1usize, 1usize, ..]);
+        assert!((cell[4] - cell.sum()).abs() < 1e-10);
+        assert!(cell[4] > 0.0);
+        // Cells away from the edge are empty
+        assert_eq!(hog.cells.slice(s![0, .., ..]).sum(), 0.0);
+
+        let params = HogParameters {
+            bins: 18,
+            signed: true,
+            ..Default::default()
+        };
+        let hog = image.hog(&params).unwrap();
+        assert!(hog.cells[[1, 1, 4]] > 0.0);
+        image.data.mapv_inplace(|x| 1.0 - x);
+        let inverted = image.hog(&params).unwrap();
+        assert!(inverted.cells[[1, 1, 4]] < 1e-10);
+        assert!((inverted.cells[[1, 1, 13]] - hog.cells[[1, 1, 4]]).abs() < 1e-10);
+    }
+
+    #[test]
+    fn hog_integer_image() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let data = Array3::random_using((32, 32, 1), Uniform::new(0u8, 255), &mut rng);
+        let image = Image::<u8, Gray>::from_data(data);
+        let hog = image.hog(&HogParameters::default()).unwrap();
+        // Block normalisation makes the features independent of the scale
+        let scaled = image.data.mapv(|x| x as f64 / 255.0);
+        let expected = scaled.hog(&HogParameters::default()).unwrap();
+        for (a, b) in hog.features.iter().zip(expected.features.iter()) {
+            assert!((a - b).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn hog_parameters() {
+        let image = Image::<f64, Gray>::new(16, 16);
+        let params = HogParameters {
+            cell_size: 16,
+            ..Default::default()
+        };
+        assert_eq!(image.hog(&params), Err(Error::InvalidDimensions));
+        let params = HogParameters {
+            bins: 0,
+            ..Default::default()
+        };
+        assert_eq!(image.hog(&params), Err(Error::InvalidParameter));
+        // A flat image has no gradient
+        let hog = image.hog(&HogParameters::default()).unwrap();
+        assert!(hog.features.iter().all(|x| *x == 0.0));
+    }
+}
@@ -0,0 +1,383 @@
+use crate::core::{Gray, Image, ImageBase};
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+use std::f64::consts::PI;
+
+/// How the binary pattern around a pixel is turned into a code
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum LbpVariant {
+    /// The raw pattern, bit `p` is set if sampling point `p` is at least as
+    /// bright as the centre
+    Default,
+    /// Each uniform pattern, one with at most two 0/1 transitions around the
+    /// circle, gets its own code and all other patterns share the last code
+    Uniform,
+    /// The smallest code of all rotations of the pattern
+    RotationInvariant,
+    /// The number of set bits for uniform patterns, all other patterns share
+    /// the code `points + 1`
+    RotationInvariantUniform,
+}
+
+/// Parameters for local binary patterns
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct LbpParameters {
+    /// Radius of the circle of sampling points in pixels
+    pub radius: f64,
+    /// Number of sampling points, between 1 and 31
+    pub points: usize,
+    /// How patterns are coded
+    pub variant: LbpVariant,
+}
+
+impl Default for LbpParameters {
+    fn default() -> Self {
+        Self {
+            radius: 1.0,
+            points: 8,
+            variant: LbpVariant::Default,
+        }
+    }
+}
+
+impl LbpParameters {
+    /// Number of possible codes, codes are always less than this
+    pub fn bins(&self) -> usize {
+        let p = self.points;
+        match self.variant {
+            LbpVariant::Default | LbpVariant::RotationInvariant => 1 << p,
+            LbpVariant::Uniform => p * (p - 1) + 3,
+            LbpVariant::RotationInvariantUniform => p + 2,
+        }
+    }
+}
+
+/// Calculates local binary patterns (LBP), a texture descriptor comparing each
+/// pixel with points sampled on a circle around it
+pub trait LbpExt {
+    /// Calculates the LBP code of every pixel. Sampling points which don't fall
+    /// on a pixel are bilinearly interpolated and pixels outside the image are
+    /// replicated from the border.
+    fn local_binary_pattern(&self, params: &LbpParameters) -> Result<Image<u32, Gray>, Error>;
+
+    /// Calculates the LBP codes and a histogram of them for each cell of a
+    /// `grid_rows` by `grid_cols` grid covering the image. The histograms are
+    /// indexed by `[cell_row, cell_col, code]`.
+    ///
+    /// Histograms with more than `MAX_LBP_BINS` bins are rejected with
+    /// `Error::InvalidParameter`, this limits the `Default` and
+    /// `RotationInvariant` variants to 16 points.
+    fn lbp_histograms(
+        &self,
+        params: &LbpParameters,
+        grid_rows: usize,
+        grid_cols: usize,
+    ) -> Result<(Image<u32, Gray>, Array3<u32>), Error>;
+}
+
+/// Largest number of bins in each LBP histogram
+pub const MAX_LBP_BINS: usize = 1 << 16;
+
+/// Number of 0/1 transitions going round the pattern
+fn transitions(code: u32, points: usize) -> u32 {
+    let rotated = (code >> 1) | ((code & 1) << (points - 1));
+    (code ^ rotated).count_ones()
+}
+
+fn rotate(code: u32, points: usize, n: usize) -> u32 {
+    let mask = (1u32 << points) - 1;
+    ((code >> n) | (code << (points - n))) & mask
+}
+
+fn encode(code: u32, points: usize, variant: LbpVariant) -> u32 {
+    let p = points as u32;
+    match variant {
+        LbpVariant::Default => code,
+        LbpVariant::RotationInvariant => (0..points)
+            .map(|n| rotate(code, points, n))
+            .min()
+            .unwrap_or(code),
+        LbpVariant::RotationInvariantUniform => {
+            if transitions(code, points) <= 2 {
+                code.count_ones()
+            } else {
+                p + 1
+            }
+        }
+        LbpVariant::Uniform => {
+            let ones = code.count_ones();
+            if transitions(code, points) > 2 {
+                p * (p - 1) + 2
+            } else if ones == 0 {
+                0
+            } else if ones == p {
+                p * (p - 1) + 1
+            } else {
+                // Uniform patterns are a run of ones, label them by the length
+                // of the run and the bit it starts at
+                let start = (0..points)
+                    .find(|n| rotate(code, points, *n) == (1 << ones) - 1)
+                    .unwrap_or(0) as u32;
+                1 + (ones - 1) * p + start
+            }
+        }
+    }
+}
+
+fn bilinear(image: ArrayView2<f64>, row: f64, col: f64) -> f64 {
+    let (rows, cols) = image.dim();
+    let clamp = |x: isize, len: usize| x.clamp(0, len as isize - 1) as usize;
+    let (r0, c0) = (row.floor(), col.floor());
+    let (fr, fc) = (row - r0, col - c0);
+    let (r0, c0) = (r0 as isize, c0 as isize);
+    let sample = |r: isize, c: isize| image[[clamp(r, rows), clamp(c, cols)]];
+    // Interpolating as differences keeps flat regions exact so they compare
+    // equal to the centre
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let top = lerp(sample(r0, c0), sample(r0, c0 + 1), fc);
+    let bottom = lerp(sample(r0 + 1, c0), sample(r0 + 1, c0 + 1), fc);
+    lerp(top, bottom, fr)
+}
+
+impl<T, U> LbpExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn local_binary_pattern(&self, params: &LbpParameters) -> Result<Image<u32, Gray>, Error> {
+        let (rows, cols, channels) = self.dim();
+        if channels != 1 {
+            return Err(Error::ChannelDimensionMismatch);
+        } else if !(params.radius > 0.0 && params.radius.is_finite())
+            || params.points == 0
+            || params.points > 31
+        {
+            return Err(Error::InvalidParameter);
+        }
+        let image = self
+            .index_axis(Axis(2), 0)
+            .iter()
+            .map(|x| x.to_f64())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::NumericError)?;
+        let image = Array2::from_shape_vec((rows, cols), image).unwrap();
+
+        // Offsets as (row, col) going anticlockwise from the right, snapped to
+        // whole pixels when they're close so axis aligned points aren't blurred
+        let snap = |x: f64| {
+            if (x - x.round()).abs() < 1e-6 {
+                x.round()
+            } else {
+                x
+            }
+        };
+        let offsets = (0..params.points)
+            .map(|p| {
+                let angle = 2.0 * PI * p as f64 / params.points as f64;
+                (
+                    snap(-params.radius * angle.sin()),
+                    snap(params.radius * angle.cos()),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        let mut codes = Image::<u32, Gray>::new(rows, cols);
+        for ((r, c), centre) in image.indexed_iter() {
+            let mut code = 0;
+            for (p, (dr, dc)) in offsets.iter().enumerate() {
+                if bilinear(image.view(), r as f64 + dr, c as f64 + dc) >= *centre {
+                    code |= 1 << p;
+                }
+            }
+            codes.data[[r, c, 0]] = encode(code, params.points, params.variant);
+        }
+        Ok(codes)
+    }
+
+    fn lbp_histograms(
+        &self,
+        params: &LbpParameters,
+        grid_rows: usize,
+        grid_cols: usize,
+    ) -> Result<(Image<u32, Gray>, Array3<u32>), Error> {
+        let (rows, cols, _) = self.dim();
+        if grid_rows == 0 || grid_cols == 0 || grid_rows > rows || grid_cols > cols {
+            return Err(Error::InvalidDimensions);
+        }
+        let codes = self.local_binary_pattern(params)?;
+        if params.bins() > MAX_LBP_BINS {
+            return Err(Error::InvalidParameter);
+        }
+        let mut histograms = Array3::<u32>::zeros((grid_rows, grid_cols, params.bins()));
+        for ((r, c, _), code) in codes.data.indexed_iter() {
+            // Cells are as even in size as possible
+            let cell_row = r * grid_rows / rows;
+            let cell_col = c * grid_cols / cols;
+            histograms[[cell_row, cell_col, *code as usize]] += 1;
+        }
+        Ok((codes, histograms))
+    }
+}
+
+impl<T, U> LbpExt for ImageBase<U, Gray>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn local_binary_pattern(&self, params: &LbpParameters) -> Result<Image<u32, Gray>, Error> {
+        self.data.local_binary_pattern(params)
+    }
+
+    fn lbp_histograms(
+        &self,
+        params: &LbpParameters,
+        grid_rows: usize,
+        grid_cols: usize,
+    ) -> Result<(Image<u32, Gray>, Array3<u32>), Error> {
+        self.data.lbp_histograms(params, grid_rows, grid_cols)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+
+    fn params(variant: LbpVariant) -> LbpParameters {
+        LbpParameters {
+            variant,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn lbp_codes() {
+        let mut image = Image::<u8, Gray>::new(5, 5);
+        let flat = image
+            .local_binary_pattern(&params(LbpVariant::Default))
+            .unwrap();
+        assert!(flat.data.iter().all(|x| *x == 255));
+
+        // A bright centre is brighter than all its neighbours
+        image.data[[2, 2, 0]] = 100;
+        let codes = image
+            .local_binary_pattern(&params(LbpVariant::Default))
+            .unwrap();
+        assert_eq!(codes.data[[2, 2, 0]], 0);
+        // Its darker neighbours see it as at least as bright
+        assert_eq!(codes.data[[2, 1, 0]], 255);
+
+        // Vertical edge with the centre on the bright side, points 3 to 5 are
+        // on the dark side
+        let mut edge = Image::<u8, Gray>::new(5, 5);
+        edge.data.slice_mut(s![.., 2.., ..]).fill(100);
+        let codes = edge
+            .local_binary_pattern(&params(LbpVariant::Default))
+            .unwrap();
+        assert_eq!(codes.data[[2, 2, 0]], 0b1100_0111);
+        let codes = edge
+            .local_binary_pattern(&params(LbpVariant::RotationInvariant))
+            .unwrap();
+        assert_eq!(codes.data[[2, 2, 0]], 0b0001_1111);
+        let codes = edge
+            .local_binary_pattern(&params(LbpVariant::RotationInvariantUniform))
+            .unwrap();
+        assert_eq!(codes.data[[2, 2, 0]], 5);
+        let codes = edge
+            .local_binary_pattern(&params(LbpVariant::Uniform))
+            .unwrap();
+        assert_eq!(codes.data[[2, 2, 0]], 1 + 4 * 8 + 6);
+
+        // Alternating bits aren't uniform
+        assert_eq!(
+            encode(0b0101_0101, 8, LbpVariant::RotationInvariantUniform),
+            9
+        );
+        assert_eq!(encode(0b0101_0101, 8, LbpVariant::Uniform), 58);
+        assert_eq!(encode(0, 8, LbpVariant::Uniform), 0);
+        assert_eq!(encode(255, 8, LbpVariant::Uniform), 57);
+    }
+
+    #[test]
+    fn lbp_rotation_invariance() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let data = Array3::random_using((16, 16, 1), Uniform::new(0.0, 1.0), &mut rng);
+        let image = Image::<f64, Gray>::from_data(data);
+        // Rotate the image 90 degrees clockwise
+        let rotated =
+            Image::<f64, Gray>::from_data(Array3::from_shape_fn((16, 16, 1), |(r, c, _)| {
+                image.data[[15 - c, r, 0]]
+            }));
+        for variant in &[
+            LbpVariant::RotationInvariant,
+            LbpVariant::RotationInvariantUniform,
+        ] {
+            let a = image.local_binary_pattern(&params(*variant)).unwrap();
+            let b = rotated.local_binary_pattern(&params(*variant)).unwrap();
+            for r in 2..14 {
+                for c in 2..14 {
+                    assert_eq!(a.data[[r, c, 0]], b.data[[c, 15 - r, 0]]);
+                }
+            }
+            assert!(a
+                .data
+                .iter()
+                .all(|x| (*x as usize) < params(*variant).bins()));
+        }
+    }
+
+    #[test]
+    fn lbp_grid_histograms() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let data = Array3::random_using((10, 9, 1), Uniform::new(0, 255u8), &mut rng);
+        let image = Image::<u8, Gray>::from_data(data);
+        let params = LbpParameters {
+            radius: 2.0,
+            points: 12,
+            variant: LbpVariant::RotationInvariantUniform,
+        };
+        let (codes, histograms) = image.lbp_histograms(&params, 2, 3).unwrap();
+        assert_eq!(histograms.dim(), (2, 3, 14));
+        assert_eq!(histograms.sum(), 90);
+        assert!(histograms
+            .outer_iter()
+            .all(|row| row.outer_iter().all(|cell| cell.sum() == 15)));
+        let code = codes.data[[0, 0, 0]] as usize;
+        assert!(histograms[[0, 0, code]] > 0);
+
+        assert_eq!(
+            image.lbp_histograms(&params, 11, 1),
+            Err(Error::InvalidDimensions)
+        );
+        let params = LbpParameters {
+            points: 32,
+            ..params
+        };
+        assert_eq!(
+            image.local_binary_pattern(&params),
+            Err(Error::InvalidParameter)
+        );
+
+        // Raw codes with many points have too many bins for histograms
+        let mut params = LbpParameters {
+            points: 17,
+            variant: LbpVariant::Default,
+            ..params
+        };
+        assert!(image.local_binary_pattern(&params).is_ok());
+        assert_eq!(
+            image.lbp_histograms(&params, 2, 3),
+            Err(Error::InvalidParameter)
+        );
+        params.points = 16;
+        let (_, histograms) = image.lbp_histograms(&params, 1, 1).unwrap();
+        assert_eq!(histograms.dim(), (1, 1, MAX_LBP_BINS));
+        params.variant = LbpVariant::RotationInvariantUniform;
+        params.points = 31;
+        assert!(image.lbp_histograms(&params, 2, 3).is_ok());
+    }
+}
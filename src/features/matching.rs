@@ -0,0 +1,188 @@
+use std::cmp::Ordering;
+
+/// A feature descriptor which can be compared to another of the same type
+pub trait Descriptor {
+    /// Distance between two descriptors, smaller is more similar
+    fn distance(&self, other: &Self) -> f64;
+}
+
+/// A match between a query descriptor and a train descriptor
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Match {
+    /// Index of the descriptor in the query set
+    pub query: usize,
+    /// Index of the descriptor in the train set
+    pub train: usize,
+    /// Distance between the two descriptors
+    pub distance: f64,
+}
+
+/// Matches descriptors by comparing every query descriptor with every train
+/// descriptor
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct BruteForceMatcher {
+    /// Lowe's ratio test, a match is rejected unless its distance is below
+    /// this fraction of the distance to the second best train descriptor
+    pub ratio: Option<f64>,
+    /// Only keep matches where the query descriptor is also the best match
+    /// for the train descriptor
+    pub cross_check: bool,
+}
+
+/// Finds the closest and second closest distance in `candidates` to `target`
+fn nearest<D: Descriptor>(target: &D, candidates: &[D]) -> Option<(usize, f64, f64)> {
+    let mut best: Option<(usize, f64, f64)> = None;
+    for (i, candidate) in candidates.iter().enumerate() {
+        let distance = target.distance(candidate);
+        best = match best {
+            None => Some((i, distance, f64::INFINITY)),
+            Some((_, d, _)) if distance < d => Some((i, distance, d)),
+            Some((j, d, second)) => Some((j, d, second.min(distance))),
+        };
+    }
+    best
+}
+
+impl BruteForceMatcher {
+    /// Creates a matcher with the ratio test and cross-check disabled
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enables Lowe's ratio test, 0.7 to 0.8 is typical
+    pub fn ratio(self, ratio: f64) -> Self {
+        Self {
+            ratio: Some(ratio),
+            ..self
+        }
+    }
+
+    /// Enables or disables cross-checking
+    pub fn cross_check(self, cross_check: bool) -> Self {
+        Self {
+            cross_check,
+            ..self
+        }
+    }
+
+    /// Finds the best train descriptor for each query descriptor. Query
+    /// descriptors whose match is rejected are omitted, the matches are
+    /// returned in query order.
+    pub fn match_descriptors<D: Descriptor>(&self, query: &[D], train: &[D]) -> Vec<Match> {
+        let mut matches = vec![];
+        for (i, descriptor) in query.iter().enumerate() {
+            let (j, distance, second) = match nearest(descriptor, train) {
+                Some(x) => x,
+                None => break,
+            };
+            if let Some(ratio) = self.ratio {
+                if distance >= ratio * second {
+                    continue;
+                }
+            }
+            if self.cross_check {
+                let back = nearest(&train[j], query).map(|(k, _, _)| k);
+                if back != Some(i) {
+                    continue;
+                }
+            }
+            matches.push(Match {
+                query: i,
+                train: j,
+                distance,
+            });
+        }
+        matches
+    }
+
+    /// Finds the `k` best train descriptors for each query descriptor, each
+    /// list is sorted by increasing distance. The ratio test and cross-check
+    /// aren't applied.
+    pub fn knn_match<D: Descriptor>(&self, query: &[D], train: &[D], k: usize) -> Vec<Vec<Match>> {
+        query
+            .iter()
+            .enumerate()
+            .map(|(i, descriptor)| {
+                let mut matches = train
+                    .iter()
+                    .enumerate()
+                    .map(|(j, other)| Match {
+                        query: i,
+                        train: j,
+                        distance: descriptor.distance(other),
+                    })
+                    .collect::<Vec<_>>();
+                matches.sort_by(|a, b| {
+                    a.distance
+                        .partial_cmp(&b.distance)
+                        .unwrap_or(Ordering::Equal)
+                });
+                matches.truncate(k);
+                matches
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    impl Descriptor for f64 {
+        fn distance(&self, other: &Self) -> f64 {
+            (self - other).abs()
+        }
+    }
+
+    #[test]
+    fn brute_force_filters() {
+        let query = [0.0, 10.0, 28.0, 3.0];
+        let train = [0.5, 9.0, 11.0, 30.0];
+
+        let matches = BruteForceMatcher::new().match_descriptors(&query, &train);
+        let pairs = matches
+            .iter()
+            .map(|m| (m.query, m.train))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(0, 0), (1, 1), (2, 3), (3, 0)]);
+        assert_eq!(matches[0].distance, 0.5);
+
+        // 10 is equally far from 9 and 11
+        let matches = BruteForceMatcher::new()
+            .ratio(0.8)
+            .match_descriptors(&query, &train);
+        let pairs = matches
+            .iter()
+            .map(|m| (m.query, m.train))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(0, 0), (2, 3), (3, 0)]);
+
+        // 0.5 is closer to 0 than 3
+        let matches = BruteForceMatcher::new()
+            .cross_check(true)
+            .match_descriptors(&query, &train);
+        let pairs = matches
+            .iter()
+            .map(|m| (m.query, m.train))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(0, 0), (1, 1), (2, 3)]);
+
+        assert!(BruteForceMatcher::new()
+            .match_descriptors(&query, &[] as &[f64])
+            .is_empty());
+    }
+
+    #[test]
+    fn knn_matching() {
+        let query = [0.0, 10.0];
+        let train = [3.0, 1.0, 12.0];
+        let matches = BruteForceMatcher::new().knn_match(&query, &train, 2);
+        assert_eq!(matches.len(), 2);
+        let trains = matches
+            .iter()
+            .map(|m| m.iter().map(|x| x.train).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(trains, vec![vec![1, 0], vec![2, 0]]);
+        assert!(matches.iter().all(|m| m.iter().all(|x| x.distance >= 0.0)));
+    }
+}
@@ -0,0 +1,40 @@
+/// Harris and Shi-Tomasi corner detection
+pub mod corners;
+/// FAST keypoint detection
+pub mod fast;
+/// Histogram of oriented gradients features
+pub mod hog;
+/// Local binary pattern texture descriptors
+pub mod lbp;
+/// Descriptor matching
+pub mod matching;
+/// ORB binary descriptors
+pub mod orb;
+/// Scale invariant keypoints and gradient histogram descriptors
+pub mod sift;
+
+pub use corners::*;
+pub use fast::*;
+pub use hog::*;
+pub use lbp::*;
+pub use matching::*;
+pub use orb::*;
+pub use sift::*;
+
+/// A keypoint found by a feature detector which can be used to compute a
+/// descriptor
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Keypoint {
+    /// Row of the keypoint in the original image
+    pub row: f64,
+    /// Column of the keypoint in the original image
+    pub col: f64,
+    /// Strength of the detector response, larger is stronger
+    pub response: f64,
+    /// Scale the keypoint was detected at, 1.0 for detectors that work at a
+    /// single scale
+    pub scale: f64,
+    /// Orientation of the keypoint in radians, this is zero for detectors that
+    /// don't assign an orientation
+    pub orientation: f64,
+}
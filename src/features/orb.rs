@@ -0,0 +1,285 @@
+use crate::core::{Gray, ImageBase, XorShift};
+use crate::features::{Descriptor, Keypoint};
+use crate::processing::conv::gaussian_blur;
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+
+/// Number of point pair comparisons in an ORB descriptor
+const ORB_BITS: usize = 256;
+
+/// A 256 bit binary descriptor, compared with the Hamming distance
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct OrbDescriptor(pub [u8; ORB_BITS / 8]);
+
+impl Descriptor for OrbDescriptor {
+    /// Number of differing bits
+    fn distance(&self, other: &Self) -> f64 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (a ^ b).count_ones())
+            .sum::<u32>() as f64
+    }
+}
+
+/// Parameters for computing ORB descriptors
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct OrbParameters {
+    /// Width of the square patch around the keypoint used for the orientation
+    /// and descriptor, must be odd
+    pub patch_size: usize,
+    /// Standard deviation of the Gaussian used to smooth the image before
+    /// sampling
+    pub blur_sigma: f64,
+}
+
+impl Default for OrbParameters {
+    fn default() -> Self {
+        Self {
+            patch_size: 31,
+            blur_sigma: 2.0,
+        }
+    }
+}
+
+/// Computes ORB (oriented FAST and rotated BRIEF) descriptors. The orientation
+/// of each keypoint is found with the intensity centroid of a circular patch
+/// and the BRIEF sampling pattern is rotated to match it so the descriptors
+/// are rotation invariant.
+pub trait OrbExt {
+    /// Computes descriptors for the keypoints. Keypoints too close to the
+    /// border for the patch to fit are dropped, the returned keypoints have
+    /// their orientation set and match the descriptors by index.
+    fn orb_descriptors(
+        &self,
+        keypoints: &[Keypoint],
+        params: &OrbParameters,
+    ) -> Result<(Vec<Keypoint>, Vec<OrbDescriptor>), Error>;
+}
+
+/// The fixed BRIEF sampling pattern, pairs of `(row, col)` offsets drawn from
+/// an isotropic Gaussian and kept inside the inscribed circle of the patch so
+/// they remain in the patch when rotated
+fn brief_pattern(radius: isize) -> Vec<[(isize, isize); 2]> {
+    let mut rng = XorShift::new(0x0b5e_55ed);
+    let sigma = (2 * radius + 1) as f64 / 5.0;
+    let max = radius as f64 - 2.0;
+    let mut sample = || loop {
+        // Box-Muller transform
+        let u1 = 1.0 - rng.next_f64();
+        let u2 = rng.next_f64();
+        let magnitude = sigma * (-2.0 * u1.ln()).sqrt();
+        let (sin, cos) = (2.0 * std::f64::consts::PI * u2).sin_cos();
+        let (row, col) = ((magnitude * sin).round(), (magnitude * cos).round());
+        if row.hypot(col) <= max {
+            return (row as isize, col as isize);
+        }
+    };
+    (0..ORB_BITS).map(|_| [sample(), sample()]).collect()
+}
+
+/// Orientation of the patch from the intensity centroid
+fn patch_orientation(image: ArrayView2<f64>, row: usize, col: usize, radius: isize) -> f64 {
+    let (mut m01, mut m10) = (0.0, 0.0);
+    for dr in -radius..=radius {
+        for dc in -radius..=radius {
+            if dr * dr + dc * dc <= radius * radius {
+                let value = image[[(row as isize + dr) as usize, (col as isize + dc) as usize]];
+                m01 += dr as f64 * value;
+                m10 += dc as f64 * value;
+            }
+        }
+    }
+    m01.atan2(m10)
+}
+
+impl<T, U> OrbExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn orb_descriptors(
+        &self,
+        keypoints: &[Keypoint],
+        params: &OrbParameters,
+    ) -> Result<(Vec<Keypoint>, Vec<OrbDescriptor>), Error> {
+        if self.dim().2 != 1 {
+            return Err(Error::ChannelDimensionMismatch);
+        } else if params.patch_size & 1 == 0 || params.patch_size < 5 {
+            return Err(Error::InvalidDimensions);
+        } else if !(params.blur_sigma > 0.0 && params.blur_sigma.is_finite()) {
+            return Err(Error::InvalidParameter);
+        }
+        let data = self
+            .iter()
+            .map(|x| x.to_f64())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::NumericError)?;
+        let data = Array3::from_shape_vec(self.dim(), data).unwrap();
+        let smoothed = gaussian_blur(&data, params.blur_sigma)?;
+        let smoothed = smoothed.index_axis(Axis(2), 0);
+        let (rows, cols) = smoothed.dim();
+
+        let radius = (params.patch_size / 2) as isize;
+        let pattern = brief_pattern(radius);
+        let mut kept = vec![];
+        let mut descriptors = vec![];
+        for keypoint in keypoints {
+            let row = keypoint.row.round();
+            let col = keypoint.col.round();
+            let fits = |x: f64, len: usize| x >= radius as f64 && x + (radius as f64) < len as f64;
+            if !fits(row, rows) || !fits(col, cols) {
+                continue;
+            }
+            let (row, col) = (row as usize, col as usize);
+            let orientation = patch_orientation(smoothed, row, col, radius);
+            let (sin, cos) = orientation.sin_cos();
+            let sample = |(dr, dc): (isize, isize)| {
+                let (dr, dc) = (dr as f64, dc as f64);
+                let r = row as f64 + (dc * sin + dr * cos).round();
+                let c = col as f64 + (dc * cos - dr * sin).round();
+                smoothed[[r as usize, c as usize]]
+            };
+            let mut bits = [0u8; ORB_BITS / 8];
+            for (i, [a, b]) in pattern.iter().enumerate() {
+                if sample(*a) < sample(*b) {
+                    bits[i / 8] |= 1 << (i % 8);
+                }
+            }
+            kept.push(Keypoint {
+                orientation,
+                ..*keypoint
+            });
+            descriptors.push(OrbDescriptor(bits));
+        }
+        Ok((kept, descriptors))
+    }
+}
+
+impl<T, U> OrbExt for ImageBase<U, Gray>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn orb_descriptors(
+        &self,
+        keypoints: &[Keypoint],
+        params: &OrbParameters,
+    ) -> Result<(Vec<Keypoint>, Vec<OrbDescriptor>), Error> {
+        self.data.orb_descriptors(keypoints, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Image;
+    use crate::features::*;
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+
+    fn texture() -> Image<f64, Gray> {
+        let mut rng = StdRng::seed_from_u64(3);
+        let data = Array3::random_using((64, 64, 1), Uniform::new(0.0, 1.0), &mut rng);
+        Image::from_data(data)
+    }
+
+    fn keypoint(row: f64, col: f64) -> Keypoint {
+        Keypoint {
+            row,
+            col,
+            response: 1.0,
+            scale: 1.0,
+            orientation: 0.0,
+        }
+    }
+
+    #[test]
+    fn orb_rotation_invariance() {
+        let image = texture();
+        // Rotate the image 90 degrees clockwise
+        let rotated =
+            Image::<f64, Gray>::from_data(Array3::from_shape_fn((64, 64, 1), |(r, c, _)| {
+                image.data[[63 - c, r, 0]]
+            }));
+        let points = [(20.0, 20.0), (32.0, 40.0), (40.0, 25.0)];
+        let original = points
+            .iter()
+            .map(|(r, c)| keypoint(*r, *c))
+            .collect::<Vec<_>>();
+        let moved = points
+            .iter()
+            .map(|(r, c)| keypoint(*c, 63.0 - r))
+            .collect::<Vec<_>>();
+        let params = OrbParameters::default();
+        let (kp_a, desc_a) = image.orb_descriptors(&original, &params).unwrap();
+        let (kp_b, desc_b) = rotated.orb_descriptors(&moved, &params).unwrap();
+        assert_eq!(desc_a.len(), 3);
+        for i in 0..3 {
+            let turn =
+                (kp_b[i].orientation - kp_a[i].orientation).rem_euclid(2.0 * std::f64::consts::PI);
+            assert!((turn - std::f64::consts::FRAC_PI_2).abs() < 1e-6);
+            assert!(desc_a[i].distance(&desc_b[i]) < 25.0);
+            for (j, other) in desc_b.iter().enumerate() {
+                if i != j {
+                    assert!(desc_a[i].distance(other) > 60.0);
+                }
+            }
+        }
+
+        let matches = BruteForceMatcher::default().match_descriptors(&desc_a, &desc_b);
+        let pairs = matches
+            .iter()
+            .map(|m| (m.query, m.train))
+            .collect::<Vec<_>>();
+        assert_eq!(pairs, vec![(0, 0), (1, 1), (2, 2)]);
+    }
+
+    #[test]
+    fn orb_border_and_parameters() {
+        let image = texture();
+        let keypoints = vec![
+            keypoint(5.0, 30.0),
+            keypoint(30.0, 30.0),
+            keypoint(30.0, 60.0),
+        ];
+        let (kept, descriptors) = image
+            .orb_descriptors(&keypoints, &OrbParameters::default())
+            .unwrap();
+        assert_eq!(kept.len(), 1);
+        assert_eq!(descriptors.len(), 1);
+        assert_eq!((kept[0].row, kept[0].col), (30.0, 30.0));
+
+        let params = OrbParameters {
+            patch_size: 30,
+            ..Default::default()
+        };
+        assert_eq!(
+            image.orb_descriptors(&keypoints, &params),
+            Err(Error::InvalidDimensions)
+        );
+        let params = OrbParameters {
+            blur_sigma: f64::NAN,
+            ..Default::default()
+        };
+        assert_eq!(
+            image.orb_descriptors(&keypoints, &params),
+            Err(Error::InvalidParameter)
+        );
+
+        // Arrays give the same descriptors as images and must be single channel
+        let params = OrbParameters::default();
+        assert_eq!(
+            image.data.orb_descriptors(&keypoints, &params),
+            image.orb_descriptors(&keypoints, &params)
+        );
+        let colour = Array3::<f64>::zeros((64, 64, 3));
+        assert_eq!(
+            colour.orb_descriptors(&keypoints, &params),
+            Err(Error::ChannelDimensionMismatch)
+        );
+    }
+}
@@ -0,0 +1,558 @@
+use crate::core::{Gray, ImageBase};
+use crate::features::{Descriptor, Keypoint};
+use crate::processing::conv::gaussian_blur;
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+use std::f64::consts::PI;
+
+/// Spatial cells along each side of the descriptor window
+const DESCRIPTOR_CELLS: usize = 4;
+/// Orientation bins in each descriptor cell
+const DESCRIPTOR_BINS: usize = 8;
+/// Bins in the histogram used to assign keypoint orientations
+const ORIENTATION_BINS: usize = 36;
+/// Pixels at the border of each octave which aren't searched for extrema
+const BORDER: usize = 5;
+/// The blur the input image is assumed to already have
+const INPUT_SIGMA: f64 = 0.5;
+
+/// A 128 element gradient histogram descriptor, compared with the Euclidean
+/// distance
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SiftDescriptor(pub [f32; DESCRIPTOR_CELLS * DESCRIPTOR_CELLS * DESCRIPTOR_BINS]);
+
+impl Descriptor for SiftDescriptor {
+    /// Euclidean distance between the descriptors
+    fn distance(&self, other: &Self) -> f64 {
+        self.0
+            .iter()
+            .zip(other.0.iter())
+            .map(|(a, b)| (*a as f64 - *b as f64).powi(2))
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+/// Parameters for the scale invariant keypoint detector
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct SiftParameters {
+    /// Number of octaves, each half the size of the previous. If `None` octaves
+    /// are added until the image is too small to search.
+    pub octaves: Option<usize>,
+    /// Number of scales searched for extrema in each octave
+    pub scales_per_octave: usize,
+    /// Standard deviation of the blur at the first scale of each octave
+    pub sigma: f64,
+    /// Extrema with an absolute difference of Gaussians response below this
+    /// divided by `scales_per_octave` are rejected. Given in the units of the
+    /// image, the default assumes intensities in `[0, 1]`.
+    pub contrast_threshold: f64,
+    /// Extrema where the ratio of the principal curvatures is above this are
+    /// rejected as edges
+    pub edge_threshold: f64,
+}
+
+impl Default for SiftParameters {
+    fn default() -> Self {
+        Self {
+            octaves: None,
+            scales_per_octave: 3,
+            sigma: 1.6,
+            contrast_threshold: 0.04,
+            edge_threshold: 10.0,
+        }
+    }
+}
+
+/// Detects scale invariant keypoints as extrema of a difference of Gaussians
+/// pyramid and describes them with histograms of gradient orientations in the
+/// style of SIFT. Keypoints are refined to subpixel and subscale accuracy and
+/// may be duplicated with different orientations when there's more than one
+/// dominant orientation.
+pub trait SiftExt {
+    /// Detects keypoints, the keypoint scale is the standard deviation of the
+    /// blur they were detected at in original image pixels
+    fn detect_sift(&self, params: &SiftParameters) -> Result<Vec<Keypoint>, Error>;
+
+    /// Detects keypoints and computes their descriptors, the keypoints match
+    /// the descriptors by index
+    fn sift(&self, params: &SiftParameters) -> Result<(Vec<Keypoint>, Vec<SiftDescriptor>), Error>;
+}
+
+/// An octave of the Gaussian and difference of Gaussian pyramids
+struct Octave {
+    gaussians: Vec<Array2<f64>>,
+    dogs: Vec<Array2<f64>>,
+}
+
+/// A keypoint located in the pyramid
+struct Extremum {
+    octave: usize,
+    level: usize,
+    row: f64,
+    col: f64,
+    /// Scale within the octave in octave pixels
+    sigma: f64,
+    response: f64,
+}
+
+fn build_pyramid(image: Array2<f64>, params: &SiftParameters) -> Result<Vec<Octave>, Error> {
+    let s = params.scales_per_octave;
+    let k = 2.0f64.powf(1.0 / s as f64);
+    let smallest = 2 * BORDER + 3;
+    let blur = |image: &Array2<f64>, sigma: f64| -> Result<Array2<f64>, Error> {
+        let image = image.view().insert_axis(Axis(2)).to_owned();
+        Ok(gaussian_blur(&image, sigma)?.index_axis_move(Axis(2), 0))
+    };
+
+    let initial = (params.sigma.powi(2) - INPUT_SIGMA.powi(2))
+        .max(0.01)
+        .sqrt();
+    let mut base = blur(&image, initial)?;
+    let mut octaves = vec![];
+    while params.octaves.is_none_or(|n| octaves.len() < n) {
+        let (rows, cols) = base.dim();
+        if rows < smallest || cols < smallest {
+            break;
+        }
+        let mut gaussians = vec![base];
+        for i in 1..(s + 3) {
+            // Blurs compose by adding variances
+            let previous = params.sigma * k.powi(i as i32 - 1);
+            let increment = previous * (k * k - 1.0).sqrt();
+            let next = blur(&gaussians[i - 1], increment)?;
+            gaussians.push(next);
+        }
+        let dogs = gaussians.windows(2).map(|w| &w[1] - &w[0]).collect();
+        // The level with twice the initial sigma starts the next octave
+        base = gaussians[s].slice(s![..;2, ..;2]).to_owned();
+        octaves.push(Octave { gaussians, dogs });
+    }
+    Ok(octaves)
+}
+
+/// Solves the 3x3 system `a x = b` with Cramer's rule
+fn solve3(a: [[f64; 3]; 3], b: [f64; 3]) -> Option<[f64; 3]> {
+    let det = |m: [[f64; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(a);
+    if d.abs() < f64::EPSILON {
+        return None;
+    }
+    let mut x = [0.0; 3];
+    for (i, x) in x.iter_mut().enumerate() {
+        let mut m = a;
+        for row in 0..3 {
+            m[row][i] = b[row];
+        }
+        *x = det(m) / d;
+    }
+    Some(x)
+}
+
+fn is_extremum(dogs: &[Array2<f64>], level: usize, r: usize, c: usize) -> bool {
+    let value = dogs[level][[r, c]];
+    let mut is_max = true;
+    let mut is_min = true;
+    for dog in &dogs[level - 1..level + 2] {
+        for other in dog.slice(s![r - 1..r + 2, c - 1..c + 2]) {
+            is_max &= value >= *other;
+            is_min &= value <= *other;
+        }
+    }
+    is_max || is_min
+}
+
+/// Fits a quadratic to the difference of Gaussians around an extremum moving
+/// to a neighbouring sample if the offset is over half a sample. Rejects low
+/// contrast and edge like extrema.
+fn refine_extremum(
+    octave: &Octave,
+    index: usize,
+    mut level: usize,
+    mut r: usize,
+    mut c: usize,
+    params: &SiftParameters,
+) -> Option<Extremum> {
+    let s = params.scales_per_octave;
+    let dogs = &octave.dogs;
+    let (rows, cols) = dogs[0].dim();
+    for _ in 0..5 {
+        let d = |l: usize, dr: isize, dc: isize| {
+            dogs[l][[(r as isize + dr) as usize, (c as isize + dc) as usize]]
+        };
+        let centre = d(level, 0, 0);
+        let gradient = [
+            (d(level, 0, 1) - d(level, 0, -1)) / 2.0,
+            (d(level, 1, 0) - d(level, -1, 0)) / 2.0,
+            (d(level + 1, 0, 0) - d(level - 1, 0, 0)) / 2.0,
+        ];
+        let dxx = d(level, 0, 1) + d(level, 0, -1) - 2.0 * centre;
+        let dyy = d(level, 1, 0) + d(level, -1, 0) - 2.0 * centre;
+        let dss = d(level + 1, 0, 0) + d(level - 1, 0, 0) - 2.0 * centre;
+        let dxy = (d(level, 1, 1) - d(level, 1, -1) - d(level, -1, 1) + d(level, -1, -1)) / 4.0;
+        let dxs = (d(level + 1, 0, 1) - d(level + 1, 0, -1) - d(level - 1, 0, 1)
+            + d(level - 1, 0, -1))
+            / 4.0;
+        let dys = (d(level + 1, 1, 0) - d(level + 1, -1, 0) - d(level - 1, 1, 0)
+            + d(level - 1, -1, 0))
+            / 4.0;
+        let hessian = [[dxx, dxy, dxs], [dxy, dyy, dys], [dxs, dys, dss]];
+        let offset = solve3(hessian, [-gradient[0], -gradient[1], -gradient[2]])?;
+
+        if offset.iter().all(|x| x.abs() <= 0.5) {
+            let response = centre + 0.5 * (0..3).map(|i| gradient[i] * offset[i]).sum::<f64>();
+            if response.abs() * (s as f64) < params.contrast_threshold {
+                return None;
+            }
+            let trace = dxx + dyy;
+            let det = dxx * dyy - dxy * dxy;
+            let ratio = params.edge_threshold;
+            if det <= 0.0 || trace * trace * ratio >= (ratio + 1.0).powi(2) * det {
+                return None;
+            }
+            return Some(Extremum {
+                octave: index,
+                level,
+                row: r as f64 + offset[1],
+                col: c as f64 + offset[0],
+                sigma: params.sigma * 2.0f64.powf((level as f64 + offset[2]) / s as f64),
+                response: response.abs(),
+            });
+        }
+        let step = |x: usize, o: f64| (x as f64 + o.round()) as isize;
+        let (new_c, new_r, new_l) = (
+            step(c, offset[0]),
+            step(r, offset[1]),
+            step(level, offset[2]),
+        );
+        if new_l < 1
+            || new_l > s as isize
+            || new_r < BORDER as isize
+            || new_c < BORDER as isize
+            || new_r >= (rows - BORDER) as isize
+            || new_c >= (cols - BORDER) as isize
+        {
+            return None;
+        }
+        level = new_l as usize;
+        r = new_r as usize;
+        c = new_c as usize;
+    }
+    None
+}
+
+fn find_extrema(octaves: &[Octave], params: &SiftParameters) -> Vec<Extremum> {
+    let s = params.scales_per_octave;
+    // Cheap test before the quadratic fit
+    let prefilter = 0.5 * params.contrast_threshold / s as f64;
+    let mut extrema = vec![];
+    for (index, octave) in octaves.iter().enumerate() {
+        let (rows, cols) = octave.dogs[0].dim();
+        for level in 1..=s {
+            for r in BORDER..rows - BORDER {
+                for c in BORDER..cols - BORDER {
+                    if octave.dogs[level][[r, c]].abs() > prefilter
+                        && is_extremum(&octave.dogs, level, r, c)
+                    {
+                        if let Some(e) = refine_extremum(octave, index, level, r, c, params) {
+                            extrema.push(e);
+                        }
+                    }
+                }
+            }
+        }
+    }
+    extrema
+}
+
+/// Central difference gradient as `(magnitude, angle)`, `None` at the border
+fn pixel_gradient(image: ArrayView2<f64>, r: isize, c: isize) -> Option<(f64, f64)> {
+    let (rows, cols) = image.dim();
+    if r < 1 || c < 1 || r + 1 >= rows as isize || c + 1 >= cols as isize {
+        return None;
+    }
+    let (r, c) = (r as usize, c as usize);
+    let dx = image[[r, c + 1]] - image[[r, c - 1]];
+    let dy = image[[r + 1, c]] - image[[r - 1, c]];
+    Some((dx.hypot(dy), dy.atan2(dx)))
+}
+
+/// Finds the dominant orientations from a histogram of gradient orientations
+/// in a Gaussian weighted window, every peak within 80% of the highest is kept
+fn orientations(image: ArrayView2<f64>, e: &Extremum) -> Vec<f64> {
+    let sigma = 1.5 * e.sigma;
+    let radius = (3.0 * sigma).round() as isize;
+    let (row, col) = (e.row.round() as isize, e.col.round() as isize);
+    let mut histogram = [0.0; ORIENTATION_BINS];
+    for dr in -radius..=radius {
+        for dc in -radius..=radius {
+            if let Some((magnitude, angle)) = pixel_gradient(image, row + dr, col + dc) {
+                let weight = (-((dr * dr + dc * dc) as f64) / (2.0 * sigma * sigma)).exp();
+                let bin = (angle.rem_euclid(2.0 * PI) * ORIENTATION_BINS as f64 / (2.0 * PI))
+                    as usize
+                    % ORIENTATION_BINS;
+                histogram[bin] += weight * magnitude;
+            }
+        }
+    }
+    for _ in 0..2 {
+        let previous = histogram;
+        for (i, bin) in histogram.iter_mut().enumerate() {
+            let left = previous[(i + ORIENTATION_BINS - 1) % ORIENTATION_BINS];
+            let right = previous[(i + 1) % ORIENTATION_BINS];
+            *bin = 0.25 * left + 0.5 * previous[i] + 0.25 * right;
+        }
+    }
+
+    let max = histogram.iter().cloned().fold(0.0, f64::max);
+    let mut result = vec![];
+    for i in 0..ORIENTATION_BINS {
+        let left = histogram[(i + ORIENTATION_BINS - 1) % ORIENTATION_BINS];
+        let right = histogram[(i + 1) % ORIENTATION_BINS];
+        let centre = histogram[i];
+        if max > 0.0 && centre > left && centre >= right && centre >= 0.8 * max {
+            let offset = 0.5 * (left - right) / (left - 2.0 * centre + right);
+            let angle = (i as f64 + 0.5 + offset) * 2.0 * PI / ORIENTATION_BINS as f64;
+            result.push(angle.sin().atan2(angle.cos()));
+        }
+    }
+    result
+}
+
+/// Histograms of gradient orientations relative to the keypoint orientation
+/// over a 4x4 grid of cells, each sample is trilinearly interpolated into the
+/// neighbouring cells and bins
+fn describe(image: ArrayView2<f64>, e: &Extremum, orientation: f64) -> SiftDescriptor {
+    let d = DESCRIPTOR_CELLS;
+    let bins = DESCRIPTOR_BINS as f64;
+    let cell_width = 3.0 * e.sigma;
+    let radius = (cell_width * std::f64::consts::SQRT_2 * (d as f64 + 1.0) * 0.5).round() as isize;
+    let (sin, cos) = orientation.sin_cos();
+    let (row, col) = (e.row.round() as isize, e.col.round() as isize);
+    let mut histogram = Array3::<f64>::zeros((d, d, DESCRIPTOR_BINS));
+    for dr in -radius..=radius {
+        for dc in -radius..=radius {
+            // Offset in the keypoint's frame measured in cells
+            let x = (cos * dc as f64 + sin * dr as f64) / cell_width;
+            let y = (-sin * dc as f64 + cos * dr as f64) / cell_width;
+            let cbin = x + d as f64 / 2.0 - 0.5;
+            let rbin = y + d as f64 / 2.0 - 0.5;
+            if cbin <= -1.0 || rbin <= -1.0 || cbin >= d as f64 || rbin >= d as f64 {
+                continue;
+            }
+            let (magnitude, angle) = match pixel_gradient(image, row + dr, col + dc) {
+                Some(g) => g,
+                None => continue,
+            };
+            let weight = (-(x * x + y * y) / (2.0 * (d as f64 / 2.0).powi(2))).exp();
+            let obin = (angle - orientation).rem_euclid(2.0 * PI) * bins / (2.0 * PI);
+            let (r0, c0, o0) = (rbin.floor(), cbin.floor(), obin.floor());
+            let (fr, fc, fo) = (rbin - r0, cbin - c0, obin - o0);
+            for (ri, wr) in [(r0, 1.0 - fr), (r0 + 1.0, fr)] {
+                for (ci, wc) in [(c0, 1.0 - fc), (c0 + 1.0, fc)] {
+                    if ri < 0.0 || ci < 0.0 || ri >= d as f64 || ci >= d as f64 {
+                        continue;
+                    }
+                    for (oi, wo) in [(o0, 1.0 - fo), (o0 + 1.0, fo)] {
+                        let oi = oi as usize % DESCRIPTOR_BINS;
+                        histogram[[ri as usize, ci as usize, oi]] +=
+                            weight * magnitude * wr * wc * wo;
+                    }
+                }
+            }
+        }
+    }
+
+    // Normalising removes contrast changes and clamping reduces the influence
+    // of large gradient magnitudes
+    let normalise = |h: &mut Array3<f64>| {
+        let norm = h.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norm > 0.0 {
+            h.mapv_inplace(|x| x / norm);
+        }
+    };
+    normalise(&mut histogram);
+    histogram.mapv_inplace(|x| x.min(0.2));
+    normalise(&mut histogram);
+    let mut descriptor = [0.0f32; DESCRIPTOR_CELLS * DESCRIPTOR_CELLS * DESCRIPTOR_BINS];
+    for (x, h) in descriptor.iter_mut().zip(histogram.iter()) {
+        *x = *h as f32;
+    }
+    SiftDescriptor(descriptor)
+}
+
+fn sift_impl(
+    image: Array2<f64>,
+    params: &SiftParameters,
+    with_descriptors: bool,
+) -> Result<(Vec<Keypoint>, Vec<SiftDescriptor>), Error> {
+    if params.scales_per_octave == 0
+        || params.sigma <= 0.0
+        || params.contrast_threshold < 0.0
+        || params.edge_threshold < 1.0
+    {
+        return Err(Error::InvalidParameter);
+    }
+    let octaves = build_pyramid(image, params)?;
+    let extrema = find_extrema(&octaves, params);
+    let mut keypoints = vec![];
+    let mut descriptors = vec![];
+    for e in &extrema {
+        let image = octaves[e.octave].gaussians[e.level].view();
+        let factor = 2.0f64.powi(e.octave as i32);
+        for orientation in orientations(image, e) {
+            keypoints.push(Keypoint {
+                row: e.row * factor,
+                col: e.col * factor,
+                response: e.response,
+                scale: e.sigma * factor,
+                orientation,
+            });
+            if with_descriptors {
+                descriptors.push(describe(image, e, orientation));
+            }
+        }
+    }
+    Ok((keypoints, descriptors))
+}
+
+impl<T, U> SiftExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn detect_sift(&self, params: &SiftParameters) -> Result<Vec<Keypoint>, Error> {
+        let image = to_f64(self)?;
+        Ok(sift_impl(image, params, false)?.0)
+    }
+
+    fn sift(&self, params: &SiftParameters) -> Result<(Vec<Keypoint>, Vec<SiftDescriptor>), Error> {
+        let image = to_f64(self)?;
+        sift_impl(image, params, true)
+    }
+}
+
+impl<T, U> SiftExt for ImageBase<U, Gray>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn detect_sift(&self, params: &SiftParameters) -> Result<Vec<Keypoint>, Error> {
+        self.data.detect_sift(params)
+    }
+
+    fn sift(&self, params: &SiftParameters) -> Result<(Vec<Keypoint>, Vec<SiftDescriptor>), Error> {
+        self.data.sift(params)
+    }
+}
+
+fn to_f64<T, U>(data: &ArrayBase<U, Ix3>) -> Result<Array2<f64>, Error>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    let (rows, cols, channels) = data.dim();
+    if channels != 1 {
+        return Err(Error::ChannelDimensionMismatch);
+    }
+    let values = data
+        .iter()
+        .map(|x| x.to_f64())
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Error::NumericError)?;
+    Ok(Array2::from_shape_vec((rows, cols), values).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Image;
+    use crate::features::BruteForceMatcher;
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+    use ndarray_rand::rand_distr::Uniform;
+    use ndarray_rand::RandomExt;
+
+    #[test]
+    fn sift_blob_scale() {
+        for blob_sigma in &[3.0, 6.0] {
+            let image =
+                Image::<f64, Gray>::from_data(Array3::from_shape_fn((64, 64, 1), |(r, c, _)| {
+                    let d2 = (r as f64 - 32.0).powi(2) + (c as f64 - 32.0).powi(2);
+                    (-d2 / (2.0 * blob_sigma * blob_sigma)).exp()
+                }));
+            let keypoints = image.detect_sift(&SiftParameters::default()).unwrap();
+            let strongest = keypoints
+                .iter()
+                .max_by(|a, b| a.response.partial_cmp(&b.response).unwrap())
+                .unwrap();
+            assert!((strongest.row - 32.0).abs() < 0.5, "{:?}", strongest);
+            assert!((strongest.col - 32.0).abs() < 0.5, "{:?}", strongest);
+            let ratio = strongest.scale / blob_sigma;
+            assert!(ratio > 0.7 && ratio < 1.4, "{:?}", strongest);
+        }
+    }
+
+    #[test]
+    fn sift_rotation_matching() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let noise = Array3::random_using((96, 96, 1), Uniform::new(0.0, 1.0), &mut rng);
+        let data = gaussian_blur(&noise, 2.0).unwrap();
+        let image = Image::<f64, Gray>::from_data(data);
+        // Rotate the image 90 degrees clockwise
+        let rotated =
+            Image::<f64, Gray>::from_data(Array3::from_shape_fn((96, 96, 1), |(r, c, _)| {
+                image.data[[95 - c, r, 0]]
+            }));
+        let params = SiftParameters {
+            contrast_threshold: 0.01,
+            ..Default::default()
+        };
+        let (kp_a, desc_a) = image.sift(&params).unwrap();
+        let (kp_b, desc_b) = rotated.sift(&params).unwrap();
+        assert_eq!(kp_a.len(), desc_a.len());
+        assert!(kp_a.len() > 10);
+
+        let matches = BruteForceMatcher::new()
+            .ratio(0.8)
+            .match_descriptors(&desc_a, &desc_b);
+        let correct = matches
+            .iter()
+            .filter(|m| {
+                let (a, b) = (kp_a[m.query], kp_b[m.train]);
+                (b.row - a.col).hypot(b.col - (95.0 - a.row)) < 2.0
+            })
+            .count();
+        assert!(matches.len() >= 10, "{}", matches.len());
+        assert!(
+            correct * 10 >= matches.len() * 9,
+            "{} {}",
+            correct,
+            matches.len()
+        );
+    }
+
+    #[test]
+    fn sift_parameters() {
+        let image = Image::<f64, Gray>::new(32, 32);
+        assert!(image
+            .detect_sift(&SiftParameters::default())
+            .unwrap()
+            .is_empty());
+        let params = SiftParameters {
+            scales_per_octave: 0,
+            ..Default::default()
+        };
+        assert_eq!(image.detect_sift(&params), Err(Error::InvalidParameter));
+        let colour = Array3::<f64>::zeros((32, 32, 3));
+        assert_eq!(
+            colour.detect_sift(&SiftParameters::default()),
+            Err(Error::ChannelDimensionMismatch)
+        );
+    }
+}
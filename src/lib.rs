@@ -30,6 +30,9 @@ pub mod core;
 /// Image enhancement intrinsics and algorithms
 #[cfg(feature = "enhancement")]
 pub mod enhancement;
+/// Feature detection, description and matching
+#[cfg(feature = "features")]
+pub mod features;
 /// Image formats - encoding and decoding images from bytes for saving and
 /// loading
 #[cfg(feature = "format")]
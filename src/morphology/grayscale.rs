@@ -0,0 +1,464 @@
+use super::StructuringElement;
+use crate::core::util::kernel_centre;
+use crate::core::{ColourModel, Image, ImageBase, PixelBound};
+use ndarray::prelude::*;
+use ndarray::{Data, Zip};
+use num_traits::{FromPrimitive, ToPrimitive};
+
+/// Structuring element for grayscale morphology, the element is centred on
+/// the pixel as for the binary operations. Flat `StructuringElement`s with
+/// their own anchors are applied with the `gray_*_with` methods.
+#[derive(Clone, Debug)]
+pub enum GrayscaleKernel<'a> {
+    /// The minimum or maximum over the set pixels
+    Flat(ArrayView2<'a, bool>),
+    /// Heights subtracted from the pixels for erosion and added for dilation,
+    /// entries of negative infinity aren't part of the element
+    NonFlat(ArrayView2<'a, f64>),
+}
+
+/// Grayscale morphology for images of any ordered pixel type. Pixels outside
+/// the image are ignored and non-flat results saturate if they don't fit in
+/// the pixel type.
+pub trait GrayscaleMorphologyExt<T> {
+    /// Output type
+    type Output;
+
+    /// Erosion, the minimum over the kernel centred on each pixel
+    fn gray_erode(&self, kernel: GrayscaleKernel) -> Self::Output;
+
+    /// Dilation, the maximum over the reflected kernel centred on each pixel
+    fn gray_dilate(&self, kernel: GrayscaleKernel) -> Self::Output;
+
+    /// Opening, `iterations` erosions followed by `iterations` dilations.
+    /// Removes bright features the kernel doesn't fit in.
+    fn gray_open(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output;
+
+    /// Closing, `iterations` dilations followed by `iterations` erosions.
+    /// Removes dark features the kernel doesn't fit in.
+    fn gray_close(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output;
+
+    /// White top-hat, the image minus its opening. With a kernel larger than
+    /// the features this subtracts the background.
+    fn gray_top_hat(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output;
+
+    /// Black-hat, the closing minus the image
+    fn gray_black_hat(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output;
+
+    /// Flat erosion by each element in turn about its anchor
+    fn gray_erode_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Flat dilation by each element in turn, reflected about its anchor
+    fn gray_dilate_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Flat opening, erodes by each element in turn and then dilates by each
+    /// reflected element
+    fn gray_open_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Flat closing, dilates by each reflected element in turn and then erodes
+    /// by each element
+    fn gray_close_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// White top-hat by the elements
+    fn gray_top_hat_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Black-hat by the elements
+    fn gray_black_hat_with(&self, elements: &[StructuringElement]) -> Self::Output;
+}
+
+/// Offsets from the kernel centre and heights of the kernel elements
+fn element(kernel: &GrayscaleKernel) -> (Vec<(isize, isize, f64)>, bool) {
+    let offset = |(r, c): (usize, usize), rows: usize, cols: usize| {
+        let (ro, co) = kernel_centre(rows, cols);
+        (r as isize - ro as isize, c as isize - co as isize)
+    };
+    match kernel {
+        GrayscaleKernel::Flat(k) => {
+            let element = k
+                .indexed_iter()
+                .filter(|(_, x)| **x)
+                .map(|(i, _)| {
+                    let (r, c) = offset(i, k.nrows(), k.ncols());
+                    (r, c, 0.0)
+                })
+                .collect();
+            (element, true)
+        }
+        GrayscaleKernel::NonFlat(k) => {
+            let element = k
+                .indexed_iter()
+                .filter(|(_, x)| **x != f64::NEG_INFINITY)
+                .map(|(i, h)| {
+                    let (r, c) = offset(i, k.nrows(), k.ncols());
+                    (r, c, *h)
+                })
+                .collect();
+            (element, false)
+        }
+    }
+}
+
+/// Applies a single erosion or dilation
+fn apply<T>(
+    image: ArrayView3<T>,
+    element: &[(isize, isize, f64)],
+    flat: bool,
+    erode: bool,
+) -> Array3<T>
+where
+    T: Copy + PartialOrd + PixelBound + ToPrimitive + FromPrimitive,
+{
+    let (rows, cols, _) = image.dim();
+    let (min, max) = (T::min_pixel(), T::max_pixel());
+    let mut result = Array3::from_elem(image.dim(), if erode { max } else { min });
+    let better = |a: f64, b: f64| if erode { a < b } else { a > b };
+    Zip::indexed(&mut result).for_each(|(r, c, chan), out| {
+        let mut best: Option<(T, f64)> = None;
+        for (dr, dc, height) in element {
+            // Dilation uses the reflected element
+            let (dr, dc) = if erode { (*dr, *dc) } else { (-dr, -dc) };
+            let (r, c) = (r as isize + dr, c as isize + dc);
+            if r < 0 || c < 0 || r >= rows as isize || c >= cols as isize {
+                continue;
+            }
+            let x = image[[r as usize, c as usize, chan]];
+            if flat {
+                let keep = best.is_some_and(|(b, _)| (erode && b <= x) || (!erode && b >= x));
+                if !keep {
+                    best = Some((x, 0.0));
+                }
+            } else if let Some(v) = x.to_f64() {
+                let v = if erode { v - height } else { v + height };
+                if best.is_none_or(|(_, b)| better(v, b)) {
+                    best = Some((x, v));
+                }
+            }
+        }
+        *out = match best {
+            Some((x, _)) if flat => x,
+            Some((_, v)) => {
+                // Round fractional heights for integer pixels
+                let v = if T::is_integral() { v.round() } else { v };
+                T::from_f64(v).unwrap_or(if v > 0.0 { max } else { min })
+            }
+            None => *out,
+        };
+    });
+    result
+}
+
+/// Offsets from the anchor of each structuring element as flat elements
+fn flat_elements(elements: &[StructuringElement]) -> Vec<Vec<(isize, isize, f64)>> {
+    elements
+        .iter()
+        .map(|e| e.offsets().into_iter().map(|(r, c)| (r, c, 0.0)).collect())
+        .collect()
+}
+
+/// Erodes or dilates by each element in turn
+fn apply_sequence<T>(
+    image: ArrayView3<T>,
+    elements: &[Vec<(isize, isize, f64)>],
+    flat: bool,
+    erode: bool,
+) -> Array3<T>
+where
+    T: Copy + PartialOrd + PixelBound + ToPrimitive + FromPrimitive,
+{
+    let mut result = image.to_owned();
+    for element in elements {
+        result = apply(result.view(), element, flat, erode);
+    }
+    result
+}
+
+/// Erosions by each element followed by dilations or vice versa
+fn compound<T>(
+    image: ArrayView3<T>,
+    elements: &[Vec<(isize, isize, f64)>],
+    flat: bool,
+    erode_first: bool,
+) -> Array3<T>
+where
+    T: Copy + PartialOrd + PixelBound + ToPrimitive + FromPrimitive,
+{
+    let result = apply_sequence(image, elements, flat, erode_first);
+    apply_sequence(result.view(), elements, flat, !erode_first)
+}
+
+/// Difference of two images saturating at zero
+fn difference<T>(a: ArrayView3<T>, b: ArrayView3<T>) -> Array3<T>
+where
+    T: Copy + PixelBound + ToPrimitive + FromPrimitive,
+{
+    Zip::from(a).and(b).map_collect(|a, b| {
+        match (a.to_f64(), b.to_f64()) {
+            (Some(a), Some(b)) if a > b => T::from_f64(a - b),
+            _ => None,
+        }
+        .unwrap_or_else(|| T::from_f64(0.0).unwrap_or_else(T::min_pixel))
+    })
+}
+
+impl<T, U> GrayscaleMorphologyExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + PartialOrd + PixelBound + ToPrimitive + FromPrimitive,
+{
+    type Output = Array3<T>;
+
+    fn gray_erode(&self, kernel: GrayscaleKernel) -> Self::Output {
+        let (element, flat) = element(&kernel);
+        apply(self.view(), &element, flat, true)
+    }
+
+    fn gray_dilate(&self, kernel: GrayscaleKernel) -> Self::Output {
+        let (element, flat) = element(&kernel);
+        apply(self.view(), &element, flat, false)
+    }
+
+    fn gray_open(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        let (element, flat) = element(&kernel);
+        compound(self.view(), &vec![element; iterations], flat, true)
+    }
+
+    fn gray_close(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        let (element, flat) = element(&kernel);
+        compound(self.view(), &vec![element; iterations], flat, false)
+    }
+
+    fn gray_top_hat(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        let opened = self.gray_open(kernel, iterations);
+        difference(self.view(), opened.view())
+    }
+
+    fn gray_black_hat(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        let closed = self.gray_close(kernel, iterations);
+        difference(closed.view(), self.view())
+    }
+
+    fn gray_erode_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        apply_sequence(self.view(), &flat_elements(elements), true, true)
+    }
+
+    fn gray_dilate_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        apply_sequence(self.view(), &flat_elements(elements), true, false)
+    }
+
+    fn gray_open_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        compound(self.view(), &flat_elements(elements), true, true)
+    }
+
+    fn gray_close_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        compound(self.view(), &flat_elements(elements), true, false)
+    }
+
+    fn gray_top_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let opened = self.gray_open_with(elements);
+        difference(self.view(), opened.view())
+    }
+
+    fn gray_black_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let closed = self.gray_close_with(elements);
+        difference(closed.view(), self.view())
+    }
+}
+
+impl<T, U, C> GrayscaleMorphologyExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + PartialOrd + PixelBound + ToPrimitive + FromPrimitive,
+    C: ColourModel,
+{
+    type Output = Image<T, C>;
+
+    fn gray_erode(&self, kernel: GrayscaleKernel) -> Self::Output {
+        Image::from_data(self.data.gray_erode(kernel))
+    }
+
+    fn gray_dilate(&self, kernel: GrayscaleKernel) -> Self::Output {
+        Image::from_data(self.data.gray_dilate(kernel))
+    }
+
+    fn gray_open(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        Image::from_data(self.data.gray_open(kernel, iterations))
+    }
+
+    fn gray_close(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        Image::from_data(self.data.gray_close(kernel, iterations))
+    }
+
+    fn gray_top_hat(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        Image::from_data(self.data.gray_top_hat(kernel, iterations))
+    }
+
+    fn gray_black_hat(&self, kernel: GrayscaleKernel, iterations: usize) -> Self::Output {
+        Image::from_data(self.data.gray_black_hat(kernel, iterations))
+    }
+
+    fn gray_erode_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Image::from_data(self.data.gray_erode_with(elements))
+    }
+
+    fn gray_dilate_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Image::from_data(self.data.gray_dilate_with(elements))
+    }
+
+    fn gray_open_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Image::from_data(self.data.gray_open_with(elements))
+    }
+
+    fn gray_close_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Image::from_data(self.data.gray_close_with(elements))
+    }
+
+    fn gray_top_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Image::from_data(self.data.gray_top_hat_with(elements))
+    }
+
+    fn gray_black_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Image::from_data(self.data.gray_black_hat_with(elements))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, RGB};
+    use ndarray::{arr2, arr3};
+
+    #[test]
+    fn flat_erode_dilate() {
+        let image = arr3(&[[[5u8], [1], [7], [3], [9]]]);
+        let kern = Array2::from_elem((1, 3), true);
+        let flat = || GrayscaleKernel::Flat(kern.view());
+        assert_eq!(image.gray_erode(flat()), arr3(&[[[1], [1], [1], [3], [3]]]));
+        assert_eq!(
+            image.gray_dilate(flat()),
+            arr3(&[[[5], [7], [7], [9], [9]]])
+        );
+        let opened = image.gray_open(flat(), 1);
+        assert_eq!(opened, arr3(&[[[1], [1], [3], [3], [3]]]));
+        assert_eq!(
+            image.gray_top_hat(flat(), 1),
+            arr3(&[[[4], [0], [4], [0], [6]]])
+        );
+        let closed = image.gray_close(flat(), 1);
+        assert_eq!(closed, arr3(&[[[5], [5], [7], [7], [9]]]));
+        assert_eq!(
+            image.gray_black_hat(flat(), 1),
+            arr3(&[[[0], [4], [0], [4], [0]]])
+        );
+
+        // Asymmetric kernels are reflected for dilation
+        let image = arr3(&[[[0.0], [0.0], [9.0], [0.0]]]);
+        let kern = arr2(&[[true, true]]);
+        let pair = GrayscaleKernel::Flat(kern.view());
+        assert_eq!(
+            image.gray_dilate(pair.clone()),
+            arr3(&[[[0.0], [0.0], [9.0], [9.0]]])
+        );
+        assert_eq!(
+            image.gray_erode(pair.clone()),
+            arr3(&[[[0.0], [0.0], [0.0], [0.0]]])
+        );
+        // The dark pixel at the border is too narrow for the kernel
+        assert_eq!(
+            image.gray_close(pair, 1),
+            arr3(&[[[0.0], [0.0], [9.0], [9.0]]])
+        );
+    }
+
+    #[test]
+    fn non_flat() {
+        let heights = arr2(&[[-1.0, 0.0, -1.0]]);
+        let image = arr3(&[[[0.0], [10.0], [0.0], [0.0]]]);
+        let dilated = image.gray_dilate(GrayscaleKernel::NonFlat(heights.view()));
+        assert_eq!(dilated, arr3(&[[[9.0], [10.0], [9.0], [0.0]]]));
+
+        // Results saturate for integer types
+        let heights = arr2(&[[f64::NEG_INFINITY, 10.0, 10.0]]);
+        let image = arr3(&[[[250u8], [5], [0]]]);
+        let kern = || GrayscaleKernel::NonFlat(heights.view());
+        assert_eq!(image.gray_dilate(kern()), arr3(&[[[255], [255], [15]]]));
+        assert_eq!(image.gray_erode(kern()), arr3(&[[[0], [0], [0]]]));
+
+        // Fractional heights are rounded for integer types
+        let heights = arr2(&[[4.7, -0.4]]);
+        let image = arr3(&[[[10u8], [20]]]);
+        let kern = || GrayscaleKernel::NonFlat(heights.view());
+        assert_eq!(image.gray_dilate(kern()), arr3(&[[[15], [25]]]));
+        assert_eq!(image.gray_erode(kern()), arr3(&[[[5], [15]]]));
+    }
+
+    #[test]
+    fn structuring_elements() {
+        // The anchor moves the erosion and dilation
+        let image = arr3(&[[[0u8], [0], [9], [0], [0]]]);
+        let moved = StructuringElement::rect(1, 2).with_anchor((0, 1));
+        let moved = std::slice::from_ref(&moved);
+        assert_eq!(
+            image.gray_dilate_with(moved),
+            arr3(&[[[0], [9], [9], [0], [0]]])
+        );
+        assert_eq!(
+            image.gray_erode_with(moved),
+            arr3(&[[[0], [0], [0], [0], [0]]])
+        );
+        let centred = [StructuringElement::rect(1, 2)];
+        assert_eq!(
+            image.gray_dilate_with(&centred),
+            arr3(&[[[0], [0], [9], [9], [0]]])
+        );
+
+        // Elements match the flat kernels and decompositions match the
+        // whole element
+        let image =
+            Image::<u8, RGB>::from_data(Array3::from_shape_fn((9, 11, 3), |(r, c, chan)| {
+                ((r * 37 + c * 11 + chan * 53) % 101) as u8
+            }));
+        let kern = Array2::from_elem((3, 4), true);
+        let flat = || GrayscaleKernel::Flat(kern.view());
+        let rect = [StructuringElement::rect(3, 4)];
+        let sequence = StructuringElement::rect_sequence(3, 4);
+        assert_eq!(image.gray_erode_with(&rect), image.gray_erode(flat()));
+        assert_eq!(image.gray_dilate_with(&sequence), image.gray_dilate(flat()));
+        assert_eq!(image.gray_open_with(&sequence), image.gray_open(flat(), 1));
+        assert_eq!(image.gray_close_with(&rect), image.gray_close(flat(), 1));
+        assert_eq!(
+            image.gray_top_hat_with(&sequence),
+            image.gray_top_hat(flat(), 1)
+        );
+        assert_eq!(
+            image.gray_black_hat_with(&rect),
+            image.gray_black_hat(flat(), 1)
+        );
+    }
+
+    #[test]
+    fn background_subtraction() {
+        // Small bright spots on an uneven background
+        let mut image =
+            Image::<u8, RGB>::from_data(Array3::from_shape_fn((20, 20, 3), |(r, c, chan)| {
+                (r + c + 10 * chan) as u8
+            }));
+        image.data[[5, 5, 0]] += 100;
+        image.data[[12, 14, 2]] += 50;
+        let kern = Array2::from_elem((5, 5), true);
+        let top_hat = image.gray_top_hat(GrayscaleKernel::Flat(kern.view()), 1);
+        assert!(top_hat.data[[5, 5, 0]] >= 95);
+        assert!(top_hat.data[[12, 14, 2]] >= 45);
+        let background = top_hat
+            .data
+            .indexed_iter()
+            .filter(|((r, c, _), _)| (*r, *c) != (5, 5) && (*r, *c) != (12, 14))
+            .map(|(_, x)| *x)
+            .max();
+        // The ramp leaves at most a few levels behind
+        assert!(background.unwrap() <= 4);
+
+        let gray = Image::<f64, Gray>::from_data(Array3::from_elem((4, 4, 1), 0.5));
+        let opened = gray.gray_open(GrayscaleKernel::Flat(kern.view()), 2);
+        assert_eq!(opened, gray);
+    }
+}
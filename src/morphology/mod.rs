@@ -1,13 +1,30 @@
 use crate::core::*;
 use ndarray::{prelude::*, DataMut, Zip};
 
+/// Grayscale erosion, dilation and compound operations
+pub mod grayscale;
+/// Structuring elements and their decompositions
+pub mod structuring_element;
+
+pub use grayscale::*;
+pub use structuring_element::*;
+
 pub trait MorphologyExt {
     type Output;
 
+    /// Erosion, a pixel stays set if every pixel under the set kernel
+    /// elements centred on it is set. Pixels outside the image are unset and
+    /// each channel is eroded independently.
     fn erode(&self, kernel: ArrayView2<bool>) -> Self::Output;
 
     fn erode_inplace(&mut self, kernel: ArrayView2<bool>);
 
+    /// Dilation, a pixel is set if any pixel under the set kernel elements
+    /// centred on it is set. Each channel is dilated independently.
+    ///
+    /// The kernel isn't reflected, so for asymmetric kernels this differs from
+    /// the dilation used by `open`, `close` and `gradient` which reflect it.
+    /// Use `dilate_with` for the reflected dilation.
     fn dilate(&self, kernel: ArrayView2<bool>) -> Self::Output;
 
     fn dilate_inplace(&mut self, kernel: ArrayView2<bool>);
@@ -19,6 +36,186 @@ pub trait MorphologyExt {
     fn intersection(&self, other: &Self) -> Self::Output;
 
     fn intersection_inplace(&mut self, other: &Self);
+
+    /// Opening, the image is eroded `iterations` times and then dilated by the
+    /// reflected kernel `iterations` times. Removes foreground smaller than the
+    /// kernel.
+    fn open(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output;
+
+    fn open_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize);
+
+    /// Closing, the image is dilated by the reflected kernel `iterations` times
+    /// and then eroded `iterations` times. Fills background smaller than the
+    /// kernel.
+    fn close(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output;
+
+    fn close_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize);
+
+    /// Morphological gradient, the pixels set in the dilation but not in the
+    /// erosion leaving the outlines of the foreground
+    fn gradient(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output;
+
+    fn gradient_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize);
+
+    /// White top-hat, the pixels set in the image but not in its opening
+    fn top_hat(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output;
+
+    fn top_hat_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize);
+
+    /// Black-hat, the pixels set in the closing but not in the image
+    fn black_hat(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output;
+
+    fn black_hat_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize);
+
+    /// Erodes by each element in turn, a decomposed element such as
+    /// `StructuringElement::rect_sequence` gives the same result as eroding by
+    /// the whole element
+    fn erode_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Dilates by each element in turn, unlike `dilate` the elements are
+    /// reflected about their anchor so a set pixel becomes a copy of the element
+    fn dilate_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Opening by the elements, erodes by each element in turn and then
+    /// dilates by each reflected element. Unlike `open` the anchors of the
+    /// elements are kept.
+    fn open_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Closing by the elements, dilates by each reflected element in turn and
+    /// then erodes by each element
+    fn close_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Morphological gradient by the elements
+    fn gradient_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// White top-hat by the elements
+    fn top_hat_with(&self, elements: &[StructuringElement]) -> Self::Output;
+
+    /// Black-hat by the elements
+    fn black_hat_with(&self, elements: &[StructuringElement]) -> Self::Output;
+}
+
+/// Offsets of the set kernel elements relative to the kernel centre
+fn kernel_offsets(kernel: ArrayView2<bool>) -> Vec<(isize, isize)> {
+    StructuringElement::centred(kernel.to_owned()).offsets()
+}
+
+/// Sets each output pixel if all (erosion) or any (dilation) of the input
+/// pixels at the offsets from it are set, pixels outside the image are unset
+fn apply_offsets(
+    image: ArrayView3<bool>,
+    output: ArrayViewMut3<bool>,
+    offsets: &[(isize, isize)],
+    erode: bool,
+) {
+    let (rows, cols, _) = image.dim();
+    Zip::indexed(output).for_each(|(r, c, chan), out| {
+        let mut hits = offsets.iter().map(|(dr, dc)| {
+            let (r, c) = (r as isize + dr, c as isize + dc);
+            r >= 0
+                && c >= 0
+                && r < rows as isize
+                && c < cols as isize
+                && image[[r as usize, c as usize, chan]]
+        });
+        *out = if erode {
+            hits.all(|x| x)
+        } else {
+            hits.any(|x| x)
+        };
+    });
+}
+
+/// Applies each pass of erosion (true) or dilation (false) with the offsets in
+/// turn. Passes alternate between the image and a single scratch buffer, the
+/// result is only copied back if the last pass wrote to the scratch buffer
+fn apply_passes<'a, S>(
+    image: &mut ArrayBase<S, Ix3>,
+    passes: impl IntoIterator<Item = (&'a [(isize, isize)], bool)>,
+) where
+    S: DataMut<Elem = bool>,
+{
+    let mut scratch: Option<Array3<bool>> = None;
+    let mut in_scratch = false;
+    for (offsets, erode) in passes {
+        let buffer = scratch.get_or_insert_with(|| Array3::from_elem(image.dim(), false));
+        if in_scratch {
+            apply_offsets(buffer.view(), image.view_mut(), offsets, erode);
+        } else {
+            apply_offsets(image.view(), buffer.view_mut(), offsets, erode);
+        }
+        in_scratch = !in_scratch;
+    }
+    if let (true, Some(buffer)) = (in_scratch, &scratch) {
+        image.assign(buffer);
+    }
+}
+
+/// Offsets reflected about the anchor
+fn reflect(offsets: &[(isize, isize)]) -> Vec<(isize, isize)> {
+    offsets.iter().map(|(r, c)| (-r, -c)).collect()
+}
+
+/// Offsets for a sequence of erosions and the matching reflected dilations
+struct Passes {
+    erode: Vec<Vec<(isize, isize)>>,
+    dilate: Vec<Vec<(isize, isize)>>,
+}
+
+impl Passes {
+    fn new(offsets: impl Iterator<Item = Vec<(isize, isize)>>) -> Self {
+        let erode = offsets.collect::<Vec<_>>();
+        let dilate = erode.iter().map(|x| reflect(x)).collect();
+        Self { erode, dilate }
+    }
+
+    /// The centred kernel repeated `iterations` times
+    fn kernel(kernel: ArrayView2<bool>, iterations: usize) -> Self {
+        let offsets = kernel_offsets(kernel);
+        Self::new((0..iterations).map(|_| offsets.clone()))
+    }
+
+    /// Each element about its own anchor
+    fn elements(elements: &[StructuringElement]) -> Self {
+        Self::new(elements.iter().map(|e| e.offsets()))
+    }
+
+    fn erosions(&self) -> impl Iterator<Item = (&[(isize, isize)], bool)> {
+        self.erode.iter().map(|x| (&x[..], true))
+    }
+
+    fn dilations(&self) -> impl Iterator<Item = (&[(isize, isize)], bool)> {
+        self.dilate.iter().map(|x| (&x[..], false))
+    }
+
+    fn open<S: DataMut<Elem = bool>>(&self, image: &mut ArrayBase<S, Ix3>) {
+        apply_passes(image, self.erosions().chain(self.dilations()));
+    }
+
+    fn close<S: DataMut<Elem = bool>>(&self, image: &mut ArrayBase<S, Ix3>) {
+        apply_passes(image, self.dilations().chain(self.erosions()));
+    }
+
+    fn gradient<S: DataMut<Elem = bool>>(&self, image: &mut ArrayBase<S, Ix3>) {
+        let mut eroded = image.to_owned();
+        apply_passes(&mut eroded, self.erosions());
+        apply_passes(image, self.dilations());
+        Zip::from(image).and(&eroded).for_each(|x, e| *x = *x && !e);
+    }
+
+    fn top_hat<S: DataMut<Elem = bool>>(&self, image: &mut ArrayBase<S, Ix3>) {
+        let mut opened = image.to_owned();
+        self.open(&mut opened);
+        Zip::from(image).and(&opened).for_each(|x, o| *x = *x && !o);
+    }
+
+    fn black_hat<S: DataMut<Elem = bool>>(&self, image: &mut ArrayBase<S, Ix3>) {
+        let original = image.to_owned();
+        self.close(image);
+        Zip::from(image)
+            .and(&original)
+            .for_each(|x, o| *x = *x && !o);
+    }
 }
 
 impl<U> MorphologyExt for ArrayBase<U, Ix3>
@@ -28,39 +225,33 @@ where
     type Output = Array<bool, Ix3>;
 
     fn erode(&self, kernel: ArrayView2<bool>) -> Self::Output {
-        let sh = kernel.shape();
-        let (ro, co) = kernel_centre(sh[0], sh[1]);
         let mut result = Self::Output::from_elem(self.dim(), false);
-        if self.shape()[0] >= sh[0] && self.shape()[1] >= sh[1] {
-            Zip::indexed(self.slice(s![.., .., 0]).windows(kernel.dim())).for_each(
-                |(i, j), window| {
-                    result[[i + ro, j + co, 0]] = (&kernel & &window) == kernel;
-                },
-            );
-        }
+        apply_offsets(
+            self.view(),
+            result.view_mut(),
+            &kernel_offsets(kernel),
+            true,
+        );
         result
     }
 
     fn erode_inplace(&mut self, kernel: ArrayView2<bool>) {
-        self.assign(&self.erode(kernel));
+        apply_passes(self, Some((&kernel_offsets(kernel)[..], true)));
     }
 
     fn dilate(&self, kernel: ArrayView2<bool>) -> Self::Output {
-        let sh = kernel.shape();
-        let (ro, co) = kernel_centre(sh[0], sh[1]);
         let mut result = Self::Output::from_elem(self.dim(), false);
-        if self.shape()[0] >= sh[0] && self.shape()[1] >= sh[1] {
-            Zip::indexed(self.slice(s![.., .., 0]).windows(kernel.dim())).for_each(
-                |(i, j), window| {
-                    result[[i + ro, j + co, 0]] = (&kernel & &window).iter().any(|x| *x);
-                },
-            );
-        }
+        apply_offsets(
+            self.view(),
+            result.view_mut(),
+            &kernel_offsets(kernel),
+            false,
+        );
         result
     }
 
     fn dilate_inplace(&mut self, kernel: ArrayView2<bool>) {
-        self.assign(&self.dilate(kernel));
+        apply_passes(self, Some((&kernel_offsets(kernel)[..], false)));
     }
 
     fn union(&self, other: &Self) -> Self::Output {
@@ -78,6 +269,97 @@ where
     fn intersection_inplace(&mut self, other: &Self) {
         *self &= other;
     }
+    fn open(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        let mut result = self.to_owned();
+        result.open_inplace(kernel, iterations);
+        result
+    }
+
+    fn open_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        Passes::kernel(kernel, iterations).open(self);
+    }
+
+    fn close(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        let mut result = self.to_owned();
+        result.close_inplace(kernel, iterations);
+        result
+    }
+
+    fn close_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        Passes::kernel(kernel, iterations).close(self);
+    }
+
+    fn gradient(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        let mut result = self.to_owned();
+        result.gradient_inplace(kernel, iterations);
+        result
+    }
+
+    fn gradient_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        Passes::kernel(kernel, iterations).gradient(self);
+    }
+
+    fn top_hat(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        let mut result = self.to_owned();
+        result.top_hat_inplace(kernel, iterations);
+        result
+    }
+
+    fn top_hat_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        Passes::kernel(kernel, iterations).top_hat(self);
+    }
+
+    fn black_hat(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        let mut result = self.to_owned();
+        result.black_hat_inplace(kernel, iterations);
+        result
+    }
+
+    fn black_hat_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        Passes::kernel(kernel, iterations).black_hat(self);
+    }
+
+    fn erode_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let mut result = self.to_owned();
+        apply_passes(&mut result, Passes::elements(elements).erosions());
+        result
+    }
+
+    fn dilate_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let mut result = self.to_owned();
+        apply_passes(&mut result, Passes::elements(elements).dilations());
+        result
+    }
+
+    fn open_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let mut result = self.to_owned();
+        Passes::elements(elements).open(&mut result);
+        result
+    }
+
+    fn close_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let mut result = self.to_owned();
+        Passes::elements(elements).close(&mut result);
+        result
+    }
+
+    fn gradient_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let mut result = self.to_owned();
+        Passes::elements(elements).gradient(&mut result);
+        result
+    }
+
+    fn top_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let mut result = self.to_owned();
+        Passes::elements(elements).top_hat(&mut result);
+        result
+    }
+
+    fn black_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        let mut result = self.to_owned();
+        Passes::elements(elements).black_hat(&mut result);
+        result
+    }
 }
 
 impl<U, C> MorphologyExt for ImageBase<U, C>
@@ -118,6 +400,73 @@ where
     fn intersection_inplace(&mut self, other: &Self) {
         self.data.intersection_inplace(&other.data);
     }
+    fn open(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        Self::Output::from_data(self.data.open(kernel, iterations))
+    }
+
+    fn open_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        self.data.open_inplace(kernel, iterations);
+    }
+
+    fn close(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        Self::Output::from_data(self.data.close(kernel, iterations))
+    }
+
+    fn close_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        self.data.close_inplace(kernel, iterations);
+    }
+
+    fn gradient(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        Self::Output::from_data(self.data.gradient(kernel, iterations))
+    }
+
+    fn gradient_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        self.data.gradient_inplace(kernel, iterations);
+    }
+
+    fn top_hat(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        Self::Output::from_data(self.data.top_hat(kernel, iterations))
+    }
+
+    fn top_hat_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        self.data.top_hat_inplace(kernel, iterations);
+    }
+
+    fn black_hat(&self, kernel: ArrayView2<bool>, iterations: usize) -> Self::Output {
+        Self::Output::from_data(self.data.black_hat(kernel, iterations))
+    }
+
+    fn black_hat_inplace(&mut self, kernel: ArrayView2<bool>, iterations: usize) {
+        self.data.black_hat_inplace(kernel, iterations);
+    }
+
+    fn erode_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Self::Output::from_data(self.data.erode_with(elements))
+    }
+
+    fn dilate_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Self::Output::from_data(self.data.dilate_with(elements))
+    }
+
+    fn open_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Self::Output::from_data(self.data.open_with(elements))
+    }
+
+    fn close_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Self::Output::from_data(self.data.close_with(elements))
+    }
+
+    fn gradient_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Self::Output::from_data(self.data.gradient_with(elements))
+    }
+
+    fn top_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Self::Output::from_data(self.data.top_hat_with(elements))
+    }
+
+    fn black_hat_with(&self, elements: &[StructuringElement]) -> Self::Output {
+        Self::Output::from_data(self.data.black_hat_with(elements))
+    }
 }
 
 #[cfg(test)]
@@ -204,4 +553,239 @@ mod tests {
         a.union_inplace(&b);
         assert_eq!(a, c);
     }
+
+    fn blank(rows: usize, cols: usize) -> Image<bool, Gray> {
+        Image::from_data(Array3::from_elem((rows, cols, 1), false))
+    }
+
+    fn squares() -> Image<bool, Gray> {
+        // A lone pixel, a 3x3 square with a hole and a solid 5x5 square
+        let mut image = blank(14, 12);
+        image.data[[1, 1, 0]] = true;
+        image.data.slice_mut(s![1..4, 5..8, ..]).fill(true);
+        image.data[[2, 6, 0]] = false;
+        image.data.slice_mut(s![7..12, 5..10, ..]).fill(true);
+        image
+    }
+
+    #[test]
+    fn open_and_close() {
+        let kern = Array2::from_elem((3, 3), true);
+        let image = squares();
+
+        let opened = image.open(kern.view(), 1);
+        let mut expected = blank(14, 12);
+        expected.data.slice_mut(s![7..12, 5..10, ..]).fill(true);
+        assert_eq!(opened, expected);
+        // Two iterations behave like a 5x5 kernel
+        let big = Array2::from_elem((5, 5), true);
+        assert_eq!(image.open(kern.view(), 2), image.open(big.view(), 1));
+        assert_eq!(image.open(kern.view(), 0), image);
+
+        let closed = image.close(kern.view(), 1);
+        assert!(closed.data[[2, 6, 0]]);
+        assert_eq!(closed.data.iter().filter(|x| **x).count(), 1 + 9 + 25);
+
+        let mut inplace = image.clone();
+        inplace.open_inplace(kern.view(), 1);
+        assert_eq!(inplace, opened);
+        let mut inplace = image.clone();
+        inplace.close_inplace(kern.view(), 1);
+        assert_eq!(inplace, closed);
+    }
+
+    #[test]
+    fn gradient_and_hats() {
+        let kern = Array2::from_elem((3, 3), true);
+        let image = squares();
+
+        let gradient = image.gradient(kern.view(), 1);
+        // The inside of the solid square isn't part of the gradient
+        assert!(!gradient.data[[9, 7, 0]]);
+        assert!(gradient.data[[7, 5, 0]] && gradient.data[[6, 4, 0]]);
+        assert!(gradient.data[[2, 6, 0]]);
+
+        let top_hat = image.top_hat(kern.view(), 1);
+        let mut small = image.clone();
+        small.data.slice_mut(s![7..12, 5..10, ..]).fill(false);
+        assert_eq!(top_hat, small);
+
+        let black_hat = image.black_hat(kern.view(), 1);
+        assert_eq!(black_hat.data.iter().filter(|x| **x).count(), 1);
+        assert!(black_hat.data[[2, 6, 0]]);
+
+        let mut inplace = image.clone();
+        inplace.gradient_inplace(kern.view(), 1);
+        assert_eq!(inplace, gradient);
+        let mut inplace = image.clone();
+        inplace.top_hat_inplace(kern.view(), 1);
+        assert_eq!(inplace, top_hat);
+        let mut inplace = image;
+        inplace.black_hat_inplace(kern.view(), 1);
+        assert_eq!(inplace, black_hat);
+    }
+
+    #[test]
+    fn asymmetric_kernel() {
+        // An L shaped kernel, opening and closing must be idempotent and
+        // bounded by the image which fails if the kernel isn't reflected
+        let kern = arr2(&[[true, false], [true, true]]);
+        let mut image = blank(8, 8);
+        for (r, c) in [
+            (1, 1),
+            (2, 1),
+            (2, 2),
+            (2, 3),
+            (4, 5),
+            (5, 5),
+            (6, 6),
+            (5, 2),
+        ] {
+            image.data[[r, c, 0]] = true;
+        }
+        let opened = image.open(kern.view(), 1);
+        assert!(opened.data.iter().any(|x| *x));
+        assert!(opened
+            .data
+            .iter()
+            .zip(image.data.iter())
+            .all(|(o, i)| !o || *i));
+        assert_eq!(opened.open(kern.view(), 1), opened);
+
+        let closed = image.close(kern.view(), 1);
+        assert!(closed
+            .data
+            .iter()
+            .zip(image.data.iter())
+            .all(|(c, i)| *c || !i));
+        assert_eq!(closed.close(kern.view(), 1), closed);
+
+        // Dilating a corner pixel also sets the border pixels
+        let mut corner = blank(3, 3);
+        corner.data[[0, 0, 0]] = true;
+        let square = Array2::from_elem((3, 3), true);
+        let dilated = corner.dilate(square.view());
+        assert_eq!(dilated.data.iter().filter(|x| **x).count(), 4);
+    }
+
+    #[test]
+    fn inplace_views_and_channels() {
+        let kern = Array2::from_elem((3, 3), true);
+        let mut image = Array3::from_elem((7, 7, 3), false);
+        image.slice_mut(s![2..5, 2..5, 1]).fill(true);
+        image[[0, 6, 2]] = true;
+        let dilated = image.dilate(kern.view());
+        // Every channel is processed and border pixels are dilated
+        assert_eq!(
+            dilated.slice(s![.., .., 1]).iter().filter(|x| **x).count(),
+            25
+        );
+        assert!(dilated[[1, 5, 2]] && dilated[[0, 5, 2]]);
+        assert!(!dilated.slice(s![.., .., 0]).iter().any(|x| *x));
+
+        // In place operations on views match the allocating operations
+        for iterations in 0..3 {
+            let mut copy = image.clone();
+            copy.view_mut().close_inplace(kern.view(), iterations);
+            assert_eq!(copy, image.close(kern.view(), iterations));
+            let mut copy = image.clone();
+            copy.view_mut().gradient_inplace(kern.view(), iterations);
+            assert_eq!(copy, image.gradient(kern.view(), iterations));
+        }
+        let mut copy = image.clone();
+        copy.slice_mut(s![.., .., 1..2]).dilate_inplace(kern.view());
+        assert_eq!(copy.slice(s![.., .., 1]), dilated.slice(s![.., .., 1]));
+        assert_eq!(copy.slice(s![.., .., 2]), image.slice(s![.., .., 2]));
+    }
+
+    #[test]
+    fn structuring_elements() {
+        let mut image = blank(9, 9);
+        image.data[[4, 4, 0]] = true;
+        // Dilating a single pixel gives the element about its anchor
+        let element = StructuringElement::rect(1, 2).with_anchor((0, 1));
+        let dilated = image.dilate_with(&[element]);
+        assert!(dilated.data[[4, 3, 0]] && dilated.data[[4, 4, 0]]);
+        assert_eq!(dilated.data.iter().filter(|x| **x).count(), 2);
+        let line = StructuringElement::line(3, std::f64::consts::FRAC_PI_4);
+        let dilated = image.dilate_with(&[line]);
+        assert!(dilated.data[[3, 5, 0]] && dilated.data[[5, 3, 0]]);
+
+        let kern = Array2::from_elem((3, 3), true);
+        let square = StructuringElement::rect(3, 3);
+        let squares = squares();
+        assert_eq!(
+            squares.erode_with(std::slice::from_ref(&square)),
+            squares.erode(kern.view())
+        );
+        assert_eq!(squares.dilate_with(&[square]), squares.dilate(kern.view()));
+    }
+
+    #[test]
+    fn element_compound_operations() {
+        let image = squares();
+        let kern = arr2(&[[true, false], [true, true]]);
+        let element = StructuringElement::centred(kern.clone());
+        let single = std::slice::from_ref(&element);
+        assert_eq!(image.open_with(single), image.open(kern.view(), 1));
+        assert_eq!(image.close_with(single), image.close(kern.view(), 1));
+        assert_eq!(image.gradient_with(single), image.gradient(kern.view(), 1));
+        assert_eq!(image.top_hat_with(single), image.top_hat(kern.view(), 1));
+        assert_eq!(
+            image.black_hat_with(single),
+            image.black_hat(kern.view(), 1)
+        );
+        let sequence = StructuringElement::rect_sequence(3, 4);
+        let rect = Array2::from_elem((3, 4), true);
+        assert_eq!(image.open_with(&sequence), image.open(rect.view(), 1));
+        assert_eq!(image.close_with(&sequence), image.close(rect.view(), 1));
+
+        // The anchor moves the erosion and dilation but not the opening
+        let mut point = blank(5, 5);
+        point.data[[2, 2, 0]] = true;
+        let moved = StructuringElement::rect(1, 2).with_anchor((0, 1));
+        let moved = std::slice::from_ref(&moved);
+        let gradient = point.gradient_with(moved);
+        assert!(gradient.data[[2, 1, 0]] && gradient.data[[2, 2, 0]]);
+        assert_eq!(gradient.data.iter().filter(|x| **x).count(), 2);
+        let centred = [StructuringElement::rect(1, 2)];
+        assert_ne!(gradient, point.gradient_with(&centred));
+        assert_eq!(image.open_with(moved), image.open_with(&centred));
+        assert_eq!(image.close_with(moved), image.close_with(&centred));
+    }
+
+    #[test]
+    fn element_decompositions() {
+        let image = squares();
+        let rect = StructuringElement::rect(3, 4);
+        let sequence = StructuringElement::rect_sequence(3, 4);
+        assert_eq!(
+            image.dilate_with(&sequence),
+            image.dilate_with(std::slice::from_ref(&rect))
+        );
+        let dilated = image.dilate_with(std::slice::from_ref(&rect));
+        assert_eq!(dilated.erode_with(&sequence), dilated.erode_with(&[rect]));
+
+        let mut point = blank(21, 21);
+        point.data[[10, 10, 0]] = true;
+        let shape = |elements: &[StructuringElement]| {
+            let dilated = point.dilate_with(elements);
+            dilated.data.slice(s![.., .., 0]).to_owned()
+        };
+        let diamond = StructuringElement::diamond(4);
+        assert_eq!(
+            shape(&StructuringElement::diamond_sequence(4)),
+            shape(&[diamond])
+        );
+
+        // The disk approximation has the same extent and a similar area
+        let disk = shape(&[StructuringElement::disk(9)]);
+        let approx = shape(&StructuringElement::disk_sequence(9));
+        for x in [&disk, &approx] {
+            assert!(x.row(1).iter().any(|x| *x) && x.column(19).iter().any(|x| *x));
+            assert!(!x.row(0).iter().any(|x| *x) && !x.column(20).iter().any(|x| *x));
+        }
+        let area = |x: &Array2<bool>| x.iter().filter(|x| **x).count() as f64;
+        assert!((area(&approx) - area(&disk)).abs() < 0.1 * area(&disk));
+    }
 }
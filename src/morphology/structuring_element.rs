@@ -0,0 +1,251 @@
+use crate::core::util::kernel_centre;
+use ndarray::prelude::*;
+
+/// A binary structuring element with an anchor, the pixel of the element
+/// placed over the pixel being processed
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct StructuringElement {
+    /// Pixels which are part of the element
+    pub data: Array2<bool>,
+    /// Row and column of the anchor, this doesn't have to be set or even
+    /// inside the element
+    pub anchor: (usize, usize),
+}
+
+impl StructuringElement {
+    /// Creates an element from the data with the given anchor
+    pub fn new(data: Array2<bool>, anchor: (usize, usize)) -> Self {
+        Self { data, anchor }
+    }
+
+    /// Creates an element from the data anchored at its centre, for even sizes
+    /// the centre is the pixel before the middle as for the kernels
+    pub fn centred(data: Array2<bool>) -> Self {
+        let anchor = kernel_centre(data.nrows().max(1), data.ncols().max(1));
+        Self { data, anchor }
+    }
+
+    /// Returns the element with the anchor moved
+    pub fn with_anchor(self, anchor: (usize, usize)) -> Self {
+        Self { anchor, ..self }
+    }
+
+    /// A filled rectangle
+    pub fn rect(rows: usize, cols: usize) -> Self {
+        Self::centred(Array2::from_elem((rows, cols), true))
+    }
+
+    /// The middle row and column of a rectangle
+    pub fn cross(rows: usize, cols: usize) -> Self {
+        let (ro, co) = kernel_centre(rows.max(1), cols.max(1));
+        Self::centred(Array2::from_shape_fn((rows, cols), |(r, c)| {
+            r == ro || c == co
+        }))
+    }
+
+    /// A disk of the given radius with a width of `2 * radius + 1`
+    pub fn disk(radius: usize) -> Self {
+        Self::ellipse(radius, radius)
+    }
+
+    /// An ellipse with the given radii along the rows and columns
+    pub fn ellipse(row_radius: usize, col_radius: usize) -> Self {
+        let (a, b) = (row_radius as f64, col_radius as f64);
+        let axis = |x: f64, radius: f64| if radius > 0.0 { x / radius } else { x };
+        Self::centred(Array2::from_shape_fn(
+            (2 * row_radius + 1, 2 * col_radius + 1),
+            |(r, c)| {
+                let r = axis(r as f64 - a, a);
+                let c = axis(c as f64 - b, b);
+                r * r + c * c <= 1.0
+            },
+        ))
+    }
+
+    /// Pixels within the given city block distance of the centre
+    pub fn diamond(radius: usize) -> Self {
+        Self::centred(Array2::from_shape_fn(
+            (2 * radius + 1, 2 * radius + 1),
+            |(r, c)| r.abs_diff(radius) + c.abs_diff(radius) <= radius,
+        ))
+    }
+
+    /// A line of `length` pixels through the anchor at `angle` radians
+    /// anticlockwise from the columns axis. The line steps one pixel at a
+    /// time along the axis closest to the angle, for even lengths the anchor
+    /// is the pixel before the middle.
+    pub fn line(length: usize, angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        // Scale so the dominant axis moves exactly one pixel per step
+        let scale = sin.abs().max(cos.abs());
+        let middle = (length.max(1) - 1) / 2;
+        let points = (0..length.max(1))
+            .map(|i| {
+                let t = (i as isize - middle as isize) as f64;
+                // Rows go down the image
+                (
+                    -(t * sin / scale).round() as isize,
+                    (t * cos / scale).round() as isize,
+                )
+            })
+            .collect::<Vec<_>>();
+        let min_r = points.iter().map(|p| p.0).min().unwrap_or(0).min(0);
+        let min_c = points.iter().map(|p| p.1).min().unwrap_or(0).min(0);
+        let max_r = points.iter().map(|p| p.0).max().unwrap_or(0).max(0);
+        let max_c = points.iter().map(|p| p.1).max().unwrap_or(0).max(0);
+        let shape = ((max_r - min_r + 1) as usize, (max_c - min_c + 1) as usize);
+        let mut data = Array2::from_elem(shape, false);
+        for (r, c) in points {
+            data[[(r - min_r) as usize, (c - min_c) as usize]] = true;
+        }
+        Self::new(data, ((-min_r) as usize, (-min_c) as usize))
+    }
+
+    /// An octagon with horizontal and vertical sides of `side` pixels and
+    /// diagonal sides spanning `slant` pixels, the width is `side + 2 * slant`
+    pub fn octagon(side: usize, slant: usize) -> Self {
+        let size = side + 2 * slant;
+        Self::centred(Array2::from_shape_fn((size, size), |(r, c)| {
+            let dr = r.min(size - 1 - r);
+            let dc = c.min(size - 1 - c);
+            dr + dc >= slant
+        }))
+    }
+
+    /// A rectangle as a column followed by a row, applying these in sequence
+    /// gives the same result as the rectangle with fewer comparisons
+    pub fn rect_sequence(rows: usize, cols: usize) -> Vec<Self> {
+        vec![Self::rect(rows, 1), Self::rect(1, cols)]
+    }
+
+    /// A diamond as a sequence of 3x3 crosses, applying these in sequence
+    /// gives the same result as the diamond
+    pub fn diamond_sequence(radius: usize) -> Vec<Self> {
+        if radius == 0 {
+            vec![Self::rect(1, 1)]
+        } else {
+            vec![Self::cross(3, 3); radius]
+        }
+    }
+
+    /// Approximates a disk with a sequence of 3x3 squares and crosses which
+    /// combine into an octagon with the same width and a similar area
+    pub fn disk_sequence(radius: usize) -> Vec<Self> {
+        if radius == 0 {
+            return vec![Self::rect(1, 1)];
+        }
+        // The octagon cuts a triangle with legs of `radius - squares` from each
+        // corner of the square, this is chosen to match the area of the disk
+        let cut = (2.0 - std::f64::consts::FRAC_PI_2).sqrt();
+        let squares = (radius as f64 * (1.0 - cut)).round() as usize;
+        let mut sequence = vec![Self::rect(3, 3); squares];
+        sequence.extend(vec![Self::cross(3, 3); radius - squares]);
+        sequence
+    }
+
+    /// Offsets of the set pixels from the anchor
+    pub(crate) fn offsets(&self) -> Vec<(isize, isize)> {
+        let (ar, ac) = (self.anchor.0 as isize, self.anchor.1 as isize);
+        self.data
+            .indexed_iter()
+            .filter(|(_, x)| **x)
+            .map(|((r, c), _)| (r as isize - ar, c as isize - ac))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ndarray::arr2;
+    use std::f64::consts::{FRAC_PI_2, FRAC_PI_4};
+
+    fn count(element: &StructuringElement) -> usize {
+        element.data.iter().filter(|x| **x).count()
+    }
+
+    #[test]
+    fn element_shapes() {
+        let rect = StructuringElement::rect(3, 4);
+        assert_eq!(rect.data.dim(), (3, 4));
+        assert_eq!(rect.anchor, (1, 1));
+        assert_eq!(count(&rect), 12);
+
+        let cross = StructuringElement::cross(3, 3);
+        assert_eq!(
+            cross.data,
+            arr2(&[
+                [false, true, false],
+                [true, true, true],
+                [false, true, false]
+            ])
+        );
+
+        let disk = StructuringElement::disk(2);
+        assert_eq!(disk.anchor, (2, 2));
+        assert_eq!(count(&disk), 13);
+        assert_eq!(disk, StructuringElement::ellipse(2, 2));
+        let ellipse = StructuringElement::ellipse(1, 3);
+        assert_eq!(ellipse.data.dim(), (3, 7));
+        assert!(ellipse.data[[1, 0]] && !ellipse.data[[0, 0]]);
+        assert_eq!(
+            StructuringElement::ellipse(0, 2).data,
+            Array2::from_elem((1, 5), true)
+        );
+
+        let diamond = StructuringElement::diamond(2);
+        assert_eq!(count(&diamond), 13);
+        assert!(!diamond.data[[1, 0]] && diamond.data[[2, 0]]);
+        assert_eq!(StructuringElement::diamond(1), cross);
+
+        let octagon = StructuringElement::octagon(3, 2);
+        assert_eq!(octagon.data.dim(), (7, 7));
+        assert_eq!(octagon.anchor, (3, 3));
+        // Each corner loses a triangle of 3 pixels
+        assert_eq!(count(&octagon), 49 - 4 * 3);
+        assert_eq!(
+            StructuringElement::octagon(3, 0),
+            StructuringElement::rect(3, 3)
+        );
+    }
+
+    #[test]
+    fn lines_and_anchors() {
+        let line = StructuringElement::line(5, 0.0);
+        assert_eq!(line, StructuringElement::rect(1, 5));
+        let line = StructuringElement::line(5, FRAC_PI_2);
+        assert_eq!(line, StructuringElement::rect(5, 1));
+        let line = StructuringElement::line(3, FRAC_PI_4);
+        assert_eq!(
+            line.data,
+            arr2(&[
+                [false, false, true],
+                [false, true, false],
+                [true, false, false]
+            ])
+        );
+        assert_eq!(line.anchor, (1, 1));
+        assert_eq!(count(&StructuringElement::line(7, 0.3)), 7);
+        // Even lengths don't repeat pixels
+        let line = StructuringElement::line(2, (0.6f64).atan2(0.8));
+        assert_eq!(line.data, arr2(&[[false, true], [true, false]]));
+        assert_eq!(line.anchor, (1, 0));
+        assert_eq!(
+            StructuringElement::line(4, 0.0),
+            StructuringElement::rect(1, 4)
+        );
+        for length in 1..10 {
+            for angle in [0.2, 0.7, FRAC_PI_4, 1.2, FRAC_PI_2, 2.5] {
+                let line = StructuringElement::line(length, angle);
+                assert_eq!(count(&line), length);
+            }
+        }
+
+        let moved = StructuringElement::rect(1, 2).with_anchor((0, 1));
+        assert_eq!(moved.offsets(), vec![(0, -1), (0, 0)]);
+        assert_eq!(
+            StructuringElement::rect(1, 2).offsets(),
+            vec![(0, 0), (0, 1)]
+        );
+    }
+}
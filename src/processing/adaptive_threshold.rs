@@ -0,0 +1,361 @@
+use crate::core::{ColourModel, Image, ImageBase};
+use crate::processing::integral::box_mean;
+use crate::processing::*;
+use ndarray::prelude::*;
+use ndarray::{Data, Zip};
+use num_traits::ToPrimitive;
+
+/// Method used to calculate the threshold of each pixel from the window around
+/// it. In the formulas `m` is the local mean and `s` the local standard
+/// deviation.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum AdaptiveThreshold {
+    /// `m - offset`
+    Mean {
+        /// Subtracted from the local mean
+        offset: f64,
+    },
+    /// Gaussian weighted local mean minus `offset`, the standard deviation of
+    /// the Gaussian is chosen from the window size
+    Gaussian {
+        /// Subtracted from the weighted local mean
+        offset: f64,
+    },
+    /// Niblack's method `m + k * s`
+    Niblack {
+        /// Weight of the standard deviation, typically -0.2
+        k: f64,
+    },
+    /// Sauvola's method `m * (1 + k * (s / r - 1))`
+    Sauvola {
+        /// Sensitivity, typically in the range 0.2 to 0.5
+        k: f64,
+        /// Dynamic range of the standard deviation, 128 for 8 bit images
+        r: f64,
+    },
+    /// Wolf and Jolion's method `m - k * (1 - s / R) * (m - M)` where `R` is
+    /// the largest local standard deviation and `M` the smallest value in the
+    /// channel
+    WolfJolion {
+        /// Sensitivity, typically 0.5
+        k: f64,
+    },
+    /// Bernsen's method, the midpoint of the local minimum and maximum. Windows
+    /// with a contrast (maximum minus minimum) below `contrast_threshold` are
+    /// treated as a single class, decided by comparing the midpoint with the
+    /// midpoint of the channel's range.
+    Bernsen {
+        /// Minimum local contrast for the local midpoint to be used
+        contrast_threshold: f64,
+    },
+}
+
+/// Runs a locally adaptive threshold on a type `T`. These handle uneven
+/// lighting where a single global threshold fails.
+pub trait ThresholdAdaptiveExt<T> {
+    /// The adaptive thresholding output is a binary image.
+    type Output;
+
+    /// Run the adaptive threshold with a square window of the given odd size.
+    /// A pixel is foreground (true) if it's greater than the threshold
+    /// calculated from the window around it. Windows are cropped at the image
+    /// borders and each channel is thresholded independently.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidDimensions` error if the window size is even or
+    /// smaller than 3 and an `InvalidParameter` error if a method parameter is
+    /// out of range.
+    fn threshold_adaptive(
+        &self,
+        method: AdaptiveThreshold,
+        window: usize,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// Minimum and maximum of every square window, cropped at the borders. The
+/// window is separable so the rows and columns are processed in turn
+fn box_extrema(data: ArrayView2<f64>, radius: usize) -> (Array2<f64>, Array2<f64>) {
+    let pass = |data: ArrayView2<f64>, axis: Axis| {
+        let len = data.len_of(axis);
+        let mut min = data.to_owned();
+        let mut max = data.to_owned();
+        for i in 0..len {
+            let window = data.slice_axis(
+                axis,
+                (i.saturating_sub(radius)..(i + radius + 1).min(len)).into(),
+            );
+            for (lane, (lo, hi)) in window.lanes(axis).into_iter().zip(
+                min.index_axis_mut(axis, i)
+                    .iter_mut()
+                    .zip(max.index_axis_mut(axis, i).iter_mut()),
+            ) {
+                *lo = lane.iter().cloned().fold(f64::INFINITY, f64::min);
+                *hi = lane.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            }
+        }
+        (min, max)
+    };
+    let (min, max) = pass(data, Axis(0));
+    (pass(min.view(), Axis(1)).0, pass(max.view(), Axis(1)).1)
+}
+
+/// Weighted mean of every window with the kernel's weights, the window is
+/// cropped at the borders and the weights renormalised over what remains
+fn cropped_weighted_mean(data: ArrayView2<f64>, kernel: ArrayView2<f64>) -> Array2<f64> {
+    let (rows, cols) = data.dim();
+    let (k_rows, k_cols) = kernel.dim();
+    let (r_off, c_off) = (k_rows / 2, k_cols / 2);
+    Array2::from_shape_fn((rows, cols), |(r, c)| {
+        let (mut sum, mut weight) = (0.0, 0.0);
+        for ((kr, kc), w) in kernel.indexed_iter() {
+            let (rr, cc) = ((r + kr).wrapping_sub(r_off), (c + kc).wrapping_sub(c_off));
+            if rr < rows && cc < cols {
+                sum += w * data[[rr, cc]];
+                weight += w;
+            }
+        }
+        sum / weight
+    })
+}
+
+fn local_thresholds(
+    data: ArrayView2<f64>,
+    method: AdaptiveThreshold,
+    window: usize,
+) -> Result<Array2<f64>, Error> {
+    let radius = window / 2;
+    let mean = || box_mean(data.insert_axis(Axis(2)), radius).index_axis_move(Axis(2), 0);
+    let deviation = |mean: &Array2<f64>| {
+        let squares = data.mapv(|x| x * x).insert_axis(Axis(2));
+        let squares = box_mean(squares.view(), radius).index_axis_move(Axis(2), 0);
+        // Rounding can make the variance slightly negative in flat regions
+        Zip::from(&squares)
+            .and(mean)
+            .map_collect(|s, m| (s - m * m).max(0.0).sqrt())
+    };
+    let thresholds = match method {
+        AdaptiveThreshold::Mean { offset } => mean() - offset,
+        AdaptiveThreshold::Gaussian { offset } => {
+            let kernel: Array3<f64> = GaussianFilter::build((window, window, 1))?;
+            cropped_weighted_mean(data, kernel.index_axis(Axis(2), 0)) - offset
+        }
+        AdaptiveThreshold::Niblack { k } => {
+            let mean = mean();
+            let deviation = deviation(&mean);
+            mean + deviation * k
+        }
+        AdaptiveThreshold::Sauvola { k, r } => {
+            if r <= 0.0 {
+                return Err(Error::InvalidParameter);
+            }
+            let mean = mean();
+            let deviation = deviation(&mean);
+            Zip::from(&mean)
+                .and(&deviation)
+                .map_collect(|m, s| m * (1.0 + k * (s / r - 1.0)))
+        }
+        AdaptiveThreshold::WolfJolion { k } => {
+            let mean = mean();
+            let deviation = deviation(&mean);
+            let min = data.iter().cloned().fold(f64::INFINITY, f64::min);
+            let max_deviation = deviation.iter().cloned().fold(0.0, f64::max);
+            Zip::from(&mean).and(&deviation).map_collect(|m, s| {
+                let ratio = if max_deviation > 0.0 {
+                    s / max_deviation
+                } else {
+                    0.0
+                };
+                m - k * (1.0 - ratio) * (m - min)
+            })
+        }
+        AdaptiveThreshold::Bernsen { contrast_threshold } => {
+            if contrast_threshold < 0.0 {
+                return Err(Error::InvalidParameter);
+            }
+            let (min, max) = box_extrema(data, radius);
+            let low = data.iter().cloned().fold(f64::INFINITY, f64::min);
+            let high = data.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+            let global_mid = (low + high) / 2.0;
+            Zip::from(&min).and(&max).map_collect(|lo, hi| {
+                let mid = (lo + hi) / 2.0;
+                if hi - lo >= contrast_threshold {
+                    mid
+                } else if mid >= global_mid {
+                    // The whole window is foreground
+                    f64::NEG_INFINITY
+                } else {
+                    f64::INFINITY
+                }
+            })
+        }
+    };
+    Ok(thresholds)
+}
+
+impl<T, U> ThresholdAdaptiveExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    type Output = Array3<bool>;
+
+    fn threshold_adaptive(
+        &self,
+        method: AdaptiveThreshold,
+        window: usize,
+    ) -> Result<Self::Output, Error> {
+        if window < 3 || window & 1 == 0 {
+            return Err(Error::InvalidDimensions);
+        }
+        let data = self
+            .iter()
+            .map(|x| x.to_f64())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::NumericError)?;
+        let data = Array3::from_shape_vec(self.dim(), data).unwrap();
+        let mut result = Array3::from_elem(self.dim(), false);
+        for (channel, mut output) in data.axis_iter(Axis(2)).zip(result.axis_iter_mut(Axis(2))) {
+            let thresholds = local_thresholds(channel, method, window)?;
+            Zip::from(&mut output)
+                .and(&channel)
+                .and(&thresholds)
+                .for_each(|o, x, t| *o = x > t);
+        }
+        Ok(result)
+    }
+}
+
+impl<T, U, C> ThresholdAdaptiveExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    type Output = Image<bool, C>;
+
+    fn threshold_adaptive(
+        &self,
+        method: AdaptiveThreshold,
+        window: usize,
+    ) -> Result<Self::Output, Error> {
+        let data = self.data.threshold_adaptive(method, window)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, RGB};
+
+    /// A brightness ramp across the columns with dark 3x3 marks on it
+    fn uneven_document() -> (Image<u8, Gray>, Array2<bool>) {
+        let mut image = Image::<u8, Gray>::new(40, 40);
+        let mut text = Array2::from_elem((40, 40), false);
+        for ((_, c, _), x) in image.data.indexed_iter_mut() {
+            *x = 100 + c as u8;
+        }
+        for (r, c) in &[(5, 5), (5, 30), (20, 18), (33, 3), (33, 35)] {
+            image
+                .data
+                .slice_mut(s![*r..*r + 3, *c..*c + 3, ..])
+                .mapv_inplace(|x| x - 60);
+            text.slice_mut(s![*r..*r + 3, *c..*c + 3]).fill(true);
+        }
+        (image, text)
+    }
+
+    #[test]
+    fn adaptive_uneven_lighting() {
+        let (image, text) = uneven_document();
+        // A global threshold loses the dark end of the background
+        let mean = image.data.iter().map(|x| *x as f64).sum::<f64>() / 1600.0;
+        let global = image.threshold_apply(mean, f64::INFINITY).unwrap();
+        assert!(global.data.iter().zip(text.iter()).any(|(g, t)| !g && !t));
+
+        for method in &[
+            AdaptiveThreshold::Mean { offset: 10.0 },
+            AdaptiveThreshold::Gaussian { offset: 10.0 },
+            AdaptiveThreshold::Sauvola { k: 0.2, r: 128.0 },
+            AdaptiveThreshold::WolfJolion { k: 0.5 },
+            AdaptiveThreshold::Bernsen {
+                contrast_threshold: 15.0,
+            },
+        ] {
+            let result = image.threshold_adaptive(*method, 15).unwrap();
+            for ((r, c, _), x) in result.data.indexed_iter() {
+                assert_eq!(*x, !text[[r, c]], "{:?} at {:?}", method, (r, c));
+            }
+        }
+
+        // Niblack marks background as foreground where the cropped window's
+        // mean is above the pixel so only the interior is checked
+        let result = image
+            .threshold_adaptive(AdaptiveThreshold::Niblack { k: -0.2 }, 15)
+            .unwrap();
+        for ((r, c, _), x) in result.data.slice(s![.., 7..33, ..]).indexed_iter() {
+            assert_eq!(*x, !text[[r, c + 7]], "{:?}", (r, c + 7));
+        }
+    }
+
+    #[test]
+    fn gaussian_window_cropped() {
+        let mut data = Array2::from_elem((5, 5), 1.0);
+        data.row_mut(0).fill(0.0);
+        let thresholds =
+            local_thresholds(data.view(), AdaptiveThreshold::Gaussian { offset: 0.0 }, 3).unwrap();
+        let kernel: Array3<f64> = GaussianFilter::build((3, 3, 1)).unwrap();
+        let kernel = kernel.slice_move(s![1usize.., 1usize.., 0usize]);
+        // Only the part of the window inside the image is used for the corner
+        let expected = (kernel[[1, 0]] + kernel[[1, 1]]) / kernel.sum();
+        assert!((thresholds[[0, 0]] - expected).abs() < 1e-10);
+        assert!((thresholds[[4, 4]] - 1.0).abs() < 1e-10);
+    }
+
+    #[test]
+    fn adaptive_channels_and_errors() {
+        let (image, text) = uneven_document();
+        let mut colour = Image::<u8, RGB>::new(40, 40);
+        for chan in 0..3 {
+            colour
+                .data
+                .slice_mut(s![.., .., chan])
+                .assign(&image.data.index_axis(Axis(2), 0));
+        }
+        colour
+            .data
+            .slice_mut(s![.., .., 1])
+            .mapv_inplace(|x| 255 - x);
+        let method = AdaptiveThreshold::Mean { offset: 10.0 };
+        let result = colour.threshold_adaptive(method, 15).unwrap();
+        assert_eq!(result.data.index_axis(Axis(2), 0), text.mapv(|t| !t));
+        assert_eq!(result.data.index_axis(Axis(2), 2), text.mapv(|t| !t));
+        // Inverted the marks are brighter than their surroundings
+        let inverted = result.data.index_axis(Axis(2), 1);
+        assert!(Zip::from(&inverted).and(&text).all(|x, t| !t || *x));
+
+        assert_eq!(
+            image.threshold_adaptive(method, 4),
+            Err(Error::InvalidDimensions)
+        );
+        assert_eq!(
+            image.threshold_adaptive(AdaptiveThreshold::Sauvola { k: 0.2, r: 0.0 }, 15),
+            Err(Error::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn local_extrema() {
+        let data = arr2(&[[1.0, 5.0, 2.0], [0.0, 3.0, 4.0], [6.0, 1.0, 1.0]]);
+        let (min, max) = box_extrema(data.view(), 1);
+        assert_eq!(
+            min,
+            arr2(&[[0.0, 0.0, 2.0], [0.0, 0.0, 1.0], [0.0, 0.0, 1.0]])
+        );
+        assert_eq!(
+            max,
+            arr2(&[[5.0, 5.0, 5.0], [6.0, 6.0, 5.0], [6.0, 6.0, 4.0]])
+        );
+    }
+}
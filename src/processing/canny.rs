@@ -1,22 +1,48 @@
-use crate::core::{ColourModel, Image, ImageBase};
+use crate::core::{ColourModel, Gray, Image, ImageBase};
+use crate::processing::hysteresis::hysteresis;
 use crate::processing::*;
 use ndarray::prelude::*;
-use ndarray::{DataMut, IntoDimension};
+use ndarray::{Data, DataMut, IntoDimension, Zip};
 use num_traits::{cast::FromPrimitive, real::Real, Num, NumAssignOps};
-use std::collections::HashSet;
-use std::marker::PhantomData;
+use std::cmp::Ordering;
 
 /// Runs the Canny Edge Detector algorithm on a type T
 pub trait CannyEdgeDetectorExt<T> {
     /// Output type, this is different as canny outputs a binary image
     type Output;
 
-    /// Run the edge detection algorithm with the given parameters. Due to Canny
-    /// being specified as working on greyscale images all current implementations
-    /// assume a single channel image returning an error otherwise.
+    /// Run the edge detection algorithm with the given parameters. Images with
+    /// multiple channels use the gradient of the channel with the largest
+    /// magnitude at each pixel, the output is always a single channel.
     fn canny_edge_detector(&self, params: CannyParameters<T>) -> Result<Self::Output, Error>;
 }
 
+/// Runs the Canny Edge Detector also returning the gradient orientation
+pub trait CannyOrientationExt<T>: CannyEdgeDetectorExt<T> {
+    /// Type of the gradient orientation map
+    type Orientation;
+
+    /// Run the edge detection algorithm also returning the orientation of the
+    /// gradient in radians as `atan2(dy, dx)` where `dx` is the derivative
+    /// along the columns and `dy` along the rows.
+    fn canny_edge_detector_with_orientation(
+        &self,
+        params: CannyParameters<T>,
+    ) -> Result<(Self::Output, Self::Orientation), Error>;
+}
+
+/// Methods to automatically select the Canny thresholds from the image. The
+/// thresholds are calculated from the pixel intensities of the input image.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum CannyAutoThreshold<T> {
+    /// Given `sigma` the thresholds are `(1 - sigma) * median` and
+    /// `(1 + sigma) * median`, a sigma of 0.33 is a common choice
+    Median(T),
+    /// The upper threshold is found with Otsu's method and the lower threshold
+    /// is half of it
+    Otsu,
+}
+
 /// Builder to construct the Canny parameters, if a parameter is not selected then
 /// a sensible default is chosen
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
@@ -24,10 +50,13 @@ pub struct CannyBuilder<T> {
     blur: Option<Array3<T>>,
     t1: Option<T>,
     t2: Option<T>,
+    auto_threshold: Option<CannyAutoThreshold<T>>,
 }
 
-/// Parameters for the Canny Edge Detector
+/// Parameters for the Canny Edge Detector, these are created with the
+/// `CannyBuilder`
 #[derive(Clone, Eq, PartialEq, Hash, Debug)]
+#[non_exhaustive]
 pub struct CannyParameters<T> {
     /// By default this library uses a Gaussian blur, although other kernels can
     /// be substituted
@@ -36,6 +65,9 @@ pub struct CannyParameters<T> {
     pub t1: T,
     /// Upper threshold defining a strong edge
     pub t2: T,
+    /// If set the thresholds are calculated from the image and `t1` and `t2`
+    /// are ignored
+    pub auto_threshold: Option<CannyAutoThreshold<T>>,
 }
 
 impl<T, U, C> CannyEdgeDetectorExt<T> for ImageBase<U, C>
@@ -44,14 +76,28 @@ where
     T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
     C: ColourModel,
 {
-    type Output = Image<bool, C>;
+    type Output = Image<bool, Gray>;
 
     fn canny_edge_detector(&self, params: CannyParameters<T>) -> Result<Self::Output, Error> {
         let data = self.data.canny_edge_detector(params)?;
-        Ok(Self::Output {
-            data,
-            model: PhantomData,
-        })
+        Ok(Image::from_data(data))
+    }
+}
+
+impl<T, U, C> CannyOrientationExt<T> for ImageBase<U, C>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+    C: ColourModel,
+{
+    type Orientation = Image<T, Gray>;
+
+    fn canny_edge_detector_with_orientation(
+        &self,
+        params: CannyParameters<T>,
+    ) -> Result<(Self::Output, Self::Orientation), Error> {
+        let (edges, orientation) = self.data.canny_edge_detector_with_orientation(params)?;
+        Ok((Image::from_data(edges), Image::from_data(orientation)))
     }
 }
 
@@ -63,20 +109,182 @@ where
     type Output = Array3<bool>;
 
     fn canny_edge_detector(&self, params: CannyParameters<T>) -> Result<Self::Output, Error> {
-        if self.shape()[2] > 1 {
-            Err(Error::ChannelDimensionMismatch)
-        } else {
-            // apply blur
-            let blurred = self.conv2d(params.blur.view())?;
-            let (mag, rot) = blurred.full_sobel()?;
+        Ok(canny_with_gradient(self, params)?.edges)
+    }
+}
+
+impl<T, U> CannyOrientationExt<T> for ArrayBase<U, Ix3>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+{
+    type Orientation = Array3<T>;
+
+    fn canny_edge_detector_with_orientation(
+        &self,
+        params: CannyParameters<T>,
+    ) -> Result<(Self::Output, Self::Orientation), Error> {
+        let output = canny_with_gradient(self, params)?;
+        Ok((output.edges, output.orientation))
+    }
+}
+
+/// The edges found by Canny along with the gradient used to find them
+pub(crate) struct CannyOutput<T> {
+    /// Linked edges
+    pub edges: Array3<bool>,
+    /// Gradient magnitude before non-maxima suppression
+    pub magnitude: Array3<T>,
+    /// Gradient orientation as `atan2(dy, dx)`
+    pub orientation: Array3<T>,
+}
+
+/// Runs the Canny edge detector keeping the intermediate gradient
+pub(crate) fn canny_with_gradient<T, U>(
+    image: &ArrayBase<U, Ix3>,
+    params: CannyParameters<T>,
+) -> Result<CannyOutput<T>, Error>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+{
+    let channels = image.shape()[2];
+    if channels == 0 {
+        return Err(Error::ChannelDimensionMismatch);
+    }
+    let (t1, t2) = match params.auto_threshold {
+        Some(method) => auto_thresholds(image, method)?,
+        None => (params.t1, params.t2),
+    };
+    // apply blur, a single channel kernel is used for every channel
+    let blur = if params.blur.shape()[2] == 1 {
+        let (rows, cols, _) = params.blur.dim();
+        Array3::from_shape_fn((rows, cols, channels), |(r, c, _)| params.blur[[r, c, 0]])
+    } else {
+        params.blur
+    };
+    let blurred = image.conv2d(blur.view())?;
+    let (magnitude, rot) = strongest_channel(blurred.full_sobel()?);
+    // The sobel rotation is atan2(-dx, -dy)
+    let mut orientation = rot.mapv(|x| (-x.cos()).atan2(-x.sin()));
+    Zip::from(&mut orientation)
+        .and(&magnitude)
+        .for_each(|o, m| *o = if m.is_zero() { T::zero() } else { *o });
+
+    let suppressed = non_maxima_supression(magnitude.clone(), orientation.view());
+
+    Ok(CannyOutput {
+        edges: link_edges(suppressed, t1, t2),
+        magnitude,
+        orientation,
+    })
+}
 
-            let mag = non_maxima_supression(mag, rot.view());
+/// Combines the per channel sobel output into a single channel by taking the
+/// channel with the largest magnitude at each pixel
+fn strongest_channel<T>((mag, rot): (Array3<T>, Array3<T>)) -> (Array3<T>, Array3<T>)
+where
+    T: Copy + Real,
+{
+    let (rows, cols, channels) = mag.dim();
+    if channels == 1 {
+        return (mag, rot);
+    }
+    let mut best_mag = mag.slice(s![.., .., 0..1]).to_owned();
+    let mut best_rot = rot.slice(s![.., .., 0..1]).to_owned();
+    for r in 0..rows {
+        for c in 0..cols {
+            for chan in 1..channels {
+                if mag[[r, c, chan]] > best_mag[[r, c, 0]] {
+                    best_mag[[r, c, 0]] = mag[[r, c, chan]];
+                    best_rot[[r, c, 0]] = rot[[r, c, chan]];
+                }
+            }
+        }
+    }
+    (best_mag, best_rot)
+}
+
+/// Calculates the lower and upper thresholds from the image intensities
+fn auto_thresholds<T, U>(
+    image: &ArrayBase<U, Ix3>,
+    method: CannyAutoThreshold<T>,
+) -> Result<(T, T), Error>
+where
+    U: Data<Elem = T>,
+    T: Copy + FromPrimitive + Real,
+{
+    let mut values = image
+        .iter()
+        .map(|x| x.to_f64())
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Error::NumericError)?;
+    if values.is_empty() {
+        return Err(Error::InvalidDimensions);
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+    let (lower, upper) = match method {
+        CannyAutoThreshold::Median(sigma) => {
+            let sigma = sigma.to_f64().ok_or(Error::NumericError)?;
+            if sigma < 0.0 {
+                return Err(Error::InvalidParameter);
+            }
+            let n = values.len();
+            let median = if n % 2 == 0 {
+                (values[n / 2 - 1] + values[n / 2]) / 2.0
+            } else {
+                values[n / 2]
+            };
+            (((1.0 - sigma) * median).max(0.0), (1.0 + sigma) * median)
+        }
+        CannyAutoThreshold::Otsu => {
+            let upper = otsu_level(&values);
+            (upper / 2.0, upper)
+        }
+    };
+    let convert = |x: f64| T::from_f64(x).ok_or(Error::NumericError);
+    Ok((convert(lower)?, convert(upper)?))
+}
 
-            Ok(link_edges(mag, params.t1, params.t2))
+/// Otsu's threshold for sorted floating point values using a 256 bin histogram
+/// between the minimum and maximum value
+fn otsu_level(sorted: &[f64]) -> f64 {
+    const BINS: usize = 256;
+    let min = sorted[0];
+    let max = sorted[sorted.len() - 1];
+    if max <= min {
+        return min;
+    }
+    let bin_width = (max - min) / BINS as f64;
+    let mut counts = [0.0; BINS];
+    for x in sorted {
+        let bin = (((x - min) / bin_width) as usize).min(BINS - 1);
+        counts[bin] += 1.0;
+    }
+    let total = sorted.len() as f64;
+    let sum_intensity: f64 = counts.iter().enumerate().map(|(i, c)| i as f64 * c).sum();
+    let mut weight_b = 0.0;
+    let mut sum_b = 0.0;
+    let mut maximum = 0.0;
+    let mut level = 0;
+    for (i, count) in counts.iter().enumerate() {
+        weight_b += count;
+        sum_b += i as f64 * count;
+        let weight_f = total - weight_b;
+        if weight_b > 0.0 && weight_f > 0.0 {
+            let diff = sum_b / weight_b - (sum_intensity - sum_b) / weight_f;
+            let val = weight_b * weight_f * diff * diff;
+            if val > maximum {
+                maximum = val;
+                level = i + 1;
+            }
         }
     }
+    min + level as f64 * bin_width
 }
 
+/// Thins edges to the pixels with the largest magnitude along the gradient,
+/// `rotations` are the gradient orientations as `atan2(dy, dx)`
 fn non_maxima_supression<T>(magnitudes: Array3<T>, rotations: ArrayView3<T>) -> Array3<T>
 where
     T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
@@ -110,12 +318,13 @@ where
             } else if dir < 0.0 {
                 dir += 180.0;
             }
-            // Now get neighbour values and suppress col if not a maxima
-            let (a, b) = if dir < 45.0 {
+            // Now get neighbour values along the gradient and suppress col if
+            // not a maxima
+            let (a, b) = if !(22.5..157.5).contains(&dir) {
                 (get_neighbours(i, j, 0, -1), get_neighbours(i, j, 0, 1))
-            } else if dir < 90.0 {
+            } else if dir < 67.5 {
                 (get_neighbours(i, j, -1, -1), get_neighbours(i, j, 1, 1))
-            } else if dir < 135.0 {
+            } else if dir < 112.5 {
                 (get_neighbours(i, j, -1, 0), get_neighbours(i, j, 1, 0))
             } else {
                 (get_neighbours(i, j, -1, 1), get_neighbours(i, j, 1, -1))
@@ -129,72 +338,18 @@ where
     result
 }
 
-fn get_candidates(
-    coord: (usize, usize),
-    bounds: (usize, usize),
-    closed_set: &HashSet<[usize; 2]>,
-) -> Vec<[usize; 2]> {
-    let mut result = Vec::new();
-    let (r, c) = coord;
-    let (rows, cols) = bounds;
-
-    if r > 0 {
-        if c > 0 && !closed_set.contains(&[r - 1, c + 1]) {
-            result.push([r - 1, c - 1]);
-        }
-        if c < cols - 1 && !closed_set.contains(&[r - 1, c + 1]) {
-            result.push([r - 1, c + 1]);
-        }
-        if !closed_set.contains(&[r - 1, c]) {
-            result.push([r - 1, c]);
-        }
-    }
-    if r < rows - 1 {
-        if c > 0 && !closed_set.contains(&[r + 1, c - 1]) {
-            result.push([r + 1, c - 1]);
-        }
-        if c < cols - 1 && !closed_set.contains(&[r + 1, c + 1]) {
-            result.push([r + 1, c + 1]);
-        }
-        if !closed_set.contains(&[r + 1, c]) {
-            result.push([r + 1, c]);
-        }
-    }
-    result
-}
-
+/// Hysteresis thresholding of the suppressed magnitudes with 8-connectivity
 fn link_edges<T>(magnitudes: Array3<T>, lower: T, upper: T) -> Array3<bool>
 where
-    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+    T: Copy + PartialOrd,
 {
-    let magnitudes = magnitudes.mapv(|x| if x >= lower { x } else { T::zero() });
-    let mut result = magnitudes.mapv(|x| x >= upper);
-    let mut visited = HashSet::new();
-
-    let rows = result.shape()[0];
-    let cols = result.shape()[1];
-
-    for r in 0..rows {
-        for c in 0..cols {
-            // If it is a strong edge check if neighbours are weak and add them
-            if result[[r, c, 0]] {
-                visited.insert([r, c]);
-                let mut buffer = get_candidates((r, c), (rows, cols), &visited);
-
-                while let Some(cand) = buffer.pop() {
-                    let coord3 = [cand[0], cand[1], 0];
-                    if magnitudes[coord3] > lower {
-                        visited.insert(cand);
-                        result[coord3] = true;
-
-                        let temp = get_candidates((cand[0], cand[1]), (rows, cols), &visited);
-                        buffer.extend_from_slice(temp.as_slice());
-                    }
-                }
-            }
-        }
-    }
-    result
+    hysteresis(
+        magnitudes.index_axis(Axis(2), 0),
+        lower,
+        upper,
+        Connectivity::Eight,
+    )
+    .insert_axis(Axis(2))
 }
 
 impl<T> Default for CannyBuilder<T>
@@ -216,24 +371,32 @@ where
             blur: None,
             t1: None,
             t2: None,
+            auto_threshold: None,
         }
     }
 
     /// Sets the lower threshold for the parameters returning a new builder
     pub fn lower_threshold(self, t1: T) -> Self {
         Self {
-            blur: self.blur,
             t1: Some(t1),
-            t2: self.t2,
+            ..self
         }
     }
 
     /// Sets the upper threshold for the parameters returning a new builder
     pub fn upper_threshold(self, t2: T) -> Self {
         Self {
-            blur: self.blur,
-            t1: self.t1,
             t2: Some(t2),
+            ..self
+        }
+    }
+
+    /// Calculate the thresholds from the image with the given method instead of
+    /// using the lower and upper thresholds
+    pub fn auto_threshold(self, method: CannyAutoThreshold<T>) -> Self {
+        Self {
+            auto_threshold: Some(method),
+            ..self
         }
     }
 
@@ -248,8 +411,7 @@ where
         if let Ok(blur) = GaussianFilter::build_with_params(shape, covariance) {
             Self {
                 blur: Some(blur),
-                t1: self.t1,
-                t2: self.t2,
+                ..self
             }
         } else {
             self
@@ -278,13 +440,20 @@ where
         if t2 < t1 {
             std::mem::swap(&mut t1, &mut t2);
         }
-        CannyParameters { blur, t1, t2 }
+        CannyParameters {
+            blur,
+            t1,
+            t2,
+            auto_threshold: self.auto_threshold,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::RGB;
+    use assert_approx_eq::assert_approx_eq;
     use ndarray::arr3;
 
     #[test]
@@ -311,6 +480,46 @@ mod tests {
         assert_eq!(gauss.shape(), [3, 3, 1]);
     }
 
+    #[test]
+    fn canny_thin_edges() {
+        // Smooth edges centred on a pixel so the magnitude has a single peak
+        let sigmoid = |x: f64| 1.0 / (1.0 + (-x).exp());
+        let vertical = Array3::from_shape_fn((17, 17, 1), |(_, c, _)| sigmoid(c as f64 - 8.0));
+        let horizontal = Array3::from_shape_fn((17, 17, 1), |(r, _, _)| sigmoid(r as f64 - 8.0));
+        let params = CannyBuilder::<f64>::new()
+            .lower_threshold(0.05)
+            .upper_threshold(0.1)
+            .blur((3, 3), [0.5, 0.5])
+            .build();
+        let count = |x: ArrayView1<bool>| x.iter().filter(|x| **x).count();
+        // Suppression across the gradient leaves one pixel wide edges
+        let edges = vertical.canny_edge_detector(params.clone()).unwrap();
+        for r in 3..14 {
+            assert_eq!(count(edges.slice(s![r, 3..14, 0])), 1);
+        }
+        let edges = horizontal.canny_edge_detector(params).unwrap();
+        for c in 3..14 {
+            assert_eq!(count(edges.slice(s![3..14, c, 0])), 1);
+        }
+    }
+
+    #[test]
+    fn canny_links_same_row() {
+        // Weak pixels directly left and right of a strong one are linked
+        let magnitudes = arr3(&[
+            [[0.0], [0.0], [0.0], [0.0], [0.0]],
+            [[0.0], [0.5], [0.9], [0.5], [0.2]],
+            [[0.0], [0.0], [0.0], [0.0], [0.0]],
+        ]);
+        let result = link_edges(magnitudes, 0.4, 0.8);
+        let expected = arr3(&[
+            [[false], [false], [false], [false], [false]],
+            [[false], [true], [true], [true], [false]],
+            [[false], [false], [false], [false], [false]],
+        ]);
+        assert_eq!(result, expected);
+    }
+
     #[test]
     fn canny_thresholding() {
         let magnitudes = arr3(&[
@@ -329,4 +538,70 @@ mod tests {
 
         assert_eq!(result, expected);
     }
+
+    fn step_image(channels: usize, edge_channel: usize) -> Array3<f64> {
+        let mut image = Array3::zeros((16, 16, channels));
+        image.slice_mut(s![.., 8.., edge_channel]).fill(1.0);
+        image
+    }
+
+    #[test]
+    fn colour_canny() {
+        let params = CannyBuilder::<f64>::new()
+            .lower_threshold(0.3)
+            .upper_threshold(0.7)
+            .blur((3, 3), [0.5, 0.5])
+            .build();
+        let gray = step_image(1, 0);
+        let expected = gray.clone().canny_edge_detector(params.clone()).unwrap();
+        assert!(expected.iter().any(|x| *x));
+
+        // An edge in any channel of a colour image is found
+        for chan in 0..3 {
+            let mut colour = step_image(3, chan);
+            let image = Image::<f64, RGB>::from_data(colour.clone());
+            let edges = image.canny_edge_detector(params.clone()).unwrap();
+            assert_eq!(edges.data, expected);
+            let edges = colour
+                .view_mut()
+                .canny_edge_detector(params.clone())
+                .unwrap();
+            assert_eq!(edges, expected);
+        }
+
+        let (edges, orientation) = gray
+            .clone()
+            .canny_edge_detector_with_orientation(params)
+            .unwrap();
+        assert_eq!(orientation.dim(), gray.dim());
+        for ((r, c, _), edge) in edges.indexed_iter() {
+            // Ignore the borders which aren't filtered
+            if *edge && (2..14).contains(&r) && (2..14).contains(&c) {
+                // Gradient points along the columns
+                assert!(orientation[[r, c, 0]].abs() < 1e-10);
+            }
+        }
+        assert_eq!(orientation[[8, 0, 0]], 0.0);
+    }
+
+    #[test]
+    fn canny_auto_thresholds() {
+        let mut image = Array3::from_elem((9, 9, 1), 0.2);
+        image.slice_mut(s![.., 4.., ..]).fill(0.6);
+        let (lower, upper) = auto_thresholds(&image, CannyAutoThreshold::Median(0.5)).unwrap();
+        assert_approx_eq!(lower, 0.3);
+        assert_approx_eq!(upper, 0.9);
+        let (lower, upper) = auto_thresholds(&image, CannyAutoThreshold::Otsu).unwrap();
+        assert!(upper > 0.2 && upper <= 0.6);
+        assert_eq!(lower, upper / 2.0);
+
+        let params = CannyBuilder::<f64>::new()
+            .lower_threshold(100.0)
+            .upper_threshold(200.0)
+            .auto_threshold(CannyAutoThreshold::Otsu)
+            .build();
+        assert_eq!(params.auto_threshold, Some(CannyAutoThreshold::Otsu));
+        let edges = image.canny_edge_detector(params).unwrap();
+        assert!(edges.iter().any(|x| *x));
+    }
 }
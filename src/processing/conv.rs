@@ -1,6 +1,6 @@
 use crate::core::padding::*;
 use crate::core::{kernel_centre, ColourModel, Image, ImageBase};
-use crate::processing::Error;
+use crate::processing::{gaussian_kernel_size, Error, GaussianFilter, KernelBuilder};
 use core::mem::MaybeUninit;
 use ndarray::prelude::*;
 use ndarray::{Data, DataMut, Zip};
@@ -243,6 +243,20 @@ where
     }
 }
 
+/// Applies a Gaussian blur with the given standard deviation to every channel.
+/// Samples outside of the image are ignored and the kernel renormalised over
+/// the remaining samples so the image doesn't darken towards the borders
+pub(crate) fn gaussian_blur(data: &Array3<f64>, sigma: f64) -> Result<Array3<f64>, Error> {
+    let size = gaussian_kernel_size(sigma);
+    let channels = data.dim().2;
+    let kernel: Array3<f64> =
+        GaussianFilter::build_with_params((size, size, channels), [sigma * sigma, sigma * sigma])?;
+    let ones = Array3::<f64>::ones(data.dim());
+    let blurred = data.conv2d_with_padding(kernel.view(), &ZeroPadding {})?;
+    let weights = ones.conv2d_with_padding(kernel.view(), &ZeroPadding {})?;
+    Ok(blurred / weights)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
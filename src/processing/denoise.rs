@@ -0,0 +1,330 @@
+use crate::core::{
+    denormalised_array, normalised_array, ColourModel, Image, ImageBase, PixelBound,
+};
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::{Data, OwnedRepr};
+use num_traits::{FromPrimitive, Num, ToPrimitive};
+
+/// Non-local means denoising. Each pixel is replaced by a weighted average of
+/// the pixels in a search window around it where the weights are given by how
+/// similar the patches surrounding the two pixels are.
+pub trait NonLocalMeansExt {
+    type Output;
+    /// Run non-local means. `patch_size` and `search_window` are the widths of
+    /// the square patch and search regions and must be odd. `h` controls the
+    /// strength of the filtering and is given as a fraction of the pixel range,
+    /// a value close to the standard deviation of the noise is a good start.
+    ///
+    /// Patch distances are calculated over all channels so colour images share
+    /// weights across channels. The search window is cropped at the image
+    /// borders and patch pixels outside the image are clamped to the nearest
+    /// edge.
+    fn non_local_means(
+        &self,
+        patch_size: usize,
+        search_window: usize,
+        h: f64,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// The conductance function used in anisotropic diffusion, this controls how
+/// strongly diffusion is stopped across edges
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Conductance {
+    /// `exp(-(|∇I|/κ)²)` - favours high contrast edges over low contrast ones
+    Exponential,
+    /// `1/(1 + (|∇I|/κ)²)` - favours wide regions over smaller ones
+    Quadratic,
+}
+
+/// Perona-Malik anisotropic diffusion. Smooths an image by iteratively
+/// diffusing pixel values to their neighbours while inhibiting diffusion across
+/// edges.
+pub trait AnisotropicDiffusionExt {
+    type Output;
+    /// Run anisotropic diffusion for the given number of iterations. `kappa` is
+    /// the gradient magnitude considered an edge and like `h` in non-local
+    /// means is a fraction of the pixel range. Each channel is diffused
+    /// independently with no flux across the image borders.
+    fn anisotropic_diffusion(
+        &self,
+        iterations: usize,
+        kappa: f64,
+        conductance: Conductance,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// Time step for each iteration, 0.25 is the largest stable value for the
+/// 4-neighbour discretisation
+const DIFFUSION_STEP: f64 = 0.25;
+
+impl<T, U> NonLocalMeansExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+{
+    type Output = ArrayBase<OwnedRepr<T>, Ix3>;
+
+    fn non_local_means(
+        &self,
+        patch_size: usize,
+        search_window: usize,
+        h: f64,
+    ) -> Result<Self::Output, Error> {
+        let is_even = |x| x & 1 == 0;
+        if is_even(patch_size) || is_even(search_window) {
+            return Err(Error::InvalidDimensions);
+        } else if h.is_nan() || h <= 0.0 {
+            return Err(Error::InvalidParameter);
+        }
+        let (rows, cols, channels) = self.dim();
+        if rows == 0 || cols == 0 {
+            return Ok(self.to_owned());
+        }
+        let data = normalised_array(self).ok_or(Error::NumericError)?;
+        let patch_radius = (patch_size / 2) as isize;
+        let search_radius = (search_window / 2) as isize;
+        let clamp_row = |r: isize| r.max(0).min(rows as isize - 1) as usize;
+        let clamp_col = |c: isize| c.max(0).min(cols as isize - 1) as usize;
+        let patch_len = (patch_size * patch_size * channels) as f64;
+        let h2 = h * h;
+
+        let mut result = Array3::zeros(self.dim());
+        let mut sums = vec![0.0; channels];
+        for r in 0..rows as isize {
+            for c in 0..cols as isize {
+                sums.iter_mut().for_each(|x| *x = 0.0);
+                let mut total_weight = 0.0;
+                // Candidates outside the image are skipped so edge pixels
+                // aren't counted more than once
+                let search_rows =
+                    (r - search_radius).max(0)..=(r + search_radius).min(rows as isize - 1);
+                for sr in search_rows {
+                    let search_cols =
+                        (c - search_radius).max(0)..=(c + search_radius).min(cols as isize - 1);
+                    for sc in search_cols {
+                        let mut distance = 0.0;
+                        for pr in -patch_radius..=patch_radius {
+                            let a_row = clamp_row(r + pr);
+                            let b_row = clamp_row(sr + pr);
+                            for pc in -patch_radius..=patch_radius {
+                                let a_col = clamp_col(c + pc);
+                                let b_col = clamp_col(sc + pc);
+                                for chan in 0..channels {
+                                    let diff =
+                                        data[[a_row, a_col, chan]] - data[[b_row, b_col, chan]];
+                                    distance += diff * diff;
+                                }
+                            }
+                        }
+                        let weight = (-(distance / patch_len) / h2).exp();
+                        let (sr, sc) = (sr as usize, sc as usize);
+                        for (chan, sum) in sums.iter_mut().enumerate() {
+                            *sum += weight * data[[sr, sc, chan]];
+                        }
+                        total_weight += weight;
+                    }
+                }
+                for (chan, sum) in sums.iter().enumerate() {
+                    result[[r as usize, c as usize, chan]] = sum / total_weight;
+                }
+            }
+        }
+        Ok(denormalised_array(result))
+    }
+}
+
+impl<T, U, C> NonLocalMeansExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    C: ColourModel,
+{
+    type Output = Image<T, C>;
+
+    fn non_local_means(
+        &self,
+        patch_size: usize,
+        search_window: usize,
+        h: f64,
+    ) -> Result<Self::Output, Error> {
+        let data = self.data.non_local_means(patch_size, search_window, h)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+impl<T, U> AnisotropicDiffusionExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+{
+    type Output = ArrayBase<OwnedRepr<T>, Ix3>;
+
+    fn anisotropic_diffusion(
+        &self,
+        iterations: usize,
+        kappa: f64,
+        conductance: Conductance,
+    ) -> Result<Self::Output, Error> {
+        if !kappa.is_finite() || kappa <= 0.0 {
+            return Err(Error::InvalidParameter);
+        }
+        let (rows, cols, channels) = self.dim();
+        let g = |grad: f64| {
+            let x = (grad / kappa).powi(2);
+            match conductance {
+                Conductance::Exponential => (-x).exp(),
+                Conductance::Quadratic => 1.0 / (1.0 + x),
+            }
+        };
+        let mut data = normalised_array(self).ok_or(Error::NumericError)?;
+        let mut next = data.clone();
+        for _ in 0..iterations {
+            for r in 0..rows {
+                for c in 0..cols {
+                    for chan in 0..channels {
+                        let centre = data[[r, c, chan]];
+                        // Neighbours outside the image give a zero gradient
+                        let mut neighbours = [centre; 4];
+                        if r > 0 {
+                            neighbours[0] = data[[r - 1, c, chan]];
+                        }
+                        if r + 1 < rows {
+                            neighbours[1] = data[[r + 1, c, chan]];
+                        }
+                        if c > 0 {
+                            neighbours[2] = data[[r, c - 1, chan]];
+                        }
+                        if c + 1 < cols {
+                            neighbours[3] = data[[r, c + 1, chan]];
+                        }
+                        let flux: f64 = neighbours
+                            .iter()
+                            .map(|n| {
+                                let grad = n - centre;
+                                g(grad.abs()) * grad
+                            })
+                            .sum();
+                        next[[r, c, chan]] = centre + DIFFUSION_STEP * flux;
+                    }
+                }
+            }
+            std::mem::swap(&mut data, &mut next);
+        }
+        Ok(denormalised_array(data))
+    }
+}
+
+impl<T, U, C> AnisotropicDiffusionExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    C: ColourModel,
+{
+    type Output = Image<T, C>;
+
+    fn anisotropic_diffusion(
+        &self,
+        iterations: usize,
+        kappa: f64,
+        conductance: Conductance,
+    ) -> Result<Self::Output, Error> {
+        let data = self
+            .data
+            .anisotropic_diffusion(iterations, kappa, conductance)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, RGB};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+    use ndarray_rand::rand_distr::Normal;
+    use ndarray_rand::RandomExt;
+
+    fn psnr(a: &Array3<f64>, b: &Array3<f64>) -> f64 {
+        let mse = (a - b).mapv(|x| x * x).mean().unwrap();
+        10.0 * (1.0 / mse).log10()
+    }
+
+    /// Piecewise constant image with additive Gaussian noise
+    fn noisy_image(channels: usize) -> (Array3<f64>, Array3<f64>) {
+        let mut clean = Array3::from_elem((24, 24, channels), 0.2f64);
+        clean.slice_mut(s![6..18, 6..18, ..]).fill(0.8);
+        clean.slice_mut(s![.., 20.., ..]).fill(0.5);
+        let mut rng = StdRng::seed_from_u64(7);
+        let noise = Array3::random_using(clean.dim(), Normal::new(0.0, 0.05).unwrap(), &mut rng);
+        let noisy = (&clean + &noise).mapv(|x: f64| x.clamp(0.0, 1.0));
+        (clean, noisy)
+    }
+
+    #[test]
+    fn non_local_means_improves_psnr() {
+        let (clean, noisy) = noisy_image(1);
+        let image = Image::<f64, Gray>::from_data(noisy.clone());
+        let denoised = image.non_local_means(5, 11, 0.1).unwrap();
+
+        let before = psnr(&clean, &noisy);
+        let after = psnr(&clean, &denoised.data);
+        assert!(after > before + 3.0, "{} -> {}", before, after);
+
+        let (clean, noisy) = noisy_image(3);
+        let image = Image::<u8, RGB>::from_data(noisy.mapv(|x| (x * 255.0).round() as u8));
+        let denoised = image.non_local_means(3, 7, 0.1).unwrap();
+        let denoised = denoised.data.mapv(|x| x as f64 / 255.0);
+        assert!(psnr(&clean, &denoised) > psnr(&clean, &noisy));
+    }
+
+    #[test]
+    fn non_local_means_borders() {
+        // With a very large h every candidate has the same weight so the
+        // result is the mean of the search window inside the image
+        let image = Array3::from_shape_fn((4, 4, 1), |(r, c, _)| (r * 4 + c) as f64 / 15.0);
+        let denoised = image.non_local_means(1, 3, 1e6).unwrap();
+        let corner = (0.0 + 1.0 + 4.0 + 5.0) / (4.0 * 15.0);
+        assert!((denoised[[0, 0, 0]] - corner).abs() < 1e-6);
+        let edge = (1.0 + 2.0 + 3.0 + 5.0 + 6.0 + 7.0) / (6.0 * 15.0);
+        assert!((denoised[[0, 2, 0]] - edge).abs() < 1e-6);
+    }
+
+    #[test]
+    fn anisotropic_diffusion_improves_psnr() {
+        let (clean, noisy) = noisy_image(1);
+        let before = psnr(&clean, &noisy);
+        for conductance in &[Conductance::Exponential, Conductance::Quadratic] {
+            let denoised = noisy.anisotropic_diffusion(15, 0.1, *conductance).unwrap();
+            let after = psnr(&clean, &denoised);
+            assert!(after > before + 3.0, "{} -> {}", before, after);
+        }
+        let unchanged = noisy
+            .anisotropic_diffusion(0, 0.1, Conductance::Exponential)
+            .unwrap();
+        assert_eq!(unchanged, noisy);
+    }
+
+    #[test]
+    fn denoise_bad_parameters() {
+        let image = Image::<f64, Gray>::new(5, 5);
+        assert_eq!(
+            image.non_local_means(4, 7, 0.1),
+            Err(Error::InvalidDimensions)
+        );
+        assert_eq!(
+            image.non_local_means(3, 7, 0.0),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            image.anisotropic_diffusion(1, 0.0, Conductance::Quadratic),
+            Err(Error::InvalidParameter)
+        );
+        for kappa in [f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                image.anisotropic_diffusion(1, kappa, Conductance::Exponential),
+                Err(Error::InvalidParameter)
+            );
+        }
+    }
+}
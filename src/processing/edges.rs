@@ -0,0 +1,389 @@
+use crate::core::{ColourModel, ImageBase};
+use crate::processing::canny::canny_with_gradient;
+use crate::processing::*;
+use ndarray::prelude::*;
+use ndarray::DataMut;
+use num_traits::{cast::FromPrimitive, real::Real, Num, NumAssignOps};
+
+/// An edge point located to subpixel precision
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct EdgePoint {
+    /// Row of the edge point
+    pub row: f64,
+    /// Column of the edge point
+    pub col: f64,
+    /// Orientation of the gradient in radians as `atan2(dy, dx)`
+    pub orientation: f64,
+    /// Interpolated gradient magnitude at the edge point
+    pub strength: f64,
+}
+
+/// A chain of connected edge points ordered along the edge
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EdgeChain {
+    /// The points in the chain, neighbouring points come from neighbouring
+    /// pixels
+    pub points: Vec<EdgePoint>,
+    /// True if the last point connects back to the first
+    pub closed: bool,
+}
+
+/// Finds edges with subpixel precision using the Canny edge detector
+pub trait SubpixelEdgeExt<T> {
+    /// Returns every Canny edge pixel refined to subpixel precision by fitting
+    /// a parabola to the gradient magnitude across the edge. Points are in
+    /// raster order.
+    fn subpixel_edge_points(&self, params: CannyParameters<T>) -> Result<Vec<EdgePoint>, Error>;
+
+    /// Links the subpixel edge points into chains of 8-connected pixels. Open
+    /// chains run from one end of the edge to the other, at junctions the edge
+    /// is split into multiple chains.
+    fn edge_chains(&self, params: CannyParameters<T>) -> Result<Vec<EdgeChain>, Error>;
+}
+
+impl<T, U> SubpixelEdgeExt<T> for ArrayBase<U, Ix3>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+{
+    fn subpixel_edge_points(&self, params: CannyParameters<T>) -> Result<Vec<EdgePoint>, Error> {
+        let (edges, refine) = canny_refiner(self, params)?;
+        Ok(edges
+            .indexed_iter()
+            .filter(|(_, e)| **e)
+            .map(|((r, c), _)| refine(r, c))
+            .collect())
+    }
+
+    fn edge_chains(&self, params: CannyParameters<T>) -> Result<Vec<EdgeChain>, Error> {
+        let (edges, refine) = canny_refiner(self, params)?;
+        Ok(trace_chains(edges.view())
+            .into_iter()
+            .map(|(pixels, closed)| EdgeChain {
+                points: pixels.into_iter().map(|(r, c)| refine(r, c)).collect(),
+                closed,
+            })
+            .collect())
+    }
+}
+
+impl<T, U, C> SubpixelEdgeExt<T> for ImageBase<U, C>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+    C: ColourModel,
+{
+    fn subpixel_edge_points(&self, params: CannyParameters<T>) -> Result<Vec<EdgePoint>, Error> {
+        self.data.subpixel_edge_points(params)
+    }
+
+    fn edge_chains(&self, params: CannyParameters<T>) -> Result<Vec<EdgeChain>, Error> {
+        self.data.edge_chains(params)
+    }
+}
+
+/// Runs Canny returning the edge mask and a function to refine an edge pixel
+#[allow(clippy::type_complexity)]
+fn canny_refiner<T, U>(
+    image: &ArrayBase<U, Ix3>,
+    params: CannyParameters<T>,
+) -> Result<(Array2<bool>, impl Fn(usize, usize) -> EdgePoint), Error>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+{
+    let output = canny_with_gradient(image, params)?;
+    let to_f64 = |x: Array3<T>| {
+        x.index_axis_move(Axis(2), 0)
+            .mapv(|v| v.to_f64().unwrap_or(0.0))
+    };
+    let magnitude = to_f64(output.magnitude);
+    let orientation = to_f64(output.orientation);
+    let edges = output.edges.index_axis_move(Axis(2), 0);
+    let refine = move |r, c| refine_edge_point(magnitude.view(), orientation[[r, c]], r, c);
+    Ok((edges, refine))
+}
+
+/// Bilinearly interpolates the image at a point clamping to the image bounds
+fn bilinear(image: ArrayView2<f64>, row: f64, col: f64) -> f64 {
+    let (rows, cols) = image.dim();
+    let row = row.max(0.0).min((rows - 1) as f64);
+    let col = col.max(0.0).min((cols - 1) as f64);
+    let (r0, c0) = (row.floor() as usize, col.floor() as usize);
+    let (r1, c1) = ((r0 + 1).min(rows - 1), (c0 + 1).min(cols - 1));
+    let (fr, fc) = (row - r0 as f64, col - c0 as f64);
+    let top = image[[r0, c0]] * (1.0 - fc) + image[[r0, c1]] * fc;
+    let bottom = image[[r1, c0]] * (1.0 - fc) + image[[r1, c1]] * fc;
+    top * (1.0 - fr) + bottom * fr
+}
+
+/// Fits a parabola to the gradient magnitude sampled one pixel either side of
+/// the edge pixel along the gradient direction and moves the point to its peak
+fn refine_edge_point(
+    magnitude: ArrayView2<f64>,
+    orientation: f64,
+    row: usize,
+    col: usize,
+) -> EdgePoint {
+    let (sin, cos) = orientation.sin_cos();
+    let (r, c) = (row as f64, col as f64);
+    let centre = magnitude[[row, col]];
+    let before = bilinear(magnitude, r - sin, c - cos);
+    let after = bilinear(magnitude, r + sin, c + cos);
+    let curvature = before - 2.0 * centre + after;
+    let (offset, strength) = if curvature < 0.0 {
+        let offset = (0.5 * (before - after) / curvature).clamp(-0.5, 0.5);
+        (offset, centre - 0.25 * (before - after) * offset)
+    } else {
+        (0.0, centre)
+    };
+    EdgePoint {
+        row: r + offset * sin,
+        col: c + offset * cos,
+        orientation,
+        strength,
+    }
+}
+
+/// Neighbour offsets with the 4-connected neighbours first so chains prefer
+/// straight steps over diagonal shortcuts
+const NEIGHBOURS: [(isize, isize); 8] = [
+    (-1, 0),
+    (0, 1),
+    (1, 0),
+    (0, -1),
+    (-1, 1),
+    (1, 1),
+    (1, -1),
+    (-1, -1),
+];
+
+fn neighbours(
+    (r, c): (usize, usize),
+    (rows, cols): (usize, usize),
+) -> impl Iterator<Item = (usize, usize)> {
+    NEIGHBOURS.iter().filter_map(move |(dr, dc)| {
+        let nr = r as isize + dr;
+        let nc = c as isize + dc;
+        if nr >= 0 && nc >= 0 && (nr as usize) < rows && (nc as usize) < cols {
+            Some((nr as usize, nc as usize))
+        } else {
+            None
+        }
+    })
+}
+
+/// Traces the edge pixels into ordered chains returning the pixels in each
+/// chain and whether it's closed
+fn trace_chains(edges: ArrayView2<bool>) -> Vec<(Vec<(usize, usize)>, bool)> {
+    let dim = edges.dim();
+    let mut visited = Array2::from_elem(dim, false);
+    let degree = |p| neighbours(p, dim).filter(|n| edges[*n]).count();
+
+    let follow = |start: (usize, usize), visited: &mut Array2<bool>| {
+        let mut path = vec![];
+        let mut current = start;
+        while let Some(next) = neighbours(current, dim).find(|n| edges[*n] && !visited[*n]) {
+            visited[next] = true;
+            path.push(next);
+            current = next;
+        }
+        path
+    };
+
+    let mut chains = vec![];
+    // Start from the ends of edges first then any remaining loops
+    for ends_only in &[true, false] {
+        for (start, is_edge) in edges.indexed_iter() {
+            if !is_edge || visited[start] || (*ends_only && degree(start) > 1) {
+                continue;
+            }
+            visited[start] = true;
+            let forward = follow(start, &mut visited);
+            let backward = follow(start, &mut visited);
+            let mut path: Vec<_> = backward.into_iter().rev().collect();
+            path.push(start);
+            path.extend(forward);
+
+            let first = path[0];
+            let last = path[path.len() - 1];
+            let closed = !*ends_only && path.len() > 2 && neighbours(first, dim).any(|n| n == last);
+            chains.push((path, closed));
+        }
+    }
+    chains
+}
+
+impl EdgeChain {
+    /// Fits a line to the points with total least squares returning `(a, b, c)`
+    /// where `a * col + b * row + c = 0` and `a² + b² = 1`. Returns `None` if
+    /// there are fewer than two points.
+    pub fn fit_line(&self) -> Option<(f64, f64, f64)> {
+        if self.points.len() < 2 {
+            return None;
+        }
+        let n = self.points.len() as f64;
+        let mean_col = self.points.iter().map(|p| p.col).sum::<f64>() / n;
+        let mean_row = self.points.iter().map(|p| p.row).sum::<f64>() / n;
+        let (mut scc, mut srr, mut src) = (0.0, 0.0, 0.0);
+        for p in &self.points {
+            let (dc, dr) = (p.col - mean_col, p.row - mean_row);
+            scc += dc * dc;
+            srr += dr * dr;
+            src += dr * dc;
+        }
+        // The normal is the eigenvector of the scatter matrix with the smallest
+        // eigenvalue, the line direction is at half the angle below
+        let angle = 0.5 * (2.0 * src).atan2(scc - srr);
+        let (a, b) = (-angle.sin(), angle.cos());
+        Some((a, b, -(a * mean_col + b * mean_row)))
+    }
+
+    /// Fits a circle to the points by algebraic least squares returning
+    /// `(centre_row, centre_col, radius)`. Returns `None` if there are fewer than
+    /// three points or they're collinear.
+    pub fn fit_circle(&self) -> Option<(f64, f64, f64)> {
+        if self.points.len() < 3 {
+            return None;
+        }
+        // Solve for x² + y² + d x + e y + f = 0 with x the column and y the row
+        let mut ata = [[0.0; 3]; 3];
+        let mut atb = [0.0; 3];
+        for p in &self.points {
+            let row = [p.col, p.row, 1.0];
+            let rhs = -(p.col * p.col + p.row * p.row);
+            for i in 0..3 {
+                for j in 0..3 {
+                    ata[i][j] += row[i] * row[j];
+                }
+                atb[i] += row[i] * rhs;
+            }
+        }
+        let det = |m: &[[f64; 3]; 3]| {
+            m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+                - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+                + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+        };
+        let d = det(&ata);
+        if d.abs() < f64::EPSILON {
+            return None;
+        }
+        // Cramer's rule
+        let mut solution = [0.0; 3];
+        for (i, s) in solution.iter_mut().enumerate() {
+            let mut m = ata;
+            for (row, b) in m.iter_mut().zip(atb.iter()) {
+                row[i] = *b;
+            }
+            *s = det(&m) / d;
+        }
+        let centre_col = -solution[0] / 2.0;
+        let centre_row = -solution[1] / 2.0;
+        let radius_sq = centre_col * centre_col + centre_row * centre_row - solution[2];
+        if radius_sq <= 0.0 {
+            None
+        } else {
+            Some((centre_row, centre_col, radius_sq.sqrt()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, Image};
+
+    /// Fraction of the pixel covered by the shape estimated by supersampling
+    fn coverage<F: Fn(f64, f64) -> bool>(rows: usize, cols: usize, inside: F) -> Array3<f64> {
+        const SAMPLES: usize = 8;
+        Array3::from_shape_fn((rows, cols, 1), |(r, c, _)| {
+            let mut count = 0;
+            for i in 0..SAMPLES {
+                for j in 0..SAMPLES {
+                    let y = r as f64 - 0.5 + (i as f64 + 0.5) / SAMPLES as f64;
+                    let x = c as f64 - 0.5 + (j as f64 + 0.5) / SAMPLES as f64;
+                    if inside(y, x) {
+                        count += 1;
+                    }
+                }
+            }
+            count as f64 / (SAMPLES * SAMPLES) as f64
+        })
+    }
+
+    fn params() -> CannyParameters<f64> {
+        CannyBuilder::<f64>::new()
+            .lower_threshold(0.1)
+            .upper_threshold(0.3)
+            .blur((5, 5), [1.0, 1.0])
+            .build()
+    }
+
+    #[test]
+    fn subpixel_straight_edge() {
+        let data = coverage(20, 20, |_, x| x >= 9.3);
+        let points = data.subpixel_edge_points(params()).unwrap();
+        let interior = points
+            .iter()
+            .filter(|p| (4.0..16.0).contains(&p.row) && (4.0..16.0).contains(&p.col))
+            .collect::<Vec<_>>();
+        assert!(interior.len() >= 12);
+        for p in &interior {
+            assert!((p.col - 9.3).abs() < 0.1, "{:?}", p);
+            assert!(p.orientation.abs() < 1e-6);
+            assert!(p.strength > 0.0);
+        }
+
+        let chains = data.edge_chains(params()).unwrap();
+        let longest = chains.iter().max_by_key(|c| c.points.len()).unwrap();
+        assert!(!longest.closed);
+        // Points are ordered along the edge, neighbours come from neighbouring
+        // pixels and are moved at most half a pixel
+        for pair in longest.points.windows(2) {
+            let distance = (pair[0].row - pair[1].row).hypot(pair[0].col - pair[1].col);
+            assert!(distance <= 2.0f64.sqrt() + 1.0);
+        }
+        assert!(longest.points.len() >= 18);
+    }
+
+    #[test]
+    fn closed_circle_chain() {
+        let (cy, cx, radius) = (20.4, 19.7, 9.5);
+        let data = coverage(40, 40, |y, x| {
+            (y - cy).powi(2) + (x - cx).powi(2) <= radius * radius
+        });
+        let image = Image::<f64, Gray>::from_data(data);
+        let chains = image.edge_chains(params()).unwrap();
+        assert_eq!(chains.len(), 1);
+        let chain = &chains[0];
+        assert!(chain.closed);
+
+        let (row, col, r) = chain.fit_circle().unwrap();
+        assert!((row - cy).abs() < 0.1, "{}", row);
+        assert!((col - cx).abs() < 0.1, "{}", col);
+        assert!((r - radius).abs() < 0.3, "{}", r);
+
+        assert_eq!(EdgeChain::default().fit_circle(), None);
+    }
+
+    #[test]
+    fn fit_line_to_chain() {
+        let points = (0..10)
+            .map(|i| EdgePoint {
+                row: i as f64,
+                col: 2.0 * i as f64 + 1.0,
+                orientation: 0.0,
+                strength: 1.0,
+            })
+            .collect();
+        let chain = EdgeChain {
+            points,
+            closed: false,
+        };
+        let (a, b, c) = chain.fit_line().unwrap();
+        assert!((a * a + b * b - 1.0).abs() < 1e-10);
+        for p in &chain.points {
+            assert!((a * p.col + b * p.row + c).abs() < 1e-10);
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::core::{ColourModel, Image, ImageBase};
+use crate::core::padding::*;
+use crate::core::{pixel_from_f64, pixel_range, ColourModel, Image, ImageBase, PixelBound};
+use crate::processing::rank::MAX_HISTOGRAM_LEVELS;
+use crate::processing::Error;
 use ndarray::prelude::*;
 use ndarray::{Data, IntoDimension, OwnedRepr, Zip};
 use ndarray_stats::interpolate::*;
@@ -9,6 +12,10 @@ use std::marker::PhantomData;
 
 /// Median filter, given a region to move over the image, each pixel is given
 /// the median value of itself and it's neighbours
+///
+/// For integer images with at most 65536 distinct levels, such as 8 and 16 bit
+/// images, a histogram based median is used which runs in constant time with
+/// respect to the region size.
 pub trait MedianFilterExt {
     type Output;
     /// Run the median filter given the region. Median is assumed to be calculated
@@ -30,19 +37,230 @@ where
         E: IntoDimension<Dim = Ix2>,
     {
         let shape = region.into_dimension();
-        let r_offset = shape[0] / 2;
-        let c_offset = shape[1] / 2;
-        let region = (shape[0], shape[1], 1);
-        let mut result = Array3::<T>::zeros(self.dim());
-        Zip::indexed(self.windows(region)).for_each(|(i, j, k), window| {
-            let mut flat_window = Array::from_iter(window.iter()).mapv(|x| *x);
-            if let Ok(v) = flat_window.quantile_mut(n64(0.5f64), &Linear {}) {
-                if let Some(r) = result.get_mut([i + r_offset, j + c_offset, k]) {
-                    *r = v;
+        let (rows, cols, _) = self.dim();
+        if shape[0] == 0 || shape[1] == 0 || shape[0] > rows || shape[1] > cols {
+            return quantile_median_filter(self, shape);
+        }
+        let min = self.iter().min().and_then(|x| x.to_i64());
+        let max = self.iter().max().and_then(|x| x.to_i64());
+        match (min, max) {
+            (Some(min), Some(max))
+                if is_integral_type::<T>()
+                    && max
+                        .checked_sub(min)
+                        .is_some_and(|x| x < MAX_HISTOGRAM_LEVELS as i64)
+                    && shape[0] <= u16::MAX as usize =>
+            {
+                let mut result = Array3::<T>::zeros(self.dim());
+                let levels = (max - min + 1) as usize;
+                for (chan, mut out) in result.axis_iter_mut(Axis(2)).enumerate() {
+                    let bins = self
+                        .slice(s![.., .., chan])
+                        .mapv(|x| x.to_i64().map_or(0, |x| (x - min) as usize));
+                    let value = |k| T::from_i64(min + k as i64).unwrap_or_else(T::zero);
+                    // Wide images are split into strips of columns overlapping
+                    // by the window width so the column histograms of a strip
+                    // stay within the bin limit
+                    let strip = (MAX_COLUMN_HISTOGRAM_BINS / levels).max(2 * shape[1]);
+                    let mut start = 0;
+                    loop {
+                        let end = (start + strip).min(cols);
+                        let strip_bins = bins.slice(s![.., start..end]);
+                        constant_time_median(strip_bins, shape, levels, |r, c, lower, higher| {
+                            let n = shape[0] * shape[1];
+                            out[[r, start + c]] = Linear::interpolate(
+                                Some(value(lower)),
+                                Some(value(higher)),
+                                n64(0.5),
+                                n,
+                            );
+                        });
+                        if end == cols {
+                            break;
+                        }
+                        start = end + 1 - shape[1];
+                    }
                 }
+                result
             }
-        });
-        result
+            _ => quantile_median_filter(self, shape),
+        }
+    }
+}
+
+/// Returns true if the type can only store whole numbers, `median_filter`
+/// doesn't require `PixelBound` so `PixelBound::is_integral` can't be used
+fn is_integral_type<T>() -> bool
+where
+    T: FromPrimitive + Num,
+{
+    T::from_f64(0.5) == Some(T::zero())
+}
+
+/// The largest number of bins the column histograms of the constant time
+/// median use at once, wider images are processed in strips of columns to
+/// limit the memory used for 16 bit images
+const MAX_COLUMN_HISTOGRAM_BINS: usize = 1 << 25;
+
+/// Median filter which finds the median of each window by partially sorting it
+fn quantile_median_filter<T, U>(data: &ArrayBase<U, Ix3>, shape: Ix2) -> Array3<T>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + Ord,
+{
+    let r_offset = shape[0] / 2;
+    let c_offset = shape[1] / 2;
+    let region = (shape[0], shape[1], 1);
+    let mut result = Array3::<T>::zeros(data.dim());
+    Zip::indexed(data.windows(region)).for_each(|(i, j, k), window| {
+        let mut flat_window = Array::from_iter(window.iter()).mapv(|x| *x);
+        if let Ok(v) = flat_window.quantile_mut(n64(0.5f64), &Linear {}) {
+            if let Some(r) = result.get_mut([i + r_offset, j + c_offset, k]) {
+                *r = v;
+            }
+        }
+    });
+    result
+}
+
+/// Histograms of every column in the image over the rows covered by the
+/// window, split into coarse and fine levels
+struct ColumnHistograms {
+    fine: Vec<u16>,
+    coarse: Vec<u16>,
+    levels: usize,
+    coarse_bins: usize,
+    coarse_width: usize,
+}
+
+impl ColumnHistograms {
+    fn fine(&self, col: usize, coarse: usize) -> &[u16] {
+        let start = col * self.levels + coarse * self.coarse_width;
+        let end = (start + self.coarse_width).min((col + 1) * self.levels);
+        &self.fine[start..end]
+    }
+
+    fn coarse(&self, col: usize) -> &[u16] {
+        &self.coarse[col * self.coarse_bins..(col + 1) * self.coarse_bins]
+    }
+
+    fn add(&mut self, col: usize, bin: usize) {
+        self.fine[col * self.levels + bin] += 1;
+        self.coarse[col * self.coarse_bins + bin / self.coarse_width] += 1;
+    }
+
+    fn remove(&mut self, col: usize, bin: usize) {
+        self.fine[col * self.levels + bin] -= 1;
+        self.coarse[col * self.coarse_bins + bin / self.coarse_width] -= 1;
+    }
+}
+
+/// Constant time median filter from "Median Filtering in Constant Time" by
+/// Perreault and Hébert. Each column keeps a histogram which is updated by one
+/// addition and removal as the window moves down a row. The kernel histogram is
+/// then updated by adding and removing whole column histograms, with the fine
+/// level only updated lazily for the coarse bins the median is found in.
+///
+/// For every window the output function is given the position of the window
+/// centre and the bins of the lower and higher median values, these are the
+/// same for windows with an odd number of pixels.
+fn constant_time_median(
+    bins: ArrayView2<usize>,
+    shape: Ix2,
+    levels: usize,
+    mut output: impl FnMut(usize, usize, usize, usize),
+) {
+    let (rows, cols) = bins.dim();
+    let (height, width) = (shape[0], shape[1]);
+    let coarse_width = ((levels as f64).sqrt().ceil() as usize).max(1);
+    let coarse_bins = levels.div_ceil(coarse_width);
+    let mut columns = ColumnHistograms {
+        fine: vec![0; cols * levels],
+        coarse: vec![0; cols * coarse_bins],
+        levels,
+        coarse_bins,
+        coarse_width,
+    };
+    for r in 0..height {
+        for c in 0..cols {
+            columns.add(c, bins[[r, c]]);
+        }
+    }
+
+    let n = height * width;
+    let lower_rank = (n - 1) / 2;
+    let higher_rank = n / 2;
+    let mut coarse = vec![0u32; coarse_bins];
+    let mut fine = vec![0u32; levels];
+    // Window column each coarse bin's fine histogram is valid for
+    let mut valid_for = vec![None; coarse_bins];
+
+    for r in 0..(rows + 1 - height) {
+        if r > 0 {
+            for c in 0..cols {
+                columns.remove(c, bins[[r - 1, c]]);
+                columns.add(c, bins[[r + height - 1, c]]);
+            }
+        }
+        coarse.iter_mut().for_each(|x| *x = 0);
+        valid_for.iter_mut().for_each(|x| *x = None);
+        for c in 0..width {
+            for (k, v) in coarse.iter_mut().zip(columns.coarse(c)) {
+                *k += *v as u32;
+            }
+        }
+        for c in 0..(cols + 1 - width) {
+            if c > 0 {
+                let (old, new) = (columns.coarse(c - 1), columns.coarse(c + width - 1));
+                for ((k, o), n) in coarse.iter_mut().zip(old).zip(new) {
+                    *k = *k + *n as u32 - *o as u32;
+                }
+            }
+            let mut find = |rank: usize| {
+                let mut seen = 0;
+                let mut bin = 0;
+                while seen + coarse[bin] as usize <= rank {
+                    seen += coarse[bin] as usize;
+                    bin += 1;
+                }
+                let start = bin * coarse_width;
+                let segment = &mut fine[start..(start + coarse_width).min(levels)];
+                match valid_for[bin] {
+                    Some(last) if c - last < width => {
+                        for k in (last + 1)..=c {
+                            let old = columns.fine(k - 1, bin);
+                            let new = columns.fine(k + width - 1, bin);
+                            for ((f, o), n) in segment.iter_mut().zip(old).zip(new) {
+                                *f = *f + *n as u32 - *o as u32;
+                            }
+                        }
+                    }
+                    _ => {
+                        segment.iter_mut().for_each(|x| *x = 0);
+                        for k in c..(c + width) {
+                            for (f, v) in segment.iter_mut().zip(columns.fine(k, bin)) {
+                                *f += *v as u32;
+                            }
+                        }
+                    }
+                }
+                valid_for[bin] = Some(c);
+                for (i, f) in segment.iter().enumerate() {
+                    seen += *f as usize;
+                    if seen > rank {
+                        return start + i;
+                    }
+                }
+                unreachable!("median bin not found in histogram")
+            };
+            let lower = find(lower_rank);
+            let higher = if higher_rank == lower_rank {
+                lower
+            } else {
+                find(higher_rank)
+            };
+            output(r + height / 2, c + width / 2, lower, higher);
+        }
     }
 }
 
@@ -66,10 +284,215 @@ where
     }
 }
 
+/// Determines how the range (intensity) weights of the bilateral filter are
+/// calculated for images with more than one channel
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum BilateralRange {
+    /// Each channel is filtered independently using weights calculated from
+    /// that channel alone
+    PerChannel,
+    /// The range distance is the euclidean distance between pixels over all
+    /// channels so every channel shares the same weights. This avoids colour
+    /// fringes appearing at edges in colour images
+    Joint,
+}
+
+/// Bilateral filter, an edge preserving smoothing filter. Each pixel becomes a
+/// weighted average of its neighbours where the weights fall off with both the
+/// spatial distance and the difference in pixel value.
+pub trait BilateralFilterExt<T>
+where
+    T: Copy,
+{
+    type Output;
+    /// Run the bilateral filter. `sigma_spatial` is the standard deviation of
+    /// the spatial Gaussian in pixels, the window covers `3 * sigma_spatial`
+    /// pixels either side of the centre. `sigma_range` is the standard deviation
+    /// of the range Gaussian given as a fraction of the pixel range, i.e. for a
+    /// `u8` image a value of 0.1 corresponds to ~25 grey levels.
+    ///
+    /// Samples outside of the image are ignored.
+    fn bilateral_filter(
+        &self,
+        sigma_spatial: f64,
+        sigma_range: f64,
+        range: BilateralRange,
+    ) -> Result<Self::Output, Error>;
+
+    /// Run the bilateral filter using the given padding strategy to provide
+    /// samples outside of the image. If the strategy won't pad a location
+    /// the sample is ignored
+    fn bilateral_filter_with_padding(
+        &self,
+        sigma_spatial: f64,
+        sigma_range: f64,
+        range: BilateralRange,
+        strategy: &impl PaddingStrategy<T>,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// Gets a sample from the image as a float, falling back to the padding
+/// strategy for coordinates outside of the image
+#[inline]
+pub(crate) fn padded_sample<T>(
+    image: ArrayView3<T>,
+    index: (isize, isize, usize),
+    strategy: &impl PaddingStrategy<T>,
+) -> Option<f64>
+where
+    T: Copy + ToPrimitive,
+{
+    let (rows, cols, _) = image.dim();
+    let (r, c, chan) = index;
+    if r >= 0 && c >= 0 && r < rows as isize && c < cols as isize {
+        image[[r as usize, c as usize, chan]].to_f64()
+    } else if strategy.will_pad(Some((r, c))) {
+        strategy.get_value(image, index).and_then(|x| x.to_f64())
+    } else {
+        None
+    }
+}
+
+impl<T, U> BilateralFilterExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+{
+    type Output = ArrayBase<OwnedRepr<T>, Ix3>;
+
+    fn bilateral_filter(
+        &self,
+        sigma_spatial: f64,
+        sigma_range: f64,
+        range: BilateralRange,
+    ) -> Result<Self::Output, Error> {
+        self.bilateral_filter_with_padding(sigma_spatial, sigma_range, range, &NoPadding {})
+    }
+
+    fn bilateral_filter_with_padding(
+        &self,
+        sigma_spatial: f64,
+        sigma_range: f64,
+        range: BilateralRange,
+        strategy: &impl PaddingStrategy<T>,
+    ) -> Result<Self::Output, Error> {
+        if !(sigma_spatial > 0.0 && sigma_spatial.is_finite() && sigma_range > 0.0) {
+            return Err(Error::InvalidParameter);
+        }
+        let radius = (3.0 * sigma_spatial).ceil() as isize;
+        let size = (2 * radius + 1) as usize;
+        let spatial = Array2::from_shape_fn((size, size), |(r, c)| {
+            let dr = r as f64 - radius as f64;
+            let dc = c as f64 - radius as f64;
+            (-(dr * dr + dc * dc) / (2.0 * sigma_spatial * sigma_spatial)).exp()
+        });
+        // Range differences are normalised so sigma_range is independent of type
+        let scale = pixel_range::<T>();
+        let range_denom = 2.0 * sigma_range * sigma_range * scale * scale;
+
+        let image = self.view();
+        let (rows, cols, channels) = self.dim();
+        let mut result = Array3::<T>::zeros(self.dim());
+        let mut centre = vec![0.0; channels];
+        let mut neighbour = vec![0.0; channels];
+        let mut sums = vec![0.0; channels];
+        let mut weights = vec![0.0; channels];
+
+        for r in 0..rows {
+            for c in 0..cols {
+                for chan in 0..channels {
+                    centre[chan] = image[[r, c, chan]].to_f64().ok_or(Error::NumericError)?;
+                    sums[chan] = 0.0;
+                    weights[chan] = 0.0;
+                }
+                for ((kr, kc), spatial_weight) in spatial.indexed_iter() {
+                    let rr = r as isize + kr as isize - radius;
+                    let cc = c as isize + kc as isize - radius;
+                    let valid =
+                        neighbour.iter_mut().enumerate().all(|(chan, n)| {
+                            match padded_sample(image, (rr, cc, chan), strategy) {
+                                Some(v) => {
+                                    *n = v;
+                                    true
+                                }
+                                None => false,
+                            }
+                        });
+                    if !valid {
+                        continue;
+                    }
+                    match range {
+                        BilateralRange::PerChannel => {
+                            for chan in 0..channels {
+                                let diff = neighbour[chan] - centre[chan];
+                                let w = spatial_weight * (-(diff * diff) / range_denom).exp();
+                                sums[chan] += w * neighbour[chan];
+                                weights[chan] += w;
+                            }
+                        }
+                        BilateralRange::Joint => {
+                            let dist: f64 = centre
+                                .iter()
+                                .zip(neighbour.iter())
+                                .map(|(a, b)| (a - b) * (a - b))
+                                .sum();
+                            let w = spatial_weight * (-dist / range_denom).exp();
+                            for chan in 0..channels {
+                                sums[chan] += w * neighbour[chan];
+                                weights[chan] += w;
+                            }
+                        }
+                    }
+                }
+                for chan in 0..channels {
+                    // The centre pixel always contributes so weights are non-zero
+                    result[[r, c, chan]] = pixel_from_f64(sums[chan] / weights[chan]);
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl<T, U, C> BilateralFilterExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    C: ColourModel,
+{
+    type Output = Image<T, C>;
+
+    fn bilateral_filter(
+        &self,
+        sigma_spatial: f64,
+        sigma_range: f64,
+        range: BilateralRange,
+    ) -> Result<Self::Output, Error> {
+        let data = self
+            .data
+            .bilateral_filter(sigma_spatial, sigma_range, range)?;
+        Ok(Image::from_data(data))
+    }
+
+    fn bilateral_filter_with_padding(
+        &self,
+        sigma_spatial: f64,
+        sigma_range: f64,
+        range: BilateralRange,
+        strategy: &impl PaddingStrategy<T>,
+    ) -> Result<Self::Output, Error> {
+        let data =
+            self.data
+                .bilateral_filter_with_padding(sigma_spatial, sigma_range, range, strategy)?;
+        Ok(Image::from_data(data))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::core::colour_models::{Gray, RGB};
+    use ndarray_rand::rand::{rngs::StdRng, Rng, SeedableRng};
 
     #[test]
     fn simple_median() {
@@ -98,4 +521,106 @@ mod tests {
 
         assert_eq!(image, expected);
     }
+
+    #[test]
+    fn constant_time_median_matches_quantile() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let pixels: Vec<u8> = (0..(17 * 19 * 3)).map(|_| rng.gen()).collect();
+        let image = Array3::from_shape_vec((17, 19, 3), pixels).unwrap();
+        for shape in &[(3, 3), (4, 5), (1, 7), (7, 1), (2, 2), (17, 19)] {
+            let shape = shape.into_dimension();
+            assert_eq!(
+                image.median_filter(shape),
+                quantile_median_filter(&image, shape),
+                "{:?}",
+                shape
+            );
+        }
+
+        let pixels: Vec<u16> = (0..(15 * 12)).map(|_| rng.gen_range(0..3000)).collect();
+        let image = Array3::from_shape_vec((15, 12, 1), pixels).unwrap();
+        let shape = Ix2(5, 6);
+        assert_eq!(
+            image.median_filter(shape),
+            quantile_median_filter(&image, shape)
+        );
+
+        let image = image.mapv(|x| x as i16 - 1500);
+        assert_eq!(
+            image.median_filter(shape),
+            quantile_median_filter(&image, shape)
+        );
+    }
+
+    #[test]
+    fn constant_time_median_strips() {
+        // The full 16 bit range over this many columns needs several strips
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut pixels: Vec<u16> = (0..(4 * 1100)).map(|_| rng.gen()).collect();
+        pixels[0] = 0;
+        pixels[1] = u16::MAX;
+        let image = Array3::from_shape_vec((4, 1100, 1), pixels).unwrap();
+        for shape in &[(3, 3), (2, 4)] {
+            let shape = shape.into_dimension();
+            assert_eq!(
+                image.median_filter(shape),
+                quantile_median_filter(&image, shape)
+            );
+        }
+    }
+
+    #[test]
+    fn bilateral_flat_image() {
+        let image = Image::<u8, RGB>::from_shape_data(4, 4, vec![100; 48]);
+        for range in &[BilateralRange::PerChannel, BilateralRange::Joint] {
+            let filtered = image.bilateral_filter(1.0, 0.1, *range).unwrap();
+            assert_eq!(filtered, image);
+            let filtered = image
+                .bilateral_filter_with_padding(1.0, 0.1, *range, &ZeroPadding {})
+                .unwrap();
+            // Zero padding is far away in value so barely contributes
+            assert_eq!(filtered, image);
+        }
+    }
+
+    #[test]
+    fn bilateral_preserves_edges() {
+        let mut image = Image::<f64, Gray>::new(8, 8);
+        image.data.slice_mut(s![.., 4.., ..]).fill(1.0);
+        image.data[[2, 1, 0]] = 0.05;
+        image.data[[5, 6, 0]] = 0.95;
+
+        let filtered = image
+            .bilateral_filter(1.0, 0.1, BilateralRange::PerChannel)
+            .unwrap();
+
+        for r in 0..8 {
+            assert!(filtered.data[[r, 3, 0]] < 0.05);
+            assert!(filtered.data[[r, 4, 0]] > 0.95);
+        }
+        // Noise is smoothed out towards its neighbours
+        assert!(filtered.data[[2, 1, 0]] < 0.02);
+        assert!(filtered.data[[5, 6, 0]] > 0.98);
+    }
+
+    #[test]
+    fn bilateral_bad_parameters() {
+        let image = Image::<u8, Gray>::new(3, 3);
+        assert_eq!(
+            image.bilateral_filter(0.0, 0.1, BilateralRange::Joint),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            image.bilateral_filter(1.0, -0.1, BilateralRange::Joint),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            image.bilateral_filter(f64::NAN, 0.1, BilateralRange::Joint),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            image.bilateral_filter(1.0, f64::NAN, BilateralRange::Joint),
+            Err(Error::InvalidParameter)
+        );
+    }
 }
@@ -0,0 +1,268 @@
+use crate::core::{ColourModel, Gray, Image, ImageBase};
+use crate::processing::*;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+
+/// Derivative operator used to calculate an image gradient
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum GradientOperator {
+    /// 3x3 Sobel operator
+    Sobel,
+    /// 3x3 Scharr operator
+    Scharr,
+    /// 3x3 Prewitt operator
+    Prewitt,
+    /// Derivative of a Gaussian with the given standard deviation
+    GaussianDerivative(f64),
+}
+
+/// Norm used to combine the gradient components into a magnitude
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum GradientNorm {
+    /// `|dx| + |dy|`
+    L1,
+    /// `sqrt(dx² + dy²)`
+    L2,
+}
+
+/// The gradient of an image
+#[derive(Clone, PartialEq, Debug)]
+pub struct Gradient<A> {
+    /// Derivative in the direction of increasing column index
+    pub dx: A,
+    /// Derivative in the direction of increasing row index
+    pub dy: A,
+    /// Magnitude of the gradient using the requested norm
+    pub magnitude: A,
+    /// Orientation of the gradient in radians given by `atan2(dy, dx)`
+    pub orientation: A,
+}
+
+/// Calculates the gradient of an image
+pub trait GradientExt {
+    /// Type of each gradient component, the components are always `f64` so
+    /// integer images can have negative derivatives
+    type Output;
+    /// Calculates the gradient with the given operator. The operators are
+    /// scaled so `dx` and `dy` estimate the true derivative, this means the
+    /// results of different operators can be compared. Pixels at the image
+    /// borders are replicated. The derivatives are of the raw pixel values,
+    /// they aren't normalised by the range of the pixel type.
+    ///
+    /// The output is always single channel, for multi-channel images the
+    /// gradient of the channel with the largest magnitude is taken at each
+    /// pixel.
+    fn gradient(
+        &self,
+        operator: GradientOperator,
+        norm: GradientNorm,
+    ) -> Result<Gradient<Self::Output>, Error>;
+}
+
+/// Returns kernels for the x and y derivatives where correlating with the
+/// kernel gives the derivative
+fn derivative_kernels(operator: GradientOperator) -> Result<(Array2<f64>, Array2<f64>), Error> {
+    // The fixed kernels give the negative derivative scaled by the sum of the
+    // kernel's absolute weights
+    let fixed = |horz: Array3<f64>, vert: Array3<f64>| {
+        let scale = -horz.mapv(f64::abs).sum();
+        (
+            horz.index_axis(Axis(2), 0).mapv(|x| x / scale),
+            vert.index_axis(Axis(2), 0).mapv(|x| x / scale),
+        )
+    };
+    let kernels = match operator {
+        GradientOperator::Sobel => fixed(
+            SobelFilter::build_with_params(Orientation::Horizontal)?,
+            SobelFilter::build_with_params(Orientation::Vertical)?,
+        ),
+        GradientOperator::Scharr => fixed(
+            ScharrFilter::build_with_params(Orientation::Horizontal)?,
+            ScharrFilter::build_with_params(Orientation::Vertical)?,
+        ),
+        GradientOperator::Prewitt => fixed(
+            PrewittFilter::build_with_params(Orientation::Horizontal)?,
+            PrewittFilter::build_with_params(Orientation::Vertical)?,
+        ),
+        GradientOperator::GaussianDerivative(sigma) => {
+            let size = gaussian_kernel_size(sigma);
+            let shape = (size, size, 1);
+            let dx: Array3<f64> =
+                GaussianDerivativeFilter::build_with_params(shape, (DerivativeOrder::X, sigma))?;
+            let dy: Array3<f64> =
+                GaussianDerivativeFilter::build_with_params(shape, (DerivativeOrder::Y, sigma))?;
+            (
+                dx.index_axis_move(Axis(2), 0),
+                dy.index_axis_move(Axis(2), 0),
+            )
+        }
+    };
+    Ok(kernels)
+}
+
+/// Correlates a single channel of the image with the kernel replicating pixels
+/// at the borders
+pub(crate) fn correlate_replicate(data: ArrayView2<f64>, kernel: ArrayView2<f64>) -> Array2<f64> {
+    let (rows, cols) = data.dim();
+    let (k_rows, k_cols) = kernel.dim();
+    let clamp = |x: isize, max: usize| x.max(0).min(max as isize - 1) as usize;
+    Array2::from_shape_fn((rows, cols), |(r, c)| {
+        let mut sum = 0.0;
+        for ((kr, kc), w) in kernel.indexed_iter() {
+            if *w != 0.0 {
+                let rr = clamp(r as isize + kr as isize - (k_rows / 2) as isize, rows);
+                let cc = clamp(c as isize + kc as isize - (k_cols / 2) as isize, cols);
+                sum += w * data[[rr, cc]];
+            }
+        }
+        sum
+    })
+}
+
+impl<T, U> GradientExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    type Output = Array3<f64>;
+
+    fn gradient(
+        &self,
+        operator: GradientOperator,
+        norm: GradientNorm,
+    ) -> Result<Gradient<Self::Output>, Error> {
+        let (rows, cols, channels) = self.dim();
+        if channels == 0 {
+            return Err(Error::ChannelDimensionMismatch);
+        }
+        let (kx, ky) = derivative_kernels(operator)?;
+        let data = self
+            .mapv(|x| x.to_f64())
+            .into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::NumericError)?;
+        let data = Array3::from_shape_vec((rows, cols, channels), data).unwrap();
+
+        let magnitude_of = |dx: f64, dy: f64| match norm {
+            GradientNorm::L1 => dx.abs() + dy.abs(),
+            GradientNorm::L2 => dx.hypot(dy),
+        };
+
+        let mut dx = Array2::zeros((rows, cols));
+        let mut dy = Array2::zeros((rows, cols));
+        let mut magnitude = Array2::from_elem((rows, cols), -1.0);
+        for chan in 0..channels {
+            let channel = data.index_axis(Axis(2), chan);
+            let chan_dx = correlate_replicate(channel, kx.view());
+            let chan_dy = correlate_replicate(channel, ky.view());
+            for ((r, c), m) in magnitude.indexed_iter_mut() {
+                let (x, y) = (chan_dx[[r, c]], chan_dy[[r, c]]);
+                let mag = magnitude_of(x, y);
+                if mag > *m {
+                    *m = mag;
+                    dx[[r, c]] = x;
+                    dy[[r, c]] = y;
+                }
+            }
+        }
+        let orientation =
+            Array2::from_shape_fn((rows, cols), |(r, c)| dy[[r, c]].atan2(dx[[r, c]]));
+
+        Ok(Gradient {
+            dx: dx.insert_axis(Axis(2)),
+            dy: dy.insert_axis(Axis(2)),
+            magnitude: magnitude.insert_axis(Axis(2)),
+            orientation: orientation.insert_axis(Axis(2)),
+        })
+    }
+}
+
+impl<T, U, C> GradientExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    type Output = Image<f64, Gray>;
+
+    fn gradient(
+        &self,
+        operator: GradientOperator,
+        norm: GradientNorm,
+    ) -> Result<Gradient<Self::Output>, Error> {
+        let grad = self.data.gradient(operator, norm)?;
+        Ok(Gradient {
+            dx: Image::from_data(grad.dx),
+            dy: Image::from_data(grad.dy),
+            magnitude: Image::from_data(grad.magnitude),
+            orientation: Image::from_data(grad.orientation),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::RGB;
+
+    #[test]
+    fn operators_estimate_derivative() {
+        let ramp = Array3::from_shape_fn((15, 15, 1), |(r, c, _)| 2.0 * c as f64 - r as f64);
+        let operators = [
+            GradientOperator::Sobel,
+            GradientOperator::Scharr,
+            GradientOperator::Prewitt,
+            GradientOperator::GaussianDerivative(1.0),
+        ];
+        for op in &operators {
+            let grad = ramp.gradient(*op, GradientNorm::L2).unwrap();
+            assert_eq!(grad.dx.dim(), (15, 15, 1));
+            // Away from the borders the result is exact
+            for r in 4..11 {
+                for c in 4..11 {
+                    assert!((grad.dx[[r, c, 0]] - 2.0).abs() < 1e-10, "{:?}", op);
+                    assert!((grad.dy[[r, c, 0]] + 1.0).abs() < 1e-10, "{:?}", op);
+                    assert!((grad.magnitude[[r, c, 0]] - 5.0f64.sqrt()).abs() < 1e-10);
+                    let expected = (-1.0f64).atan2(2.0);
+                    assert!((grad.orientation[[r, c, 0]] - expected).abs() < 1e-10);
+                }
+            }
+            let grad = ramp.gradient(*op, GradientNorm::L1).unwrap();
+            assert!((grad.magnitude[[7, 7, 0]] - 3.0).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn multichannel_takes_strongest_channel() {
+        let mut image = Image::<f64, RGB>::new(9, 9);
+        // Weak vertical edge in red and a strong horizontal edge in blue
+        image.data.slice_mut(s![.., 5.., 0]).fill(0.2);
+        image.data.slice_mut(s![5.., .., 2]).fill(0.8);
+        let grad = image
+            .gradient(GradientOperator::Sobel, GradientNorm::L2)
+            .unwrap();
+        assert_eq!(grad.magnitude.data.dim(), (9, 9, 1));
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-10;
+        assert!(close(grad.dx.data[[1, 4, 0]], 0.1));
+        assert!(close(grad.dy.data[[1, 4, 0]], 0.0));
+        assert!(close(grad.dx.data[[4, 4, 0]], 0.0));
+        assert!(close(grad.dy.data[[4, 4, 0]], 0.4));
+        assert!(close(grad.magnitude.data[[0, 0, 0]], 0.0));
+    }
+
+    #[test]
+    fn integer_images() {
+        // Derivatives of unsigned images can be negative
+        let image = Array3::from_shape_fn((5, 5, 1), |(_, c, _)| 200 - 40 * c as u8);
+        let grad = image
+            .gradient(GradientOperator::Sobel, GradientNorm::L2)
+            .unwrap();
+        assert!(grad
+            .dx
+            .iter()
+            .all(|x| (x + 40.0).abs() < 1e-10 || *x == -20.0));
+        assert!((grad.dx[[2, 2, 0]] + 40.0).abs() < 1e-10);
+        assert!((grad.magnitude[[2, 2, 0]] - 40.0).abs() < 1e-10);
+    }
+}
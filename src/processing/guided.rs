@@ -0,0 +1,463 @@
+use crate::core::{
+    denormalised_array, normalised_array, ColourModel, Image, ImageBase, PixelBound,
+};
+use crate::processing::integral::box_mean;
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::{Data, OwnedRepr};
+use num_traits::{FromPrimitive, Num, ToPrimitive};
+use std::collections::BTreeSet;
+
+/// The guided filter from "Guided Image Filtering" by He et al. The output is
+/// locally a linear transform of the guidance image, this makes it an edge
+/// preserving smoothing filter which transfers the structure of the guide into
+/// the filtered image. Commonly used to refine depth maps and alpha mattes.
+pub trait GuidedFilterExt<G> {
+    type Output;
+    /// Run the guided filter with a square window of size `2 * radius + 1`.
+    /// `epsilon` is the regularisation, larger values give smoother outputs,
+    /// pixel values are normalised to `[0, 1]` so this is relative to a
+    /// variance in that range.
+    ///
+    /// The guide may have any number of channels and must have the same number
+    /// of rows and columns as the image. Each channel of the image is filtered
+    /// independently. The running time is linear in the number of pixels and
+    /// independent of the radius.
+    fn guided_filter(&self, guide: &G, radius: usize, epsilon: f64) -> Result<Self::Output, Error>;
+}
+
+/// Joint (or cross) bilateral filter. Like the bilateral filter but the range
+/// weights are calculated from a separate guidance image.
+pub trait JointBilateralFilterExt<G> {
+    type Output;
+    /// Run the joint bilateral filter. The parameters match those of the
+    /// `BilateralFilterExt`, with the range weights calculated on the guide.
+    /// For guides with more than one channel the range distance is the
+    /// euclidean distance over all channels as with `BilateralRange::Joint`.
+    ///
+    /// Samples outside of the image are ignored.
+    ///
+    /// This is computed in linear time by quantising the guide into range
+    /// levels `sigma_range` apart on each channel and approximating the
+    /// spatial Gaussian with three box filters. The running time is
+    /// independent of `sigma_spatial` and proportional to the number of pixels
+    /// times the number of range levels the guide's values fall between.
+    fn joint_bilateral_filter(
+        &self,
+        guide: &G,
+        sigma_spatial: f64,
+        sigma_range: f64,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// Solves `a * x = b` in place for a small dense system using Gaussian
+/// elimination with partial pivoting, the solution is left in `b`
+fn solve_linear_system(a: &mut [f64], b: &mut [f64], n: usize) {
+    for col in 0..n {
+        let pivot = (col..n)
+            .max_by(|x, y| a[x * n + col].abs().total_cmp(&a[y * n + col].abs()))
+            .unwrap_or(col);
+        if pivot != col {
+            for k in 0..n {
+                a.swap(pivot * n + k, col * n + k);
+            }
+            b.swap(pivot, col);
+        }
+        let diag = a[col * n + col];
+        if diag == 0.0 {
+            continue;
+        }
+        for row in (col + 1)..n {
+            let factor = a[row * n + col] / diag;
+            for k in col..n {
+                a[row * n + k] -= factor * a[col * n + k];
+            }
+            b[row] -= factor * b[col];
+        }
+    }
+    for col in (0..n).rev() {
+        let mut sum = b[col];
+        for k in (col + 1)..n {
+            sum -= a[col * n + k] * b[k];
+        }
+        let diag = a[col * n + col];
+        b[col] = if diag == 0.0 { 0.0 } else { sum / diag };
+    }
+}
+
+fn guided_filter_impl(
+    image: ArrayView3<f64>,
+    guide: ArrayView3<f64>,
+    radius: usize,
+    epsilon: f64,
+) -> Array3<f64> {
+    let (rows, cols, channels) = image.dim();
+    let k = guide.dim().2;
+    let mean_guide = box_mean(guide, radius);
+    let mean_image = box_mean(image, radius);
+    // Second moments of the guide, only the upper triangle is calculated
+    let mut guide_products = Array3::zeros((rows, cols, k * k));
+    for i in 0..k {
+        for j in i..k {
+            let product = &guide.slice(s![.., .., i]) * &guide.slice(s![.., .., j]);
+            guide_products
+                .slice_mut(s![.., .., i * k + j])
+                .assign(&product);
+        }
+    }
+    let guide_products = box_mean(guide_products.view(), radius);
+
+    let mut result = Array3::zeros((rows, cols, channels));
+    let mut a = vec![0.0; k * k];
+    let mut coeffs = Array3::zeros((rows, cols, k + 1));
+    for chan in 0..channels {
+        let mut cross = Array3::zeros((rows, cols, k));
+        for i in 0..k {
+            let product = &guide.slice(s![.., .., i]) * &image.slice(s![.., .., chan]);
+            cross.slice_mut(s![.., .., i]).assign(&product);
+        }
+        let cross = box_mean(cross.view(), radius);
+        for r in 0..rows {
+            for c in 0..cols {
+                let mean_p = mean_image[[r, c, chan]];
+                let mut b = vec![0.0; k];
+                for i in 0..k {
+                    let mean_i = mean_guide[[r, c, i]];
+                    b[i] = cross[[r, c, i]] - mean_i * mean_p;
+                    for j in i..k {
+                        let covar =
+                            guide_products[[r, c, i * k + j]] - mean_i * mean_guide[[r, c, j]];
+                        a[i * k + j] = covar;
+                        a[j * k + i] = covar;
+                    }
+                    a[i * k + i] += epsilon;
+                }
+                solve_linear_system(&mut a, &mut b, k);
+                let mut offset = mean_p;
+                for i in 0..k {
+                    coeffs[[r, c, i]] = b[i];
+                    offset -= b[i] * mean_guide[[r, c, i]];
+                }
+                coeffs[[r, c, k]] = offset;
+            }
+        }
+        let mean_coeffs = box_mean(coeffs.view(), radius);
+        for r in 0..rows {
+            for c in 0..cols {
+                let mut q = mean_coeffs[[r, c, k]];
+                for i in 0..k {
+                    q += mean_coeffs[[r, c, i]] * guide[[r, c, i]];
+                }
+                result[[r, c, chan]] = q;
+            }
+        }
+    }
+    result
+}
+
+/// Multilinear interpolation weight of a range level for a guide pixel, the
+/// position is the guide value in units of the level spacing
+fn level_weight(position: ArrayView1<f64>, level: &[usize]) -> f64 {
+    position
+        .iter()
+        .zip(level.iter())
+        .map(|(x, l)| (1.0 - (x - *l as f64).abs()).max(0.0))
+        .product()
+}
+
+fn joint_bilateral_impl(
+    image: ArrayView3<f64>,
+    guide: ArrayView3<f64>,
+    sigma_spatial: f64,
+    sigma_range: f64,
+) -> Array3<f64> {
+    let (rows, cols, channels) = image.dim();
+    let k = guide.dim().2;
+    // Three box filters of this radius have close to the variance of the
+    // spatial Gaussian
+    let radius = (((1.0 + 4.0 * sigma_spatial * sigma_spatial).sqrt() - 1.0) / 2.0).round();
+    let radius = radius as usize;
+    // Range levels are `sigma_range` apart on each guide channel, only the
+    // corners of the cells containing a guide pixel are needed
+    let positions = guide.mapv(|x| x / sigma_range);
+    let mut levels = BTreeSet::new();
+    for position in positions.lanes(Axis(2)) {
+        for corner in 0..(1usize << k) {
+            let level = position
+                .iter()
+                .enumerate()
+                .map(|(i, x)| x.floor() as usize + ((corner >> i) & 1))
+                .collect::<Vec<_>>();
+            if level_weight(position, &level) > 0.0 {
+                levels.insert(level);
+            }
+        }
+    }
+
+    let range_denom = 2.0 * sigma_range * sigma_range;
+    let mut result = Array3::zeros((rows, cols, channels));
+    for level in &levels {
+        // Weighted image with the range weights to the level in the last
+        // channel, both are box filtered so their ratio is a weighted mean
+        let mut stack = Array3::zeros((rows, cols, channels + 1));
+        for r in 0..rows {
+            for c in 0..cols {
+                let dist: f64 = guide
+                    .slice(s![r, c, ..])
+                    .iter()
+                    .zip(level.iter())
+                    .map(|(g, l)| (g - *l as f64 * sigma_range).powi(2))
+                    .sum();
+                let w = (-dist / range_denom).exp();
+                for chan in 0..channels {
+                    stack[[r, c, chan]] = w * image[[r, c, chan]];
+                }
+                stack[[r, c, channels]] = w;
+            }
+        }
+        for _ in 0..3 {
+            stack = box_mean(stack.view(), radius);
+        }
+        for r in 0..rows {
+            for c in 0..cols {
+                let a = level_weight(positions.slice(s![r, c, ..]), level);
+                if a > 0.0 {
+                    // The pixel is within a level of its own range weight so
+                    // the filtered weight is non-zero
+                    let weight = stack[[r, c, channels]];
+                    for chan in 0..channels {
+                        result[[r, c, chan]] += a * stack[[r, c, chan]] / weight;
+                    }
+                }
+            }
+        }
+    }
+    result
+}
+
+impl<T, U, V, G> GuidedFilterExt<ArrayBase<G, Ix3>> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    G: Data<Elem = V>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    V: Copy + ToPrimitive + PixelBound,
+{
+    type Output = ArrayBase<OwnedRepr<T>, Ix3>;
+
+    fn guided_filter(
+        &self,
+        guide: &ArrayBase<G, Ix3>,
+        radius: usize,
+        epsilon: f64,
+    ) -> Result<Self::Output, Error> {
+        if self.shape()[..2] != guide.shape()[..2] || guide.shape()[2] == 0 {
+            Err(Error::InvalidDimensions)
+        } else if !(epsilon > 0.0 && epsilon.is_finite()) {
+            Err(Error::InvalidParameter)
+        } else {
+            let image = normalised_array(self).ok_or(Error::NumericError)?;
+            let guide = normalised_array(guide).ok_or(Error::NumericError)?;
+            let result = guided_filter_impl(image.view(), guide.view(), radius, epsilon);
+            Ok(denormalised_array(result))
+        }
+    }
+}
+
+impl<T, U, V, G, C, C2> GuidedFilterExt<ImageBase<G, C2>> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    G: Data<Elem = V>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    V: Copy + ToPrimitive + PixelBound,
+    C: ColourModel,
+    C2: ColourModel,
+{
+    type Output = Image<T, C>;
+
+    fn guided_filter(
+        &self,
+        guide: &ImageBase<G, C2>,
+        radius: usize,
+        epsilon: f64,
+    ) -> Result<Self::Output, Error> {
+        let data = self.data.guided_filter(&guide.data, radius, epsilon)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+impl<T, U, V, G> JointBilateralFilterExt<ArrayBase<G, Ix3>> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    G: Data<Elem = V>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    V: Copy + ToPrimitive + PixelBound,
+{
+    type Output = ArrayBase<OwnedRepr<T>, Ix3>;
+
+    fn joint_bilateral_filter(
+        &self,
+        guide: &ArrayBase<G, Ix3>,
+        sigma_spatial: f64,
+        sigma_range: f64,
+    ) -> Result<Self::Output, Error> {
+        if self.shape()[..2] != guide.shape()[..2] || guide.shape()[2] == 0 {
+            Err(Error::InvalidDimensions)
+        } else if !(sigma_spatial > 0.0 && sigma_spatial.is_finite() && sigma_range > 0.0) {
+            Err(Error::InvalidParameter)
+        } else if self.is_empty() {
+            Ok(self.to_owned())
+        } else {
+            let image = normalised_array(self).ok_or(Error::NumericError)?;
+            let guide = normalised_array(guide).ok_or(Error::NumericError)?;
+            let result =
+                joint_bilateral_impl(image.view(), guide.view(), sigma_spatial, sigma_range);
+            Ok(denormalised_array(result))
+        }
+    }
+}
+
+impl<T, U, V, G, C, C2> JointBilateralFilterExt<ImageBase<G, C2>> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    G: Data<Elem = V>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    V: Copy + ToPrimitive + PixelBound,
+    C: ColourModel,
+    C2: ColourModel,
+{
+    type Output = Image<T, C>;
+
+    fn joint_bilateral_filter(
+        &self,
+        guide: &ImageBase<G, C2>,
+        sigma_spatial: f64,
+        sigma_range: f64,
+    ) -> Result<Self::Output, Error> {
+        let data = self
+            .data
+            .joint_bilateral_filter(&guide.data, sigma_spatial, sigma_range)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, RGB};
+    use crate::processing::filter::{BilateralFilterExt, BilateralRange};
+    use ndarray_rand::rand::{rngs::StdRng, SeedableRng};
+    use ndarray_rand::rand_distr::Normal;
+    use ndarray_rand::RandomExt;
+
+    /// A colour guide with a vertical edge and a noisy depth map with the same
+    /// edge
+    fn depth_and_guide() -> (Image<f64, Gray>, Image<f64, Gray>, Image<u8, RGB>) {
+        let mut clean = Array3::from_elem((16, 16, 1), 0.25);
+        clean.slice_mut(s![.., 8.., ..]).fill(0.75);
+        let mut rng = StdRng::seed_from_u64(5);
+        let noise = Array3::random_using(clean.dim(), Normal::new(0.0, 0.03).unwrap(), &mut rng);
+        let noisy = &clean + &noise;
+
+        let mut guide = Image::<u8, RGB>::new(16, 16);
+        guide
+            .data
+            .slice_mut(s![.., ..8, ..])
+            .assign(&arr1(&[200, 40, 40]));
+        guide
+            .data
+            .slice_mut(s![.., 8.., ..])
+            .assign(&arr1(&[20, 40, 220]));
+        (Image::from_data(clean), Image::from_data(noisy), guide)
+    }
+
+    fn max_error(a: &Image<f64, Gray>, b: &Image<f64, Gray>) -> f64 {
+        (&a.data - &b.data)
+            .iter()
+            .fold(0.0f64, |acc, x| acc.max(x.abs()))
+    }
+
+    #[test]
+    fn guided_filter_follows_guide() {
+        let (clean, noisy, guide) = depth_and_guide();
+        let filtered = noisy.guided_filter(&guide, 3, 1e-4).unwrap();
+        assert!(max_error(&filtered, &clean) < max_error(&noisy, &clean));
+        // Edge is kept sharp
+        for r in 0..16 {
+            assert!(filtered.data[[r, 7, 0]] < 0.35);
+            assert!(filtered.data[[r, 8, 0]] > 0.65);
+        }
+
+        // Filtering an image with itself and a tiny epsilon is the identity
+        let identity = noisy.guided_filter(&noisy, 2, 1e-9).unwrap();
+        assert!(max_error(&identity, &noisy) < 1e-3);
+    }
+
+    #[test]
+    fn joint_bilateral_follows_guide() {
+        let (clean, noisy, guide) = depth_and_guide();
+        let filtered = noisy.joint_bilateral_filter(&guide, 2.0, 0.1).unwrap();
+        assert!(max_error(&filtered, &clean) < max_error(&noisy, &clean));
+        for r in 0..16 {
+            assert!(filtered.data[[r, 7, 0]] < 0.35);
+            assert!(filtered.data[[r, 8, 0]] > 0.65);
+        }
+    }
+
+    #[test]
+    fn joint_bilateral_approximates_bilateral() {
+        // With the image as its own guide this approximates the joint range
+        // bilateral filter
+        let mut rng = StdRng::seed_from_u64(3);
+        let image =
+            Array3::<f64>::random_using((9, 8, 3), Normal::new(0.5, 0.2).unwrap(), &mut rng)
+                .mapv(|x| x.clamp(0.0, 1.0));
+        let joint = image.joint_bilateral_filter(&image, 1.5, 0.2).unwrap();
+        let expected = image
+            .bilateral_filter(1.5, 0.2, BilateralRange::Joint)
+            .unwrap();
+        let errors = (&joint - &expected).mapv(f64::abs);
+        assert!(errors.iter().all(|x| *x < 0.05));
+        assert!(errors.mean().unwrap() < 0.01);
+        assert_eq!(
+            image.joint_bilateral_filter(&image, 1.0, f64::NAN),
+            Err(Error::InvalidParameter)
+        );
+    }
+
+    #[test]
+    fn joint_bilateral_flat_guide() {
+        // A flat guide leaves only the spatial smoothing which keeps a
+        // constant image constant at any scale
+        let image = Array3::from_elem((12, 10, 2), 0.4f64);
+        let guide = Array3::from_elem((12, 10, 1), 0.7);
+        for sigma in [0.5, 2.0, 20.0] {
+            let filtered = image.joint_bilateral_filter(&guide, sigma, 0.1).unwrap();
+            assert!(filtered.iter().all(|x| (x - 0.4).abs() < 1e-12));
+        }
+    }
+
+    #[test]
+    fn guide_dimension_mismatch() {
+        let image = Image::<f64, Gray>::new(5, 5);
+        let guide = Image::<f64, RGB>::new(5, 4);
+        assert_eq!(
+            image.guided_filter(&guide, 1, 0.01),
+            Err(Error::InvalidDimensions)
+        );
+        assert_eq!(
+            image.joint_bilateral_filter(&guide, 1.0, 0.1),
+            Err(Error::InvalidDimensions)
+        );
+    }
+
+    #[test]
+    fn guided_bad_epsilon() {
+        let image = Image::<f64, Gray>::new(5, 5);
+        for epsilon in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                image.guided_filter(&image, 1, epsilon),
+                Err(Error::InvalidParameter)
+            );
+        }
+    }
+}
@@ -0,0 +1,620 @@
+use crate::core::{ColourModel, ImageBase, XorShift};
+use crate::processing::canny::canny_with_gradient;
+use crate::processing::*;
+use ndarray::prelude::*;
+use ndarray::{Data, DataMut, Slice};
+use num_traits::{cast::FromPrimitive, real::Real, Num, NumAssignOps};
+use std::f64::consts::PI;
+
+/// Parameters for the standard Hough line transform
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HoughLineParameters {
+    /// Distance resolution of the accumulator in pixels
+    pub rho_resolution: f64,
+    /// Angle resolution of the accumulator in radians
+    pub theta_resolution: f64,
+    /// Minimum number of votes for a line to be detected
+    pub threshold: u32,
+    /// Radius in accumulator cells of the neighbourhood a line must be the
+    /// maximum of
+    pub suppression_radius: usize,
+}
+
+/// Parameters for the probabilistic Hough line transform
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ProbabilisticHoughParameters {
+    /// Distance resolution of the accumulator in pixels
+    pub rho_resolution: f64,
+    /// Angle resolution of the accumulator in radians
+    pub theta_resolution: f64,
+    /// Minimum number of votes before a line is searched for segments
+    pub threshold: u32,
+    /// Minimum length of a segment in pixels
+    pub min_length: f64,
+    /// Maximum gap in pixels between edge pixels on the same segment
+    pub max_gap: usize,
+    /// Seed for the order edge pixels are processed in, the same seed always
+    /// gives the same result
+    pub seed: u64,
+}
+
+/// Parameters for the gradient based Hough circle transform
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HoughCircleParameters {
+    /// Smallest radius searched for in pixels
+    pub min_radius: usize,
+    /// Largest radius searched for in pixels
+    pub max_radius: usize,
+    /// Minimum number of votes for a circle to be detected
+    pub threshold: u32,
+    /// Minimum distance between the centres of detected circles
+    pub min_distance: f64,
+}
+
+impl Default for HoughLineParameters {
+    fn default() -> Self {
+        Self {
+            rho_resolution: 1.0,
+            theta_resolution: PI / 180.0,
+            threshold: 50,
+            suppression_radius: 2,
+        }
+    }
+}
+
+impl Default for ProbabilisticHoughParameters {
+    fn default() -> Self {
+        Self {
+            rho_resolution: 1.0,
+            theta_resolution: PI / 180.0,
+            threshold: 20,
+            min_length: 20.0,
+            max_gap: 3,
+            seed: 0,
+        }
+    }
+}
+
+/// A line `col * cos(theta) + row * sin(theta) = rho`
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HoughLine {
+    /// Signed distance from the origin to the line
+    pub rho: f64,
+    /// Angle of the line's normal in `[0, π)`
+    pub theta: f64,
+    /// Number of votes in the accumulator
+    pub votes: u32,
+}
+
+/// A line segment between two edge pixels given as `(row, col)`
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LineSegment {
+    /// First end of the segment
+    pub start: (usize, usize),
+    /// Second end of the segment
+    pub end: (usize, usize),
+}
+
+/// A circle found by the Hough circle transform
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct HoughCircle {
+    /// Row of the centre
+    pub row: usize,
+    /// Column of the centre
+    pub col: usize,
+    /// Radius in pixels
+    pub radius: usize,
+    /// Number of votes in the accumulator
+    pub votes: u32,
+}
+
+/// Output of the Hough transforms, the accumulator and the detected shapes
+/// ordered from the most to least votes
+#[derive(Clone, PartialEq, Debug)]
+pub struct HoughResult<A, P> {
+    /// Accumulated votes
+    pub accumulator: A,
+    /// The shapes found
+    pub detections: Vec<P>,
+}
+
+/// Hough line transforms operating on a single channel binary edge image such
+/// as the output of the Canny edge detector
+pub trait HoughLinesExt {
+    /// Runs the standard Hough transform. The accumulator is indexed by
+    /// `[rho, theta]` where rho starts at minus the image diagonal.
+    fn hough_lines(
+        &self,
+        params: HoughLineParameters,
+    ) -> Result<HoughResult<Array2<u32>, HoughLine>, Error>;
+
+    /// Runs the progressive probabilistic Hough transform returning line
+    /// segments. Pixels belonging to a detected segment are removed from the
+    /// accumulator so the returned accumulator only holds the remaining votes.
+    fn probabilistic_hough_lines(
+        &self,
+        params: ProbabilisticHoughParameters,
+    ) -> Result<HoughResult<Array2<u32>, LineSegment>, Error>;
+}
+
+/// Hough circle transform, edges are found with the Canny edge detector and
+/// each edge pixel votes along its gradient direction
+pub trait HoughCirclesExt<T> {
+    /// Find circles in the image. The accumulator is indexed by
+    /// `[row, col, radius - min_radius]`.
+    fn hough_circles(
+        &self,
+        canny: CannyParameters<T>,
+        params: HoughCircleParameters,
+    ) -> Result<HoughResult<Array3<u32>, HoughCircle>, Error>;
+}
+
+/// Discretisation of the line parameter space
+struct LineSpace {
+    rho_resolution: f64,
+    rho_offset: usize,
+    rho_bins: usize,
+    trig: Vec<(f64, f64)>,
+    theta_resolution: f64,
+}
+
+impl LineSpace {
+    fn new(
+        shape: (usize, usize),
+        rho_resolution: f64,
+        theta_resolution: f64,
+    ) -> Result<Self, Error> {
+        if rho_resolution <= 0.0 || theta_resolution <= 0.0 || theta_resolution > PI {
+            return Err(Error::InvalidParameter);
+        }
+        let diagonal = (shape.0 as f64).hypot(shape.1 as f64);
+        let rho_offset = (diagonal / rho_resolution).ceil() as usize;
+        let thetas = (PI / theta_resolution).round().max(1.0) as usize;
+        let theta_resolution = PI / thetas as f64;
+        let trig = (0..thetas)
+            .map(|t| (t as f64 * theta_resolution).sin_cos())
+            .collect();
+        Ok(Self {
+            rho_resolution,
+            rho_offset,
+            rho_bins: 2 * rho_offset + 1,
+            trig,
+            theta_resolution,
+        })
+    }
+
+    fn accumulator(&self) -> Array2<u32> {
+        Array2::zeros((self.rho_bins, self.trig.len()))
+    }
+
+    fn rho_index(&self, (row, col): (usize, usize), theta: usize) -> usize {
+        let (sin, cos) = self.trig[theta];
+        let rho = col as f64 * cos + row as f64 * sin;
+        ((rho / self.rho_resolution).round() as isize + self.rho_offset as isize) as usize
+    }
+
+    fn line(&self, rho: usize, theta: usize, votes: u32) -> HoughLine {
+        HoughLine {
+            rho: (rho as f64 - self.rho_offset as f64) * self.rho_resolution,
+            theta: theta as f64 * self.theta_resolution,
+            votes,
+        }
+    }
+}
+
+/// Returns the edge pixels of a single channel binary image
+fn edge_pixels<U>(edges: &ArrayBase<U, Ix3>) -> Result<Vec<(usize, usize)>, Error>
+where
+    U: Data<Elem = bool>,
+{
+    if edges.shape()[2] != 1 {
+        return Err(Error::ChannelDimensionMismatch);
+    }
+    Ok(edges
+        .indexed_iter()
+        .filter(|(_, e)| **e)
+        .map(|((r, c, _), _)| (r, c))
+        .collect())
+}
+
+/// Indexes of the cells that are at least the threshold and the maximum of the
+/// neighbourhood of the given radius, ties are won by the first cell in raster
+/// order. Sorted by decreasing votes
+fn local_maxima(accumulator: ArrayViewD<u32>, threshold: u32, radius: usize) -> Vec<Vec<usize>> {
+    let mut maxima = vec![];
+    for (index, votes) in accumulator.indexed_iter() {
+        if *votes == 0 || *votes < threshold {
+            continue;
+        }
+        let index = index.slice().to_vec();
+        let starts = index
+            .iter()
+            .map(|i| i.saturating_sub(radius))
+            .collect::<Vec<_>>();
+        let window = accumulator.slice_each_axis(|ax| {
+            let i = index[ax.axis.index()];
+            Slice::from(i.saturating_sub(radius)..(i + radius + 1).min(ax.len))
+        });
+        let is_max = window.indexed_iter().all(|(offset, other)| {
+            let other_index = offset
+                .slice()
+                .iter()
+                .zip(starts.iter())
+                .map(|(o, s)| o + s)
+                .collect::<Vec<_>>();
+            *other < *votes || (*other == *votes && other_index >= index)
+        });
+        if is_max {
+            maxima.push((*votes, index));
+        }
+    }
+    // Stable sort keeps raster order for equal votes
+    maxima.sort_by_key(|m| std::cmp::Reverse(m.0));
+    maxima.into_iter().map(|(_, i)| i).collect()
+}
+
+impl<U> HoughLinesExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = bool>,
+{
+    fn hough_lines(
+        &self,
+        params: HoughLineParameters,
+    ) -> Result<HoughResult<Array2<u32>, HoughLine>, Error> {
+        let pixels = edge_pixels(self)?;
+        let space = LineSpace::new(
+            (self.dim().0, self.dim().1),
+            params.rho_resolution,
+            params.theta_resolution,
+        )?;
+        let mut accumulator = space.accumulator();
+        for p in &pixels {
+            for theta in 0..space.trig.len() {
+                accumulator[[space.rho_index(*p, theta), theta]] += 1;
+            }
+        }
+        let maxima = local_maxima(
+            accumulator.view().into_dyn(),
+            params.threshold,
+            params.suppression_radius,
+        );
+        // The theta axis wraps around with rho negated so a line near theta = 0
+        // is also a neighbour of cells near theta = π
+        let thetas = space.trig.len();
+        let radius = params.suppression_radius;
+        let mut accepted: Vec<Vec<usize>> = vec![];
+        for index in maxima {
+            let wrapped = accepted.iter().any(|other| {
+                let theta_distance = (index[1] as isize - other[1] as isize).unsigned_abs();
+                let mirrored = 2 * space.rho_offset - other[0];
+                thetas - theta_distance <= radius
+                    && (index[0] as isize - mirrored as isize).unsigned_abs() <= radius
+            });
+            if !wrapped {
+                accepted.push(index);
+            }
+        }
+        let detections = accepted
+            .into_iter()
+            .map(|i| space.line(i[0], i[1], accumulator[[i[0], i[1]]]))
+            .collect();
+        Ok(HoughResult {
+            accumulator,
+            detections,
+        })
+    }
+
+    fn probabilistic_hough_lines(
+        &self,
+        params: ProbabilisticHoughParameters,
+    ) -> Result<HoughResult<Array2<u32>, LineSegment>, Error> {
+        let mut pixels = edge_pixels(self)?;
+        let (rows, cols, _) = self.dim();
+        let space = LineSpace::new((rows, cols), params.rho_resolution, params.theta_resolution)?;
+        let mut accumulator = space.accumulator();
+        let mut remaining = Array2::from_shape_fn((rows, cols), |(r, c)| self[[r, c, 0]]);
+        let mut voted = Array2::from_elem((rows, cols), false);
+        XorShift::new(params.seed).shuffle(&mut pixels);
+
+        let mut detections = vec![];
+        for point in pixels {
+            if !remaining[point] {
+                continue;
+            }
+            voted[point] = true;
+            let mut best = (0, 0);
+            for theta in 0..space.trig.len() {
+                let cell = &mut accumulator[[space.rho_index(point, theta), theta]];
+                *cell += 1;
+                if *cell > best.0 {
+                    best = (*cell, theta);
+                }
+            }
+            if best.0 < params.threshold {
+                continue;
+            }
+
+            // Walk along the line in both directions to find the segment ends
+            let (sin, cos) = space.trig[best.1];
+            let scale = sin.abs().max(cos.abs());
+            let step = (cos / scale, -sin / scale);
+            let walk = |direction: f64, remaining: &Array2<bool>| {
+                let mut end = point;
+                let mut gap = 0;
+                for k in 1.. {
+                    let r = (point.0 as f64 + direction * k as f64 * step.0).round();
+                    let c = (point.1 as f64 + direction * k as f64 * step.1).round();
+                    if r < 0.0 || c < 0.0 || r >= rows as f64 || c >= cols as f64 {
+                        break;
+                    }
+                    let p = (r as usize, c as usize);
+                    if remaining[p] {
+                        end = p;
+                        gap = 0;
+                    } else {
+                        gap += 1;
+                        if gap > params.max_gap {
+                            break;
+                        }
+                    }
+                }
+                end
+            };
+            let start = walk(-1.0, &remaining);
+            let end = walk(1.0, &remaining);
+            let length = (start.0 as f64 - end.0 as f64).hypot(start.1 as f64 - end.1 as f64);
+            if length < params.min_length {
+                continue;
+            }
+
+            // Remove the segment's pixels so they don't contribute to other lines
+            let steps = (start.0 as isize - end.0 as isize)
+                .abs()
+                .max((start.1 as isize - end.1 as isize).abs());
+            for k in 0..=steps {
+                let t = if steps == 0 {
+                    0.0
+                } else {
+                    k as f64 / steps as f64
+                };
+                let r = (start.0 as f64 + t * (end.0 as f64 - start.0 as f64)).round() as usize;
+                let c = (start.1 as f64 + t * (end.1 as f64 - start.1 as f64)).round() as usize;
+                let p = (r, c);
+                if remaining[p] {
+                    remaining[p] = false;
+                    if voted[p] {
+                        for theta in 0..space.trig.len() {
+                            accumulator[[space.rho_index(p, theta), theta]] -= 1;
+                        }
+                    }
+                }
+            }
+            detections.push(LineSegment { start, end });
+        }
+        Ok(HoughResult {
+            accumulator,
+            detections,
+        })
+    }
+}
+
+impl<U, C> HoughLinesExt for ImageBase<U, C>
+where
+    U: Data<Elem = bool>,
+    C: ColourModel,
+{
+    fn hough_lines(
+        &self,
+        params: HoughLineParameters,
+    ) -> Result<HoughResult<Array2<u32>, HoughLine>, Error> {
+        self.data.hough_lines(params)
+    }
+
+    fn probabilistic_hough_lines(
+        &self,
+        params: ProbabilisticHoughParameters,
+    ) -> Result<HoughResult<Array2<u32>, LineSegment>, Error> {
+        self.data.probabilistic_hough_lines(params)
+    }
+}
+
+impl<T, U> HoughCirclesExt<T> for ArrayBase<U, Ix3>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+{
+    fn hough_circles(
+        &self,
+        canny: CannyParameters<T>,
+        params: HoughCircleParameters,
+    ) -> Result<HoughResult<Array3<u32>, HoughCircle>, Error> {
+        if params.min_radius == 0 || params.max_radius < params.min_radius {
+            return Err(Error::InvalidParameter);
+        }
+        let output = canny_with_gradient(self, canny)?;
+        let (rows, cols, _) = self.dim();
+        let radii = params.max_radius - params.min_radius + 1;
+        let mut accumulator = Array3::zeros((rows, cols, radii));
+        for ((r, c, _), edge) in output.edges.indexed_iter() {
+            if !*edge {
+                continue;
+            }
+            let (sin, cos) = output.orientation[[r, c, 0]]
+                .to_f64()
+                .ok_or(Error::NumericError)?
+                .sin_cos();
+            for i in 0..radii {
+                let radius = (params.min_radius + i) as f64;
+                // The centre could be on either side depending on the contrast
+                for direction in &[-1.0, 1.0] {
+                    let cr = (r as f64 + direction * radius * sin).round();
+                    let cc = (c as f64 + direction * radius * cos).round();
+                    if cr >= 0.0 && cc >= 0.0 && cr < rows as f64 && cc < cols as f64 {
+                        accumulator[[cr as usize, cc as usize, i]] += 1;
+                    }
+                }
+            }
+        }
+
+        let mut detections: Vec<HoughCircle> = vec![];
+        for index in local_maxima(accumulator.view().into_dyn(), params.threshold, 1) {
+            let (row, col, i) = (index[0], index[1], index[2]);
+            let too_close = detections.iter().any(|d| {
+                (d.row as f64 - row as f64).hypot(d.col as f64 - col as f64) < params.min_distance
+            });
+            if !too_close {
+                detections.push(HoughCircle {
+                    row,
+                    col,
+                    radius: params.min_radius + i,
+                    votes: accumulator[[row, col, i]],
+                });
+            }
+        }
+        Ok(HoughResult {
+            accumulator,
+            detections,
+        })
+    }
+}
+
+impl<T, U, C> HoughCirclesExt<T> for ImageBase<U, C>
+where
+    U: DataMut<Elem = T>,
+    T: Copy + Clone + FromPrimitive + Real + Num + NumAssignOps,
+    C: ColourModel,
+{
+    fn hough_circles(
+        &self,
+        canny: CannyParameters<T>,
+        params: HoughCircleParameters,
+    ) -> Result<HoughResult<Array3<u32>, HoughCircle>, Error> {
+        self.data.hough_circles(canny, params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, Image};
+
+    fn draw_line(image: &mut Array3<bool>, start: (usize, usize), end: (usize, usize)) {
+        let steps = (start.0 as isize - end.0 as isize)
+            .abs()
+            .max((start.1 as isize - end.1 as isize).abs());
+        for k in 0..=steps {
+            let t = k as f64 / steps as f64;
+            let r = (start.0 as f64 + t * (end.0 as f64 - start.0 as f64)).round() as usize;
+            let c = (start.1 as f64 + t * (end.1 as f64 - start.1 as f64)).round() as usize;
+            image[[r, c, 0]] = true;
+        }
+    }
+
+    #[test]
+    fn standard_hough_lines() {
+        let mut edges = Array3::from_elem((40, 40, 1), false);
+        draw_line(&mut edges, (10, 2), (10, 37));
+        draw_line(&mut edges, (0, 5), (39, 5));
+        let params = HoughLineParameters {
+            threshold: 25,
+            ..Default::default()
+        };
+        let result = edges.hough_lines(params).unwrap();
+        assert_eq!(result.accumulator.dim().1, 180);
+        assert_eq!(result.detections.len(), 2);
+
+        let vertical = result.detections[0];
+        assert_eq!(vertical.votes, 40);
+        assert!(vertical.theta.abs() < 1e-10);
+        assert!((vertical.rho - 5.0).abs() < 1e-10);
+
+        let horizontal = result.detections[1];
+        assert_eq!(horizontal.votes, 36);
+        assert!((horizontal.theta - PI / 2.0).abs() < 1e-10);
+        assert!((horizontal.rho - 10.0).abs() < 1e-10);
+
+        let image = Image::<bool, Gray>::from_data(edges);
+        assert_eq!(image.hough_lines(params).unwrap(), result);
+    }
+
+    #[test]
+    fn probabilistic_hough_lines() {
+        let mut edges = Array3::from_elem((40, 40, 1), false);
+        // A segment with a small gap and a diagonal segment
+        draw_line(&mut edges, (20, 5), (20, 15));
+        draw_line(&mut edges, (20, 18), (20, 30));
+        draw_line(&mut edges, (5, 5), (15, 15));
+        // A short isolated segment
+        draw_line(&mut edges, (35, 30), (35, 33));
+        let params = ProbabilisticHoughParameters {
+            threshold: 5,
+            min_length: 10.0,
+            max_gap: 3,
+            ..Default::default()
+        };
+        let result = edges.probabilistic_hough_lines(params).unwrap();
+        let mut segments = result
+            .detections
+            .iter()
+            .map(|s| {
+                if s.start < s.end {
+                    (s.start, s.end)
+                } else {
+                    (s.end, s.start)
+                }
+            })
+            .collect::<Vec<_>>();
+        segments.sort();
+        assert_eq!(segments, vec![((5, 5), (15, 15)), ((20, 5), (20, 30))]);
+        // Results are repeatable for the same seed
+        assert_eq!(edges.probabilistic_hough_lines(params).unwrap(), result);
+
+        let params = ProbabilisticHoughParameters {
+            max_gap: 1,
+            ..params
+        };
+        let result = edges.probabilistic_hough_lines(params).unwrap();
+        assert_eq!(result.detections.len(), 3);
+    }
+
+    #[test]
+    fn gradient_hough_circles() {
+        let circles = [(20, 22, 8), (45, 40, 12)];
+        let data = Array3::from_shape_fn((64, 64, 1), |(r, c, _)| {
+            let inside = circles.iter().any(|(cr, cc, rad)| {
+                (r as f64 - *cr as f64).hypot(c as f64 - *cc as f64) <= *rad as f64
+            });
+            if inside {
+                1.0
+            } else {
+                0.0
+            }
+        });
+        let image = Image::<f64, Gray>::from_data(data);
+        let canny = CannyBuilder::<f64>::new()
+            .lower_threshold(0.5)
+            .upper_threshold(1.0)
+            .blur((3, 3), [0.5, 0.5])
+            .build();
+        let params = HoughCircleParameters {
+            min_radius: 5,
+            max_radius: 15,
+            threshold: 12,
+            min_distance: 10.0,
+        };
+        let result = image.hough_circles(canny, params).unwrap();
+        assert_eq!(result.accumulator.dim(), (64, 64, 11));
+        assert_eq!(result.detections.len(), 2);
+        let mut found = result
+            .detections
+            .iter()
+            .map(|c| (c.row, c.col, c.radius))
+            .collect::<Vec<_>>();
+        found.sort();
+        for (expected, actual) in circles.iter().zip(found.iter()) {
+            assert!((expected.0 as isize - actual.0 as isize).abs() <= 1);
+            assert!((expected.1 as isize - actual.1 as isize).abs() <= 1);
+            assert!((expected.2 as isize - actual.2 as isize).abs() <= 1);
+        }
+    }
+}
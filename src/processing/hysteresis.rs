@@ -0,0 +1,196 @@
+use crate::core::{ColourModel, Image, ImageBase};
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::Data;
+
+/// Which neighbouring pixels are considered connected
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum Connectivity {
+    /// Only the pixels above, below, left and right are connected
+    Four,
+    /// The diagonal pixels are also connected
+    Eight,
+}
+
+impl Connectivity {
+    /// Row and column offsets of the connected neighbours
+    pub(crate) fn offsets(self) -> &'static [(isize, isize)] {
+        match self {
+            Connectivity::Four => &[(-1, 0), (0, -1), (0, 1), (1, 0)],
+            Connectivity::Eight => &[
+                (-1, -1),
+                (-1, 0),
+                (-1, 1),
+                (0, -1),
+                (0, 1),
+                (1, -1),
+                (1, 0),
+                (1, 1),
+            ],
+        }
+    }
+}
+
+/// Hysteresis thresholding, pixels greater than or equal to the high threshold
+/// are kept along with any pixels greater than the low threshold connected to
+/// them through other such pixels.
+pub trait HysteresisThresholdExt<T> {
+    /// Output type, a binary image
+    type Output;
+
+    /// Applies hysteresis thresholding to a single channel image.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ChannelDimensionMismatch` error if the image has more than
+    /// one channel and an `InvalidParameter` error if `low` is greater than
+    /// `high`.
+    fn threshold_hysteresis(
+        &self,
+        low: T,
+        high: T,
+        connectivity: Connectivity,
+    ) -> Result<Self::Output, Error>;
+}
+
+impl<T, U> HysteresisThresholdExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + PartialOrd,
+{
+    type Output = Array3<bool>;
+
+    fn threshold_hysteresis(
+        &self,
+        low: T,
+        high: T,
+        connectivity: Connectivity,
+    ) -> Result<Self::Output, Error> {
+        if self.shape()[2] != 1 {
+            return Err(Error::ChannelDimensionMismatch);
+        }
+        if low > high {
+            return Err(Error::InvalidParameter);
+        }
+        let result = hysteresis(self.index_axis(Axis(2), 0), low, high, connectivity);
+        Ok(result.insert_axis(Axis(2)))
+    }
+}
+
+impl<T, U, C> HysteresisThresholdExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + PartialOrd,
+    C: ColourModel,
+{
+    type Output = Image<bool, C>;
+
+    fn threshold_hysteresis(
+        &self,
+        low: T,
+        high: T,
+        connectivity: Connectivity,
+    ) -> Result<Self::Output, Error> {
+        let data = self.data.threshold_hysteresis(low, high, connectivity)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+/// Grows the regions greater than or equal to `high` into the connected pixels
+/// greater than `low`
+pub(crate) fn hysteresis<T>(
+    image: ArrayView2<T>,
+    low: T,
+    high: T,
+    connectivity: Connectivity,
+) -> Array2<bool>
+where
+    T: Copy + PartialOrd,
+{
+    let (rows, cols) = image.dim();
+    let mut result = image.mapv(|x| x >= high);
+    let mut stack = result
+        .indexed_iter()
+        .filter(|(_, strong)| **strong)
+        .map(|(index, _)| index)
+        .collect::<Vec<_>>();
+
+    while let Some((r, c)) = stack.pop() {
+        for (dr, dc) in connectivity.offsets() {
+            let nr = r as isize + dr;
+            let nc = c as isize + dc;
+            if nr < 0 || nc < 0 || nr >= rows as isize || nc >= cols as isize {
+                continue;
+            }
+            let neighbour = (nr as usize, nc as usize);
+            if !result[neighbour] && image[neighbour] > low {
+                result[neighbour] = true;
+                stack.push(neighbour);
+            }
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, RGB};
+    use ndarray::arr3;
+
+    #[test]
+    fn hysteresis_connectivity() {
+        let image = Image::<u8, Gray>::from_data(arr3(&[
+            [[9], [0], [0], [0]],
+            [[0], [5], [5], [0]],
+            [[0], [0], [0], [5]],
+            [[5], [0], [0], [2]],
+        ]));
+        let eight = image
+            .threshold_hysteresis(3, 8, Connectivity::Eight)
+            .unwrap();
+        let expected = arr3(&[
+            [[true], [false], [false], [false]],
+            [[false], [true], [true], [false]],
+            [[false], [false], [false], [true]],
+            [[false], [false], [false], [false]],
+        ]);
+        assert_eq!(eight.data, expected);
+
+        // The weak pixels are only diagonally connected to the strong one
+        let four = image
+            .threshold_hysteresis(3, 8, Connectivity::Four)
+            .unwrap();
+        assert_eq!(four.data, image.data.mapv(|x| x == 9));
+
+        // Every pixel is strong or next to a strong one
+        let all = image
+            .threshold_hysteresis(1, 5, Connectivity::Four)
+            .unwrap();
+        assert_eq!(all.data, image.data.mapv(|x| x > 1));
+    }
+
+    #[test]
+    fn hysteresis_thresholds() {
+        // Pixels equal to the low threshold aren't linked
+        let image = arr3(&[[[0.9], [0.4], [0.5]]]);
+        let result = image
+            .threshold_hysteresis(0.4, 0.8, Connectivity::Eight)
+            .unwrap();
+        assert_eq!(result, arr3(&[[[true], [false], [false]]]));
+        let result = image
+            .threshold_hysteresis(0.3, 0.8, Connectivity::Eight)
+            .unwrap();
+        assert!(result.iter().all(|x| *x));
+
+        assert_eq!(
+            image.threshold_hysteresis(0.8, 0.4, Connectivity::Four),
+            Err(Error::InvalidParameter)
+        );
+        let colour = Image::<f64, RGB>::new(2, 2);
+        assert_eq!(
+            colour.threshold_hysteresis(0.4, 0.8, Connectivity::Four),
+            Err(Error::ChannelDimensionMismatch)
+        );
+    }
+}
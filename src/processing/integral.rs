@@ -0,0 +1,109 @@
+use crate::core::{ColourModel, ImageBase};
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+
+/// Computes the integral image (also known as a summed area table) where each
+/// element is the sum of all the pixels above and to the left of it. This
+/// allows the sum of any rectangular region to be found in constant time.
+pub trait IntegralImageExt {
+    /// Returns the integral image for each channel. The output has an extra
+    /// leading row and column of zeros so element `[r, c, chan]` is the sum of
+    /// all pixels in rows `0..r` and columns `0..c`.
+    fn integral_image(&self) -> Result<Array3<f64>, Error>;
+}
+
+impl<T, U> IntegralImageExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn integral_image(&self) -> Result<Array3<f64>, Error> {
+        let (rows, cols, channels) = self.dim();
+        let mut result = Array3::zeros((rows + 1, cols + 1, channels));
+        for r in 0..rows {
+            for c in 0..cols {
+                for chan in 0..channels {
+                    let x = self[[r, c, chan]].to_f64().ok_or(Error::NumericError)?;
+                    result[[r + 1, c + 1, chan]] =
+                        x + result[[r, c + 1, chan]] + result[[r + 1, c, chan]]
+                            - result[[r, c, chan]];
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl<T, U, C> IntegralImageExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    fn integral_image(&self) -> Result<Array3<f64>, Error> {
+        self.data.integral_image()
+    }
+}
+
+/// Mean of every `(2 * radius + 1)` square window in the image using an
+/// integral image. Near the borders the window is cropped to the image so the
+/// mean is only taken over pixels in the image.
+pub(crate) fn box_mean(data: ArrayView3<f64>, radius: usize) -> Array3<f64> {
+    // Integral image of floats can't fail
+    let integral = data.integral_image().unwrap();
+    let (rows, cols, channels) = data.dim();
+    Array3::from_shape_fn((rows, cols, channels), |(r, c, chan)| {
+        let top = r.saturating_sub(radius);
+        let left = c.saturating_sub(radius);
+        let bottom = (r + radius + 1).min(rows);
+        let right = (c + radius + 1).min(cols);
+        let sum = integral[[bottom, right, chan]]
+            - integral[[top, right, chan]]
+            - integral[[bottom, left, chan]]
+            + integral[[top, left, chan]];
+        sum / ((bottom - top) * (right - left)) as f64
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, Image};
+
+    #[test]
+    #[rustfmt::skip]
+    fn simple_integral_image() {
+        let image = Image::<u8, Gray>::from_shape_data(3, 3, vec![
+            1, 2, 3,
+            4, 5, 6,
+            7, 8, 9,
+        ]);
+        let integral = image.integral_image().unwrap();
+        let expected = arr2(&[
+            [0.0, 0.0, 0.0, 0.0],
+            [0.0, 1.0, 3.0, 6.0],
+            [0.0, 5.0, 12.0, 21.0],
+            [0.0, 12.0, 27.0, 45.0],
+        ]);
+        assert_eq!(integral.slice(s![.., .., 0]), expected);
+    }
+
+    #[test]
+    fn box_mean_matches_brute_force() {
+        let data = Array3::from_shape_fn((7, 6, 2), |(r, c, chan)| {
+            ((r * 7 + c * 3 + chan * 5) % 11) as f64
+        });
+        let radius = 2;
+        let mean = box_mean(data.view(), radius);
+        for ((r, c, chan), v) in mean.indexed_iter() {
+            let window = data.slice(s![
+                r.saturating_sub(radius)..(r + radius + 1).min(7),
+                c.saturating_sub(radius)..(c + radius + 1).min(6),
+                chan
+            ]);
+            assert!((window.mean().unwrap() - v).abs() < 1e-10);
+        }
+    }
+}
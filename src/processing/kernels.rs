@@ -91,6 +91,23 @@ where
     }
 }
 
+/// Default standard deviation for a Gaussian based kernel of the given width,
+/// this recommendation was taken from OpenCV 2.4 docs
+fn default_sigma(size: usize) -> f64 {
+    0.3 * (((std::cmp::max(size, 1) - 1) as f64) * 0.5 - 1.0) + 0.8
+}
+
+/// Returns true if the kernel shape is square with an odd width and at least
+/// one channel
+fn is_odd_square(shape: Ix3) -> bool {
+    shape[0] & 1 == 1 && shape[0] == shape[1] && shape[2] > 0
+}
+
+/// Returns true for finite values greater than zero, this rejects NaN
+fn is_positive(x: f64) -> bool {
+    x > 0.0 && x.is_finite()
+}
+
 /// Builds a Gaussian kernel taking the covariance as a parameter. Covariance
 /// is given as 2 values for the x and y variance.
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -112,9 +129,7 @@ where
     where
         D: Copy + IntoDimension<Dim = Ix3>,
     {
-        // This recommendation was taken from OpenCV 2.4 docs
-        let s = shape.into_dimension();
-        let sig = 0.3 * (((std::cmp::max(s[0], 1) - 1) as f64) * 0.5 - 1.0) + 0.8;
+        let sig = default_sigma(shape.into_dimension()[0]);
         Self::build_with_params(shape, [sig, sig])
     }
 
@@ -152,6 +167,13 @@ where
     }
 }
 
+/// Returns the width of a Gaussian kernel for the given standard deviation.
+/// The kernel is always odd and covers three standard deviations either side
+/// of the centre
+pub(crate) fn gaussian_kernel_size(sigma: f64) -> usize {
+    2 * ((3.0 * sigma).ceil().max(1.0) as usize) + 1
+}
+
 /// The box linear filter is roughly defined as `1/(R*C)*Array2::ones((R, C))`
 /// This filter will be a box linear for every colour channel provided
 #[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
@@ -235,6 +257,409 @@ where
     }
 }
 
+/// Builder to create either a horizontal or vertical Scharr filter. The Scharr
+/// operator is similar to Sobel but has better rotational symmetry
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ScharrFilter;
+
+impl<T> FixedDimensionKernelBuilder<T> for ScharrFilter
+where
+    T: Copy + Clone + Num + Neg<Output = T> + FromPrimitive,
+{
+    /// Orientation of the filter. Default is vertical
+    type Params = Orientation;
+
+    fn build() -> Result<Array3<T>, Error> {
+        Self::build_with_params(Orientation::Vertical)
+    }
+
+    fn build_with_params(p: Self::Params) -> Result<Array3<T>, Error> {
+        let three = T::from_i8(3).ok_or(Error::NumericError)?;
+        let ten = T::from_i8(10).ok_or(Error::NumericError)?;
+        // Gets the gradient along the horizontal axis
+        #[rustfmt::skip]
+        let horz_scharr = arr2(&[
+            [three, T::zero(), -three],
+            [ten,   T::zero(), -ten],
+            [three, T::zero(), -three],
+        ]);
+        let scharr = match p {
+            Orientation::Vertical => horz_scharr.t().to_owned(),
+            Orientation::Horizontal => horz_scharr,
+        };
+        Ok(scharr.insert_axis(Axis(2)))
+    }
+}
+
+/// Builder to create either a horizontal or vertical Prewitt filter
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct PrewittFilter;
+
+impl<T> FixedDimensionKernelBuilder<T> for PrewittFilter
+where
+    T: Copy + Clone + Num + Neg<Output = T> + FromPrimitive,
+{
+    /// Orientation of the filter. Default is vertical
+    type Params = Orientation;
+
+    fn build() -> Result<Array3<T>, Error> {
+        Self::build_with_params(Orientation::Vertical)
+    }
+
+    fn build_with_params(p: Self::Params) -> Result<Array3<T>, Error> {
+        // Gets the gradient along the horizontal axis
+        #[rustfmt::skip]
+        let horz_prewitt = arr2(&[
+            [T::one(), T::zero(), -T::one()],
+            [T::one(), T::zero(), -T::one()],
+            [T::one(), T::zero(), -T::one()],
+        ]);
+        let prewitt = match p {
+            Orientation::Vertical => horz_prewitt.t().to_owned(),
+            Orientation::Horizontal => horz_prewitt,
+        };
+        Ok(prewitt.insert_axis(Axis(2)))
+    }
+}
+
+/// Builder to create a 2x2 Roberts cross filter. The Roberts cross takes the
+/// gradient along the diagonals, `Orientation::Horizontal` gives the diagonal
+/// from the top left to bottom right and `Orientation::Vertical` the diagonal
+/// from the top right to bottom left.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct RobertsFilter;
+
+impl<T> FixedDimensionKernelBuilder<T> for RobertsFilter
+where
+    T: Copy + Clone + Num + Neg<Output = T> + FromPrimitive,
+{
+    /// Orientation of the filter. Default is vertical
+    type Params = Orientation;
+
+    fn build() -> Result<Array3<T>, Error> {
+        Self::build_with_params(Orientation::Vertical)
+    }
+
+    fn build_with_params(p: Self::Params) -> Result<Array3<T>, Error> {
+        let roberts = match p {
+            Orientation::Horizontal => arr2(&[[T::one(), T::zero()], [T::zero(), -T::one()]]),
+            Orientation::Vertical => arr2(&[[T::zero(), T::one()], [-T::one(), T::zero()]]),
+        };
+        Ok(roberts.insert_axis(Axis(2)))
+    }
+}
+
+/// Evaluates a function at each position of an odd square kernel given the
+/// column and row offset from the centre, replicating it over every channel
+fn kernel_from_offsets<T, F>(shape: Ix3, f: F) -> Result<Array3<T>, Error>
+where
+    T: Copy + FromPrimitive,
+    F: Fn(f64, f64) -> f64,
+{
+    let centre = (shape[0] / 2) as f64;
+    let temp = Array2::from_shape_fn((shape[0], shape[1]), |(r, c)| {
+        f(c as f64 - centre, r as f64 - centre)
+    });
+    let temp = temp.mapv(T::from_f64);
+    if temp.iter().any(|x| x.is_none()) {
+        Err(Error::NumericError)
+    } else {
+        Ok(Array3::from_shape_fn(shape, |(r, c, _)| {
+            temp[[r, c]].unwrap()
+        }))
+    }
+}
+
+/// Normalised Gaussian evaluated over a kernel of the given width
+fn gaussian_weights(size: usize, sigma: f64) -> Array2<f64> {
+    let centre = (size / 2) as f64;
+    let mut weights = Array2::from_shape_fn((size, size), |(r, c)| {
+        let (x, y) = (c as f64 - centre, r as f64 - centre);
+        (-(x * x + y * y) / (2.0 * sigma * sigma)).exp()
+    });
+    let sum = weights.sum();
+    weights /= sum;
+    weights
+}
+
+/// Builds a Laplacian of Gaussian kernel, this combines Gaussian smoothing with
+/// the second spatial derivative and is commonly used for blob and edge
+/// detection. The kernel has a negative centre and is adjusted to sum to zero.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct LaplacianOfGaussianFilter;
+
+impl<T> KernelBuilder<T> for LaplacianOfGaussianFilter
+where
+    T: Copy + Clone + FromPrimitive + Num,
+{
+    /// Standard deviation of the Gaussian
+    type Params = f64;
+
+    fn build<D>(shape: D) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        Self::build_with_params(shape, default_sigma(shape.into_dimension()[0]))
+    }
+
+    fn build_with_params<D>(shape: D, sigma: Self::Params) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        let shape = shape.into_dimension();
+        if !is_odd_square(shape) {
+            Err(Error::InvalidDimensions)
+        } else if !is_positive(sigma) {
+            Err(Error::InvalidParameter)
+        } else {
+            let var = sigma * sigma;
+            let log = |x: f64, y: f64| {
+                let r2 = (x * x + y * y) / (2.0 * var);
+                -(1.0 - r2) * (-r2).exp() / (std::f64::consts::PI * var * var)
+            };
+            let size = shape[0];
+            let temp: Array3<f64> = kernel_from_offsets(Ix3(size, size, 1), log)?;
+            let mean = temp.mean().unwrap_or(0.0);
+            kernel_from_offsets(shape, |x, y| {
+                let c = (size / 2) as f64;
+                temp[[(y + c) as usize, (x + c) as usize, 0]] - mean
+            })
+        }
+    }
+}
+
+/// Builds a Difference of Gaussians kernel, the narrower Gaussian minus the
+/// wider one. This approximates the Laplacian of Gaussian and acts as a band
+/// pass filter. Both Gaussians are normalised so the kernel sums to zero.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct DifferenceOfGaussiansFilter;
+
+impl<T> KernelBuilder<T> for DifferenceOfGaussiansFilter
+where
+    T: Copy + Clone + FromPrimitive + Num,
+{
+    /// Standard deviations of the narrow and wide Gaussians, the default uses a
+    /// ratio of 1.6 between them
+    type Params = [f64; 2];
+
+    fn build<D>(shape: D) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        let sigma = default_sigma(shape.into_dimension()[0]);
+        Self::build_with_params(shape, [sigma, 1.6 * sigma])
+    }
+
+    fn build_with_params<D>(shape: D, sigmas: Self::Params) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        let shape = shape.into_dimension();
+        if !is_odd_square(shape) {
+            Err(Error::InvalidDimensions)
+        } else if !(is_positive(sigmas[0]) && is_positive(sigmas[1])) {
+            Err(Error::InvalidParameter)
+        } else {
+            let narrow = gaussian_weights(shape[0], sigmas[0]);
+            let wide = gaussian_weights(shape[0], sigmas[1]);
+            let c = (shape[0] / 2) as f64;
+            kernel_from_offsets(shape, |x, y| {
+                let (r, c) = ((y + c) as usize, (x + c) as usize);
+                narrow[[r, c]] - wide[[r, c]]
+            })
+        }
+    }
+}
+
+/// Order of the derivative taken by a `GaussianDerivativeFilter`
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum DerivativeOrder {
+    /// No derivative, a plain Gaussian blur
+    Zero,
+    /// First derivative along the columns
+    X,
+    /// First derivative along the rows
+    Y,
+    /// Second derivative along the columns
+    XX,
+    /// Mixed second derivative
+    XY,
+    /// Second derivative along the rows
+    YY,
+}
+
+/// Builds a kernel that takes a derivative of a Gaussian smoothed image. The
+/// kernels are scaled so that applying them with `conv2d` gives the derivative
+/// in the direction of increasing column (x) or row (y) index, and derivatives
+/// of polynomials up to second order are exact.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GaussianDerivativeFilter;
+
+/// Derivatives of a sampled 1D Gaussian of zero, first and second order
+fn gaussian_derivatives_1d(size: usize, sigma: f64) -> [Array1<f64>; 3] {
+    let centre = (size / 2) as f64;
+    let x = Array1::from_shape_fn(size, |i| i as f64 - centre);
+    let mut g = x.mapv(|x| (-(x * x) / (2.0 * sigma * sigma)).exp());
+    g /= g.sum();
+    let mut first = &x * &g;
+    first /= (&first * &x).sum();
+    let mut second = (&x * &x - sigma * sigma) * &g;
+    // Remove the DC response then scale so the second derivative of x² is 2
+    second = &second - &(&g * second.sum());
+    second /= (&second * &x * &x).sum() / 2.0;
+    [g, first, second]
+}
+
+impl<T> KernelBuilder<T> for GaussianDerivativeFilter
+where
+    T: Copy + Clone + FromPrimitive + Num,
+{
+    /// Order of the derivative and the standard deviation of the Gaussian
+    type Params = (DerivativeOrder, f64);
+
+    /// Builds a zero order kernel with a standard deviation chosen from the shape
+    fn build<D>(shape: D) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        let sigma = default_sigma(shape.into_dimension()[0]);
+        Self::build_with_params(shape, (DerivativeOrder::Zero, sigma))
+    }
+
+    fn build_with_params<D>(shape: D, p: Self::Params) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        let (order, sigma) = p;
+        let shape = shape.into_dimension();
+        if !is_odd_square(shape) {
+            Err(Error::InvalidDimensions)
+        } else if !is_positive(sigma) {
+            Err(Error::InvalidParameter)
+        } else {
+            let d = gaussian_derivatives_1d(shape[0], sigma);
+            let (row_order, col_order) = match order {
+                DerivativeOrder::Zero => (0, 0),
+                DerivativeOrder::X => (0, 1),
+                DerivativeOrder::Y => (1, 0),
+                DerivativeOrder::XX => (0, 2),
+                DerivativeOrder::XY => (1, 1),
+                DerivativeOrder::YY => (2, 0),
+            };
+            let c = (shape[0] / 2) as f64;
+            kernel_from_offsets(shape, |x, y| {
+                d[row_order][(y + c) as usize] * d[col_order][(x + c) as usize]
+            })
+        }
+    }
+}
+
+/// Builds a Gabor kernel, a Gaussian modulated by a sinusoid. Gabor kernels
+/// respond to edges and textures at a specific orientation and frequency.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub struct GaborFilter;
+
+/// Parameters for a Gabor kernel
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct GaborParameters {
+    /// Standard deviation of the Gaussian envelope
+    pub sigma: f64,
+    /// Wavelength of the sinusoid in pixels
+    pub wavelength: f64,
+    /// Orientation of the sinusoid in radians. At 0 the sinusoid varies along
+    /// the columns, so the kernel responds to vertical stripes
+    pub orientation: f64,
+    /// Phase offset of the sinusoid in radians, 0 gives a symmetric kernel and
+    /// π/2 an antisymmetric one
+    pub phase: f64,
+    /// Spatial aspect ratio of the Gaussian envelope, values below 1 elongate
+    /// the kernel along the stripes
+    pub aspect: f64,
+}
+
+impl<T> KernelBuilder<T> for GaborFilter
+where
+    T: Float + FromPrimitive,
+{
+    type Params = GaborParameters;
+
+    /// Builds a kernel with a sigma chosen from the shape, a wavelength of
+    /// twice sigma, an orientation and phase of zero and an aspect of 0.5
+    fn build<D>(shape: D) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        let sigma = default_sigma(shape.into_dimension()[0]);
+        let params = GaborParameters {
+            sigma,
+            wavelength: 2.0 * sigma,
+            orientation: 0.0,
+            phase: 0.0,
+            aspect: 0.5,
+        };
+        Self::build_with_params(shape, params)
+    }
+
+    fn build_with_params<D>(shape: D, p: Self::Params) -> Result<Array3<T>, Error>
+    where
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        let shape = shape.into_dimension();
+        let valid = is_positive(p.sigma)
+            && is_positive(p.wavelength)
+            && is_positive(p.aspect)
+            && p.orientation.is_finite()
+            && p.phase.is_finite();
+        if !is_odd_square(shape) {
+            Err(Error::InvalidDimensions)
+        } else if !valid {
+            Err(Error::InvalidParameter)
+        } else {
+            let (sin, cos) = p.orientation.sin_cos();
+            kernel_from_offsets(shape, |x, y| {
+                let x_rot = x * cos + y * sin;
+                let y_rot = -x * sin + y * cos;
+                let envelope = (-(x_rot * x_rot + p.aspect * p.aspect * y_rot * y_rot)
+                    / (2.0 * p.sigma * p.sigma))
+                    .exp();
+                let carrier = (2.0 * std::f64::consts::PI * x_rot / p.wavelength + p.phase).cos();
+                envelope * carrier
+            })
+        }
+    }
+}
+
+impl GaborFilter {
+    /// Builds a bank of Gabor kernels with the given parameters at evenly spaced
+    /// orientations in `[0, π)`, starting at the orientation in the parameters.
+    /// Convolving an image with each kernel in the bank and comparing the
+    /// response energies can be used to describe texture.
+    pub fn bank<T, D>(
+        shape: D,
+        params: GaborParameters,
+        orientations: usize,
+    ) -> Result<Vec<Array3<T>>, Error>
+    where
+        T: Float + FromPrimitive,
+        D: Copy + IntoDimension<Dim = Ix3>,
+    {
+        if orientations == 0 {
+            return Err(Error::InvalidParameter);
+        }
+        (0..orientations)
+            .map(|i| {
+                let orientation =
+                    params.orientation + std::f64::consts::PI * i as f64 / orientations as f64;
+                let p = GaborParameters {
+                    orientation,
+                    ..params
+                };
+                Self::build_with_params(shape, p)
+            })
+            .collect()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -318,4 +743,153 @@ mod tests {
             arr3(&[[[-1], [-1], [-1]], [[-1], [8], [-1]], [[-1], [-1], [-1]]])
         );
     }
+
+    #[test]
+    fn test_gradient_filters() {
+        let scharr: Array3<i32> = ScharrFilter::build_with_params(Orientation::Horizontal).unwrap();
+        assert_eq!(
+            scharr,
+            arr3(&[[[3], [0], [-3]], [[10], [0], [-10]], [[3], [0], [-3]]])
+        );
+        let scharr: Array3<i32> = ScharrFilter::build().unwrap();
+        assert_eq!(
+            scharr,
+            arr3(&[[[3], [10], [3]], [[0], [0], [0]], [[-3], [-10], [-3]]])
+        );
+
+        let prewitt: Array3<i32> =
+            PrewittFilter::build_with_params(Orientation::Horizontal).unwrap();
+        assert_eq!(
+            prewitt,
+            arr3(&[[[1], [0], [-1]], [[1], [0], [-1]], [[1], [0], [-1]]])
+        );
+        let prewitt: Array3<i32> = PrewittFilter::build().unwrap();
+        assert_eq!(
+            prewitt,
+            arr3(&[[[1], [1], [1]], [[0], [0], [0]], [[-1], [-1], [-1]]])
+        );
+
+        let roberts: Array3<i32> =
+            RobertsFilter::build_with_params(Orientation::Horizontal).unwrap();
+        assert_eq!(roberts, arr3(&[[[1], [0]], [[0], [-1]]]));
+        let roberts: Array3<i32> = RobertsFilter::build().unwrap();
+        assert_eq!(roberts, arr3(&[[[0], [1]], [[-1], [0]]]));
+    }
+
+    #[test]
+    fn test_blob_filters() {
+        let log: Array3<f64> =
+            LaplacianOfGaussianFilter::build_with_params((9, 9, 2), 1.4).unwrap();
+        assert!(log.sum().abs() < 1e-10);
+        assert!(log[[4, 4, 0]] < 0.0);
+        assert!(log[[4, 0, 1]] > 0.0);
+        assert_eq!(log, log.slice(s![..;-1, ..;-1, ..]));
+        assert_eq!(log.slice(s![.., .., 0]), log.slice(s![.., .., 1]));
+
+        let dog: Array3<f64> = DifferenceOfGaussiansFilter::build(Ix3(11, 11, 1)).unwrap();
+        assert!(dog.sum().abs() < 1e-10);
+        assert!(dog[[5, 5, 0]] > 0.0);
+        let dog_2d = dog.slice(s![.., .., 0]);
+        assert_eq!(dog_2d, dog_2d.t());
+
+        let bad: Result<Array3<f64>, _> = LaplacianOfGaussianFilter::build(Ix3(4, 4, 1));
+        assert_eq!(bad, Err(Error::InvalidDimensions));
+        let bad: Result<Array3<f64>, _> =
+            DifferenceOfGaussiansFilter::build_with_params(Ix3(5, 5, 1), [1.0, 0.0]);
+        assert_eq!(bad, Err(Error::InvalidParameter));
+        let bad: Result<Array3<f64>, _> =
+            DifferenceOfGaussiansFilter::build_with_params(Ix3(5, 5, 1), [f64::NAN, 2.0]);
+        assert_eq!(bad, Err(Error::InvalidParameter));
+        let bad: Result<Array3<f64>, _> =
+            LaplacianOfGaussianFilter::build_with_params(Ix3(5, 5, 1), f64::NAN);
+        assert_eq!(bad, Err(Error::InvalidParameter));
+    }
+
+    #[test]
+    fn test_gabor_filters() {
+        let params = GaborParameters {
+            sigma: 2.0,
+            wavelength: 4.0,
+            orientation: 0.0,
+            phase: 0.0,
+            aspect: 1.0,
+        };
+        let gabor: Array3<f64> = GaborFilter::build_with_params((9, 9, 1), params).unwrap();
+        assert!((gabor[[4, 4, 0]] - 1.0).abs() < 1e-10);
+        // Half a wavelength along the columns the carrier is inverted
+        assert!(gabor[[4, 6, 0]] < 0.0);
+        assert!(gabor[[6, 4, 0]] > 0.0);
+
+        // Vertical stripes with a period of 4 pixels
+        let stripes =
+            Array3::from_shape_fn((15, 15, 1), |(_, c, _)| if c % 4 < 2 { 1.0 } else { 0.0 });
+        let bank: Vec<Array3<f64>> = GaborFilter::bank((9, 9, 1), params, 4).unwrap();
+        assert_eq!(bank.len(), 4);
+        let energies = bank
+            .iter()
+            .map(|k| {
+                let centre = stripes.slice(s![3..12, 3..12, ..]);
+                (&centre * k).sum().abs()
+            })
+            .collect::<Vec<_>>();
+        assert!(energies[1..].iter().all(|e| *e < energies[0]));
+
+        for bad in [
+            GaborParameters {
+                sigma: f64::NAN,
+                ..params
+            },
+            GaborParameters {
+                wavelength: f64::NAN,
+                ..params
+            },
+            GaborParameters {
+                aspect: f64::INFINITY,
+                ..params
+            },
+            GaborParameters {
+                phase: f64::NAN,
+                ..params
+            },
+        ] {
+            let bad: Result<Array3<f64>, _> = GaborFilter::build_with_params((9, 9, 1), bad);
+            assert_eq!(bad, Err(Error::InvalidParameter));
+        }
+    }
+
+    #[test]
+    fn test_gaussian_derivatives() {
+        let kernel = |order| -> Array3<f64> {
+            GaussianDerivativeFilter::build_with_params((9, 9, 1), (order, 1.2)).unwrap()
+        };
+        let apply = |k: &Array3<f64>, f: &dyn Fn(f64, f64) -> f64| {
+            k.indexed_iter()
+                .map(|((r, c, _), w)| w * f(c as f64 - 4.0 + 10.0, r as f64 - 4.0 + 20.0))
+                .sum::<f64>()
+        };
+        let close = |a: f64, b: f64| (a - b).abs() < 1e-8;
+
+        // Evaluated at x = 10, y = 20
+        let ramp = |x: f64, y: f64| 2.0 * x + 3.0 * y + 1.0;
+        assert!(close(apply(&kernel(DerivativeOrder::Zero), &ramp), 81.0));
+        assert!(close(apply(&kernel(DerivativeOrder::X), &ramp), 2.0));
+        assert!(close(apply(&kernel(DerivativeOrder::Y), &ramp), 3.0));
+        assert!(close(apply(&kernel(DerivativeOrder::XX), &ramp), 0.0));
+
+        let quadratic = |x: f64, y: f64| x * x + 4.0 * x * y - 0.5 * y * y;
+        assert!(close(apply(&kernel(DerivativeOrder::XX), &quadratic), 2.0));
+        assert!(close(apply(&kernel(DerivativeOrder::XY), &quadratic), 4.0));
+        assert!(close(apply(&kernel(DerivativeOrder::YY), &quadratic), -1.0));
+        assert!(close(
+            apply(&kernel(DerivativeOrder::X), &quadratic),
+            20.0 + 80.0
+        ));
+
+        let bad: Result<Array3<f64>, _> =
+            GaussianDerivativeFilter::build_with_params((5, 5, 1), (DerivativeOrder::X, -1.0));
+        assert_eq!(bad, Err(Error::InvalidParameter));
+        let bad: Result<Array3<f64>, _> =
+            GaussianDerivativeFilter::build_with_params((5, 5, 1), (DerivativeOrder::X, f64::NAN));
+        assert_eq!(bad, Err(Error::InvalidParameter));
+    }
 }
@@ -1,20 +1,54 @@
+/// Locally adaptive thresholds for images with uneven lighting
+pub mod adaptive_threshold;
 /// Implementation of a Canny Edge Detector and associated types
 pub mod canny;
 /// Image convolutions in 2D
 pub mod conv;
+/// Denoising algorithms such as non-local means and anisotropic diffusion
+pub mod denoise;
+/// Subpixel edge points and edge chains
+pub mod edges;
 /// Not convolution based image filters
 pub mod filter;
+/// Image gradients with a choice of derivative operator
+pub mod gradient;
+/// Guided and joint bilateral filters which smooth an image using the
+/// structure of a guidance image
+pub mod guided;
+/// Hough transforms for lines and circles
+pub mod hough;
+/// Hysteresis thresholding of connected regions
+pub mod hysteresis;
+/// Integral images and box filters
+pub mod integral;
 /// Common convolution kernels and traits to aid in the building of kernels
 pub mod kernels;
+/// Rank filters such as minimum, maximum and percentile filters
+pub mod rank;
+/// Gaussian scale spaces
+pub mod scale_space;
+/// Image sharpening with unsharp masks and the Laplacian
+pub mod sharpen;
 /// Sobel operator for edge detection
 pub mod sobel;
 /// Thresholding functions
 pub mod threshold;
 
+pub use adaptive_threshold::*;
 pub use canny::*;
 pub use conv::*;
+pub use denoise::*;
+pub use edges::*;
 pub use filter::*;
+pub use gradient::*;
+pub use guided::*;
+pub use hough::*;
+pub use hysteresis::*;
+pub use integral::*;
 pub use kernels::*;
+pub use rank::*;
+pub use scale_space::*;
+pub use sharpen::*;
 pub use sobel::*;
 pub use threshold::*;
 
@@ -0,0 +1,528 @@
+use crate::core::padding::*;
+use crate::core::{kernel_centre, ColourModel, Image, ImageBase, PixelBound};
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::{Data, OwnedRepr};
+use num_traits::{FromPrimitive, Num, ToPrimitive};
+
+/// Largest number of distinct levels the histogram based implementation will
+/// be used for, this covers all 8 and 16 bit images
+pub(crate) const MAX_HISTOGRAM_LEVELS: usize = 1 << 16;
+
+/// The statistic computed by a rank filter over the pixels in its footprint
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Rank {
+    /// The smallest value in the footprint
+    Min,
+    /// The largest value in the footprint
+    Max,
+    /// The value at the given percentile, given as a fraction in the range
+    /// `[0, 1]`. The value with the nearest rank is taken so no interpolation
+    /// is performed
+    Percentile(f64),
+    /// The most common value in the footprint, ties are resolved by taking the
+    /// smallest value
+    Mode,
+    /// The difference between the largest and smallest value in the footprint
+    Range,
+}
+
+/// Rank filters, given a boolean footprint to move over the image each pixel
+/// is replaced by a statistic of the ordered values within the footprint.
+pub trait RankFilterExt<T>
+where
+    T: Copy,
+{
+    type Output;
+    /// Run the rank filter with the given footprint, the centre of the
+    /// footprint is the pixel being filtered. Each channel is filtered
+    /// independently. Samples outside of the image are ignored.
+    fn rank_filter(&self, footprint: ArrayView2<bool>, rank: Rank) -> Result<Self::Output, Error>;
+
+    /// Run the rank filter using the given padding strategy to provide samples
+    /// outside of the image. If the strategy won't pad a location the sample
+    /// is ignored
+    fn rank_filter_with_padding(
+        &self,
+        footprint: ArrayView2<bool>,
+        rank: Rank,
+        strategy: &impl PaddingStrategy<T>,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// Histogram of pixel values split into coarse and fine levels so order
+/// statistics can be found without scanning every bin. The largest fine count
+/// in each coarse bin is tracked so the mode can also be found by scanning the
+/// coarse bins.
+#[derive(Clone, Debug)]
+pub(crate) struct RankHistogram {
+    fine: Vec<u32>,
+    coarse: Vec<u32>,
+    coarse_max: Vec<u32>,
+    coarse_width: usize,
+    count: usize,
+}
+
+impl RankHistogram {
+    pub(crate) fn new(levels: usize) -> Self {
+        let coarse_width = ((levels as f64).sqrt().ceil() as usize).max(1);
+        let coarse_bins = levels.div_ceil(coarse_width);
+        Self {
+            fine: vec![0; levels],
+            coarse: vec![0; coarse_bins],
+            coarse_max: vec![0; coarse_bins],
+            coarse_width,
+            count: 0,
+        }
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.fine.iter_mut().for_each(|x| *x = 0);
+        self.coarse.iter_mut().for_each(|x| *x = 0);
+        self.coarse_max.iter_mut().for_each(|x| *x = 0);
+        self.count = 0;
+    }
+
+    pub(crate) fn count(&self) -> usize {
+        self.count
+    }
+
+    /// Range of fine bins in the coarse bin
+    fn fine_range(&self, coarse: usize) -> std::ops::Range<usize> {
+        let start = coarse * self.coarse_width;
+        start..(start + self.coarse_width).min(self.fine.len())
+    }
+
+    #[inline]
+    pub(crate) fn add(&mut self, bin: usize) {
+        let coarse = bin / self.coarse_width;
+        self.fine[bin] += 1;
+        self.coarse[coarse] += 1;
+        self.coarse_max[coarse] = self.coarse_max[coarse].max(self.fine[bin]);
+        self.count += 1;
+    }
+
+    #[inline]
+    pub(crate) fn remove(&mut self, bin: usize) {
+        let coarse = bin / self.coarse_width;
+        self.fine[bin] -= 1;
+        self.coarse[coarse] -= 1;
+        if self.fine[bin] + 1 == self.coarse_max[coarse] {
+            // Another bin may share the old maximum
+            let range = self.fine_range(coarse);
+            self.coarse_max[coarse] = self.fine[range].iter().copied().max().unwrap_or(0);
+        }
+        self.count -= 1;
+    }
+
+    /// Returns the bin containing the `k`th smallest value, `k` must be less
+    /// than the number of values in the histogram
+    pub(crate) fn kth(&self, k: usize) -> usize {
+        let mut seen = 0;
+        for (i, coarse) in self.coarse.iter().enumerate() {
+            let coarse = *coarse as usize;
+            if seen + coarse > k {
+                let start = i * self.coarse_width;
+                let end = (start + self.coarse_width).min(self.fine.len());
+                for (bin, fine) in self.fine[start..end].iter().enumerate() {
+                    seen += *fine as usize;
+                    if seen > k {
+                        return start + bin;
+                    }
+                }
+            }
+            seen += coarse;
+        }
+        self.fine.len() - 1
+    }
+
+    /// Returns the most common bin, the lowest bin is returned for ties
+    pub(crate) fn mode(&self) -> usize {
+        let mut best = 0;
+        for (coarse, max) in self.coarse_max.iter().enumerate() {
+            if *max > self.coarse_max[best] {
+                best = coarse;
+            }
+        }
+        let range = self.fine_range(best);
+        let max = self.coarse_max[best];
+        range
+            .clone()
+            .find(|bin| self.fine[*bin] == max)
+            .unwrap_or(range.start)
+    }
+}
+
+type Offsets = Vec<(usize, usize)>;
+
+/// Offsets in the footprint of samples leaving and entering the window when it
+/// moves one column to the right. Entering offsets are relative to the new
+/// window position
+fn footprint_transitions(footprint: ArrayView2<bool>) -> (Offsets, Offsets) {
+    let width = footprint.ncols();
+    let mut leaving = vec![];
+    let mut entering = vec![];
+    for ((r, c), v) in footprint.indexed_iter() {
+        if *v {
+            if c == 0 || !footprint[[r, c - 1]] {
+                leaving.push((r, c));
+            }
+            if c + 1 == width || !footprint[[r, c + 1]] {
+                entering.push((r, c));
+            }
+        }
+    }
+    (leaving, entering)
+}
+
+/// Compute the statistic from an unordered set of values, returns `None` if
+/// the range can't be represented
+fn rank_of_values<T>(values: &mut [T], rank: Rank) -> Option<T>
+where
+    T: Copy + Num + Ord + FromPrimitive + ToPrimitive + PixelBound,
+{
+    let value = match rank {
+        Rank::Min => *values.iter().min().unwrap(),
+        Rank::Max => *values.iter().max().unwrap(),
+        Rank::Range => {
+            let (max, min) = (*values.iter().max()?, *values.iter().min()?);
+            if T::is_integral() && min < T::zero() {
+                // Ranges of signed values can overflow the type
+                T::from_i128(max.to_i128()?.checked_sub(min.to_i128()?)?)?
+            } else {
+                max - min
+            }
+        }
+        Rank::Percentile(p) => {
+            let k = percentile_index(p, values.len());
+            *values.select_nth_unstable(k).1
+        }
+        Rank::Mode => {
+            values.sort_unstable();
+            let mut best = values[0];
+            let mut best_count = 0;
+            let mut i = 0;
+            while i < values.len() {
+                let run = values[i..].iter().take_while(|x| **x == values[i]).count();
+                if run > best_count {
+                    best = values[i];
+                    best_count = run;
+                }
+                i += run;
+            }
+            best
+        }
+    };
+    Some(value)
+}
+
+#[inline]
+pub(crate) fn percentile_index(p: f64, len: usize) -> usize {
+    ((len - 1) as f64 * p).round() as usize
+}
+
+/// Runs the rank filter on a single padded channel using a running histogram.
+/// Values are stored as histogram bins offset from the minimum
+fn histogram_rank_filter(
+    padded: ArrayView2<Option<usize>>,
+    footprint: ArrayView2<bool>,
+    rank: Rank,
+    levels: usize,
+    mut output: impl FnMut(usize, usize, Option<usize>),
+) {
+    let (leaving, entering) = footprint_transitions(footprint);
+    let rows = padded.nrows() + 1 - footprint.nrows();
+    let cols = padded.ncols() + 1 - footprint.ncols();
+    let mut hist = RankHistogram::new(levels);
+    for r in 0..rows {
+        hist.clear();
+        for ((fr, fc), v) in footprint.indexed_iter() {
+            if let (true, Some(bin)) = (v, padded[[r + fr, fc]]) {
+                hist.add(bin);
+            }
+        }
+        for c in 0..cols {
+            if c > 0 {
+                for (fr, fc) in &leaving {
+                    if let Some(bin) = padded[[r + fr, c - 1 + fc]] {
+                        hist.remove(bin);
+                    }
+                }
+                for (fr, fc) in &entering {
+                    if let Some(bin) = padded[[r + fr, c + fc]] {
+                        hist.add(bin);
+                    }
+                }
+            }
+            let value = if hist.count() == 0 {
+                None
+            } else {
+                Some(match rank {
+                    Rank::Min => hist.kth(0),
+                    Rank::Max => hist.kth(hist.count() - 1),
+                    Rank::Range => hist.kth(hist.count() - 1) - hist.kth(0),
+                    Rank::Percentile(p) => hist.kth(percentile_index(p, hist.count())),
+                    Rank::Mode => hist.mode(),
+                })
+            };
+            output(r, c, value);
+        }
+    }
+}
+
+impl<T, U> RankFilterExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + Ord + PixelBound,
+{
+    type Output = ArrayBase<OwnedRepr<T>, Ix3>;
+
+    fn rank_filter(&self, footprint: ArrayView2<bool>, rank: Rank) -> Result<Self::Output, Error> {
+        self.rank_filter_with_padding(footprint, rank, &NoPadding {})
+    }
+
+    fn rank_filter_with_padding(
+        &self,
+        footprint: ArrayView2<bool>,
+        rank: Rank,
+        strategy: &impl PaddingStrategy<T>,
+    ) -> Result<Self::Output, Error> {
+        if !footprint.iter().any(|x| *x) {
+            return Err(Error::InvalidDimensions);
+        }
+        if let Rank::Percentile(p) = rank {
+            if !(0.0..=1.0).contains(&p) {
+                return Err(Error::InvalidParameter);
+            }
+        }
+        let (rows, cols, channels) = self.dim();
+        let (f_rows, f_cols) = footprint.dim();
+        let (ro, co) = kernel_centre(f_rows, f_cols);
+        let image = self.view();
+        let padded_dim = (rows + f_rows - 1, cols + f_cols - 1);
+        let mut result = self.to_owned();
+
+        for chan in 0..channels {
+            let padded = Array2::from_shape_fn(padded_dim, |(r, c)| {
+                let r = r as isize - ro as isize;
+                let c = c as isize - co as isize;
+                if r >= 0 && c >= 0 && r < rows as isize && c < cols as isize {
+                    Some(image[[r as usize, c as usize, chan]])
+                } else if strategy.will_pad(Some((r, c))) {
+                    strategy.get_value(image, (r, c, chan))
+                } else {
+                    None
+                }
+            });
+            let min = padded.iter().flatten().min().and_then(|x| x.to_i64());
+            let max = padded.iter().flatten().max().and_then(|x| x.to_i64());
+            // Falls back to sorting if the levels don't fit in the histogram
+            let levels = match (min, max) {
+                (Some(min), Some(max)) if T::is_integral() => max
+                    .checked_sub(min)
+                    .and_then(|x| x.to_usize())
+                    .and_then(|x| x.checked_add(1))
+                    .map(|levels| (min, levels)),
+                _ => None,
+            };
+            match levels {
+                Some((min, levels)) if levels <= MAX_HISTOGRAM_LEVELS => {
+                    let bins =
+                        padded.mapv(|x| x.and_then(|x| x.to_i64()).map(|x| (x - min) as usize));
+                    let offset = if rank == Rank::Range { 0 } else { min };
+                    let mut error = false;
+                    histogram_rank_filter(bins.view(), footprint, rank, levels, |r, c, bin| {
+                        if let Some(bin) = bin {
+                            match T::from_i64(offset + bin as i64) {
+                                Some(v) => result[[r, c, chan]] = v,
+                                None => error = true,
+                            }
+                        }
+                    });
+                    if error {
+                        return Err(Error::NumericError);
+                    }
+                }
+                _ => {
+                    let mut values = Vec::with_capacity(footprint.len());
+                    for r in 0..rows {
+                        for c in 0..cols {
+                            values.clear();
+                            for ((fr, fc), v) in footprint.indexed_iter() {
+                                if let (true, Some(x)) = (v, padded[[r + fr, c + fc]]) {
+                                    values.push(x);
+                                }
+                            }
+                            if !values.is_empty() {
+                                result[[r, c, chan]] =
+                                    rank_of_values(&mut values, rank).ok_or(Error::NumericError)?;
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl<T, U, C> RankFilterExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + Ord + PixelBound,
+    C: ColourModel,
+{
+    type Output = Image<T, C>;
+
+    fn rank_filter(&self, footprint: ArrayView2<bool>, rank: Rank) -> Result<Self::Output, Error> {
+        let data = self.data.rank_filter(footprint, rank)?;
+        Ok(Image::from_data(data))
+    }
+
+    fn rank_filter_with_padding(
+        &self,
+        footprint: ArrayView2<bool>,
+        rank: Rank,
+        strategy: &impl PaddingStrategy<T>,
+    ) -> Result<Self::Output, Error> {
+        let data = self
+            .data
+            .rank_filter_with_padding(footprint, rank, strategy)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, RGB};
+    use ndarray::{arr2, arr3};
+    use ndarray_rand::rand::{rngs::StdRng, Rng, SeedableRng};
+    use noisy_float::types::{n64, N64};
+
+    #[test]
+    #[rustfmt::skip]
+    fn simple_min_max() {
+        let image = Image::<u8, Gray>::from_shape_data(3, 4, vec![
+            1, 2, 3, 4,
+            5, 6, 7, 8,
+            9, 10, 11, 12,
+        ]);
+        let footprint = Array2::from_elem((3, 3), true);
+
+        let min = image.rank_filter(footprint.view(), Rank::Min).unwrap();
+        let expected = Image::<u8, Gray>::from_shape_data(3, 4, vec![
+            1, 1, 2, 3,
+            1, 1, 2, 3,
+            5, 5, 6, 7,
+        ]);
+        assert_eq!(min, expected);
+
+        let max = image
+            .rank_filter_with_padding(footprint.view(), Rank::Max, &ZeroPadding {})
+            .unwrap();
+        let expected = Image::<u8, Gray>::from_shape_data(3, 4, vec![
+            6, 7, 8, 8,
+            10, 11, 12, 12,
+            10, 11, 12, 12,
+        ]);
+        assert_eq!(max, expected);
+
+        let min = image
+            .rank_filter_with_padding(footprint.view(), Rank::Min, &ZeroPadding {})
+            .unwrap();
+        let expected = Image::<u8, Gray>::from_shape_data(3, 4, vec![
+            0, 0, 0, 0,
+            0, 1, 2, 0,
+            0, 0, 0, 0,
+        ]);
+        assert_eq!(min, expected);
+    }
+
+    #[test]
+    #[rustfmt::skip]
+    fn mode_and_range() {
+        let image = Image::<u8, Gray>::from_shape_data(3, 3, vec![
+            4, 4, 1,
+            2, 9, 2,
+            2, 1, 4,
+        ]);
+        let footprint = Array2::from_elem((3, 3), true);
+        let mode = image.rank_filter(footprint.view(), Rank::Mode).unwrap();
+        // 2 and 4 both occur three times at the centre, the smallest is taken
+        assert_eq!(mode.data[[1, 1, 0]], 2);
+        assert_eq!(mode.data[[0, 0, 0]], 4);
+
+        let range = image.rank_filter(footprint.view(), Rank::Range).unwrap();
+        assert_eq!(range.data[[1, 1, 0]], 8);
+        assert_eq!(range.data[[0, 2, 0]], 8);
+    }
+
+    #[test]
+    fn extreme_levels() {
+        // Too many levels for the histogram falls back to sorting
+        let image = arr3(&[[[i64::MIN], [0], [i64::MAX]]]);
+        let footprint = Array2::from_elem((1, 3), true);
+        let max = image.rank_filter(footprint.view(), Rank::Max).unwrap();
+        assert_eq!(max, arr3(&[[[0], [i64::MAX], [i64::MAX]]]));
+        let min = image.rank_filter(footprint.view(), Rank::Min).unwrap();
+        assert_eq!(min, arr3(&[[[i64::MIN], [i64::MIN], [0]]]));
+        assert_eq!(
+            image.rank_filter(footprint.view(), Rank::Range),
+            Err(Error::NumericError)
+        );
+        let image = arr3(&[[[u64::MAX], [0]]]);
+        let range = image.rank_filter(footprint.view(), Rank::Range).unwrap();
+        assert_eq!(range, arr3(&[[[u64::MAX], [u64::MAX]]]));
+    }
+
+    #[test]
+    fn histogram_matches_sorting() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let pixels: Vec<u8> = (0..(13 * 11 * 3)).map(|_| rng.gen()).collect();
+        let image = Image::<u8, RGB>::from_shape_data(13, 11, pixels);
+        // Noisy floats don't go through the histogram implementation
+        let float_image = image.data.mapv(|x| n64(x as f64));
+
+        let cross = arr2(&[
+            [false, false, true, false, false],
+            [false, true, true, true, false],
+            [true, true, false, true, true],
+            [false, true, true, true, false],
+        ]);
+        let ranks = [
+            Rank::Min,
+            Rank::Max,
+            Rank::Percentile(0.0),
+            Rank::Percentile(0.3),
+            Rank::Percentile(0.5),
+            Rank::Percentile(1.0),
+            Rank::Mode,
+            Rank::Range,
+        ];
+        for rank in ranks.iter() {
+            let a = image
+                .rank_filter_with_padding(cross.view(), *rank, &ZeroPadding {})
+                .unwrap();
+            let b: Array3<N64> = float_image
+                .rank_filter_with_padding(cross.view(), *rank, &ZeroPadding {})
+                .unwrap();
+            assert_eq!(a.data.mapv(|x| n64(x as f64)), b, "{:?}", rank);
+        }
+    }
+
+    #[test]
+    fn rank_bad_parameters() {
+        let image = Image::<u8, Gray>::new(3, 3);
+        let footprint = Array2::from_elem((3, 3), false);
+        assert_eq!(
+            image.rank_filter(footprint.view(), Rank::Min),
+            Err(Error::InvalidDimensions)
+        );
+        let footprint = Array2::from_elem((3, 3), true);
+        assert_eq!(
+            image.rank_filter(footprint.view(), Rank::Percentile(1.5)),
+            Err(Error::InvalidParameter)
+        );
+    }
+}
@@ -0,0 +1,212 @@
+use crate::core::{ColourModel, ImageBase};
+use crate::processing::conv::gaussian_blur;
+use crate::processing::Error;
+use ndarray::prelude::*;
+use ndarray::Data;
+use num_traits::ToPrimitive;
+
+/// Builds a Gaussian scale space, a stack of images blurred with increasing
+/// standard deviations, from an image
+pub trait ScaleSpaceExt {
+    /// Create the scale space with the given parameters. Each level is blurred
+    /// from the previous level so the cost of large scales stays low. Pixel
+    /// values are converted to `f64` without any rescaling.
+    fn scale_space(&self, params: &ScaleSpaceParameters) -> Result<ScaleSpace, Error>;
+}
+
+/// Builder to construct the scale space parameters, if a parameter is not
+/// selected then a sensible default is chosen
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct ScaleSpaceBuilder {
+    initial_sigma: Option<f64>,
+    scale_factor: Option<f64>,
+    levels: Option<usize>,
+}
+
+/// Parameters for a Gaussian scale space
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ScaleSpaceParameters {
+    /// Standard deviation of the blur for the first level
+    pub initial_sigma: f64,
+    /// Ratio between the standard deviations of neighbouring levels
+    pub scale_factor: f64,
+    /// Number of levels in the scale space
+    pub levels: usize,
+}
+
+/// A Gaussian scale space
+#[derive(Clone, PartialEq, Debug)]
+pub struct ScaleSpace {
+    /// Standard deviation of the blur applied at each level
+    pub sigmas: Vec<f64>,
+    /// The blurred images ordered from the smallest to the largest sigma
+    pub images: Vec<Array3<f64>>,
+}
+
+impl ScaleSpaceBuilder {
+    /// Creates a new builder with no parameters selected
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the standard deviation of the first level
+    pub fn initial_sigma(self, sigma: f64) -> Self {
+        Self {
+            initial_sigma: Some(sigma),
+            ..self
+        }
+    }
+
+    /// Sets the ratio between the standard deviations of neighbouring levels
+    pub fn scale_factor(self, factor: f64) -> Self {
+        Self {
+            scale_factor: Some(factor),
+            ..self
+        }
+    }
+
+    /// Sets the number of levels
+    pub fn levels(self, levels: usize) -> Self {
+        Self {
+            levels: Some(levels),
+            ..self
+        }
+    }
+
+    /// Creates the parameters, by default the first level has a sigma of 1.6
+    /// and there are 5 levels each a factor of √2 apart. Sigma must be
+    /// positive and finite, the factor finite and greater than one and there
+    /// must be at least one level.
+    pub fn build(self) -> Result<ScaleSpaceParameters, Error> {
+        let initial_sigma = self.initial_sigma.unwrap_or(1.6);
+        let scale_factor = self.scale_factor.unwrap_or(std::f64::consts::SQRT_2);
+        let levels = self.levels.unwrap_or(5);
+        let valid = initial_sigma > 0.0
+            && initial_sigma.is_finite()
+            && scale_factor > 1.0
+            && scale_factor.is_finite();
+        if !valid || levels == 0 {
+            Err(Error::InvalidParameter)
+        } else {
+            Ok(ScaleSpaceParameters {
+                initial_sigma,
+                scale_factor,
+                levels,
+            })
+        }
+    }
+}
+
+impl ScaleSpaceParameters {
+    /// Standard deviation of each level in the scale space
+    pub fn sigmas(&self) -> Vec<f64> {
+        (0..self.levels)
+            .map(|i| self.initial_sigma * self.scale_factor.powi(i as i32))
+            .collect()
+    }
+}
+
+impl<T, U> ScaleSpaceExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    fn scale_space(&self, params: &ScaleSpaceParameters) -> Result<ScaleSpace, Error> {
+        let data = self
+            .iter()
+            .map(|x| x.to_f64())
+            .collect::<Option<Vec<_>>>()
+            .ok_or(Error::NumericError)?;
+        let mut current = Array3::from_shape_vec(self.dim(), data).unwrap();
+        let sigmas = params.sigmas();
+        let mut images = Vec::with_capacity(sigmas.len());
+        let mut previous = 0.0;
+        for sigma in &sigmas {
+            // Blurs compose by adding variances
+            let increment = (sigma * sigma - previous * previous).sqrt();
+            current = gaussian_blur(&current, increment)?;
+            images.push(current.clone());
+            previous = *sigma;
+        }
+        Ok(ScaleSpace { sigmas, images })
+    }
+}
+
+impl<T, U, C> ScaleSpaceExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    fn scale_space(&self, params: &ScaleSpaceParameters) -> Result<ScaleSpace, Error> {
+        self.data.scale_space(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, Image};
+
+    #[test]
+    fn scale_space_parameters() {
+        let params = ScaleSpaceBuilder::new()
+            .initial_sigma(1.0)
+            .scale_factor(2.0)
+            .levels(4)
+            .build()
+            .unwrap();
+        assert_eq!(params.sigmas(), vec![1.0, 2.0, 4.0, 8.0]);
+
+        let default = ScaleSpaceBuilder::new().build().unwrap();
+        assert_eq!(default.levels, 5);
+        assert_eq!(default.initial_sigma, 1.6);
+
+        assert_eq!(
+            ScaleSpaceBuilder::new().scale_factor(1.0).build(),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            ScaleSpaceBuilder::new().levels(0).build(),
+            Err(Error::InvalidParameter)
+        );
+        for sigma in [f64::NAN, f64::INFINITY] {
+            assert_eq!(
+                ScaleSpaceBuilder::new().initial_sigma(sigma).build(),
+                Err(Error::InvalidParameter)
+            );
+            assert_eq!(
+                ScaleSpaceBuilder::new().scale_factor(sigma).build(),
+                Err(Error::InvalidParameter)
+            );
+        }
+    }
+
+    #[test]
+    fn scale_space_smooths_impulse() {
+        let mut image = Image::<u8, Gray>::new(41, 41);
+        image.data[[20, 20, 0]] = 255;
+        let params = ScaleSpaceBuilder::new()
+            .initial_sigma(1.0)
+            .scale_factor(1.5)
+            .levels(3)
+            .build()
+            .unwrap();
+        let space = image.scale_space(&params).unwrap();
+        assert_eq!(space.images.len(), 3);
+        assert_eq!(space.sigmas, params.sigmas());
+
+        for (sigma, level) in space.sigmas.iter().zip(space.images.iter()) {
+            // The blur conserves the total intensity and the spread of the
+            // impulse matches the level's sigma
+            let total = level.sum();
+            assert!((total - 255.0).abs() < 1e-6);
+            let variance = level
+                .indexed_iter()
+                .map(|((r, _, _), v)| v * (r as f64 - 20.0).powi(2))
+                .sum::<f64>()
+                / total;
+            assert!((variance.sqrt() - sigma).abs() < 0.05 * sigma);
+        }
+    }
+}
@@ -0,0 +1,339 @@
+use crate::core::{
+    denormalised_array, normalised_array, ColourModel, Image, ImageBase, PixelBound, RGB,
+};
+use crate::processing::conv::gaussian_blur;
+use crate::processing::*;
+use ndarray::prelude::*;
+use ndarray::{Data, OwnedRepr};
+use num_traits::{FromPrimitive, Num, ToPrimitive};
+use std::any::TypeId;
+
+/// Selects which channels of an image are sharpened
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum SharpenChannels {
+    /// Every channel is sharpened independently
+    All,
+    /// Only the luminance of an RGB image is sharpened and the same change is
+    /// applied to every channel, this leaves the chroma unchanged and avoids
+    /// colour fringes. Requires a three channel array or an `RGB` image
+    Luminance,
+}
+
+/// Image sharpening via unsharp masking and the Laplacian. Results are clamped
+/// to the bounds of the pixel type.
+pub trait UnsharpMaskExt {
+    type Output;
+    /// Sharpen the image with an unsharp mask. The image is blurred with a
+    /// Gaussian with standard deviation `sigma` and the difference between the
+    /// image and the blur is scaled by `amount` and added back to the image.
+    /// Differences smaller than `threshold`, given as a fraction of the pixel
+    /// range, are left unchanged so that noise in flat regions isn't amplified.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if `sigma` isn't positive,
+    /// `threshold` is negative or any parameter is NaN, or if luminance
+    /// sharpening is requested for an image which isn't `RGB`.
+    fn unsharp_mask(
+        &self,
+        amount: f64,
+        sigma: f64,
+        threshold: f64,
+        channels: SharpenChannels,
+    ) -> Result<Self::Output, Error>;
+
+    /// Sharpen the image by subtracting the Laplacian scaled by `amount`. Pixels
+    /// at the image borders are replicated when calculating the Laplacian.
+    ///
+    /// # Errors
+    ///
+    /// Returns an `InvalidParameter` error if `amount` isn't finite or if
+    /// luminance sharpening is requested for an image which isn't `RGB`.
+    fn laplacian_sharpen(
+        &self,
+        amount: f64,
+        laplace: LaplaceType,
+        channels: SharpenChannels,
+    ) -> Result<Self::Output, Error>;
+}
+
+/// Weights used for luminance, these match the RGB to grayscale conversion
+const LUMINANCE_WEIGHTS: [f64; 3] = [0.3, 0.59, 0.11];
+
+/// Runs the sharpening operation on the selected channels, the operation
+/// returns the change to apply to each pixel
+fn sharpen_channels<F>(
+    data: Array3<f64>,
+    channels: SharpenChannels,
+    sharpen: F,
+) -> Result<Array3<f64>, Error>
+where
+    F: Fn(&Array3<f64>) -> Result<Array3<f64>, Error>,
+{
+    match channels {
+        SharpenChannels::All => {
+            let delta = sharpen(&data)?;
+            Ok(data + delta)
+        }
+        SharpenChannels::Luminance => {
+            if data.dim().2 != 3 {
+                return Err(Error::ChannelDimensionMismatch);
+            }
+            let luma = data
+                .map_axis(Axis(2), |pix| {
+                    pix.iter()
+                        .zip(LUMINANCE_WEIGHTS.iter())
+                        .map(|(x, w)| x * w)
+                        .sum::<f64>()
+                })
+                .insert_axis(Axis(2));
+            let delta = sharpen(&luma)?;
+            Ok(data + delta)
+        }
+    }
+}
+
+fn unsharp_delta(
+    data: &Array3<f64>,
+    amount: f64,
+    sigma: f64,
+    threshold: f64,
+) -> Result<Array3<f64>, Error> {
+    let blurred = gaussian_blur(data, sigma)?;
+    let mut delta = data - &blurred;
+    delta.mapv_inplace(|x| if x.abs() < threshold { 0.0 } else { x * amount });
+    Ok(delta)
+}
+
+fn laplacian_delta(
+    data: &Array3<f64>,
+    amount: f64,
+    laplace: LaplaceType,
+) -> Result<Array3<f64>, Error> {
+    let kernel: Array3<f64> = LaplaceFilter::build_with_params(laplace)?;
+    let (rows, cols, channels) = data.dim();
+    let (k_rows, k_cols, _) = kernel.dim();
+    let clamp = |x: isize, max: usize| x.max(0).min(max as isize - 1) as usize;
+    let mut delta = Array3::zeros(data.dim());
+    for r in 0..rows {
+        for c in 0..cols {
+            for chan in 0..channels {
+                let mut sum = 0.0;
+                for kr in 0..k_rows {
+                    let rr = clamp(r as isize + kr as isize - (k_rows / 2) as isize, rows);
+                    for kc in 0..k_cols {
+                        let cc = clamp(c as isize + kc as isize - (k_cols / 2) as isize, cols);
+                        sum += kernel[[kr, kc, 0]] * data[[rr, cc, chan]];
+                    }
+                }
+                // The Laplace kernels give the negative of the Laplacian
+                delta[[r, c, chan]] = amount * sum;
+            }
+        }
+    }
+    Ok(delta)
+}
+
+impl<T, U> UnsharpMaskExt for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+{
+    type Output = ArrayBase<OwnedRepr<T>, Ix3>;
+
+    fn unsharp_mask(
+        &self,
+        amount: f64,
+        sigma: f64,
+        threshold: f64,
+        channels: SharpenChannels,
+    ) -> Result<Self::Output, Error> {
+        if !(sigma > 0.0 && threshold >= 0.0 && amount.is_finite()) {
+            return Err(Error::InvalidParameter);
+        }
+        let data = normalised_array(self).ok_or(Error::NumericError)?;
+        let result = sharpen_channels(data, channels, |x| {
+            unsharp_delta(x, amount, sigma, threshold)
+        })?;
+        Ok(denormalised_array(result))
+    }
+
+    fn laplacian_sharpen(
+        &self,
+        amount: f64,
+        laplace: LaplaceType,
+        channels: SharpenChannels,
+    ) -> Result<Self::Output, Error> {
+        if !amount.is_finite() {
+            return Err(Error::InvalidParameter);
+        }
+        let data = normalised_array(self).ok_or(Error::NumericError)?;
+        let result = sharpen_channels(data, channels, |x| laplacian_delta(x, amount, laplace))?;
+        Ok(denormalised_array(result))
+    }
+}
+
+/// Luminance weights are only meaningful for RGB, other three channel models
+/// such as HSV would have their channels mixed
+fn check_luminance_model<C: 'static>(channels: SharpenChannels) -> Result<(), Error> {
+    if channels == SharpenChannels::Luminance && TypeId::of::<C>() != TypeId::of::<RGB>() {
+        Err(Error::InvalidParameter)
+    } else {
+        Ok(())
+    }
+}
+
+impl<T, U, C> UnsharpMaskExt for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + Clone + FromPrimitive + ToPrimitive + Num + PixelBound,
+    C: ColourModel + 'static,
+{
+    type Output = Image<T, C>;
+
+    fn unsharp_mask(
+        &self,
+        amount: f64,
+        sigma: f64,
+        threshold: f64,
+        channels: SharpenChannels,
+    ) -> Result<Self::Output, Error> {
+        check_luminance_model::<C>(channels)?;
+        let data = self.data.unsharp_mask(amount, sigma, threshold, channels)?;
+        Ok(Image::from_data(data))
+    }
+
+    fn laplacian_sharpen(
+        &self,
+        amount: f64,
+        laplace: LaplaceType,
+        channels: SharpenChannels,
+    ) -> Result<Self::Output, Error> {
+        check_luminance_model::<C>(channels)?;
+        let data = self.data.laplacian_sharpen(amount, laplace, channels)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::{Gray, HSV};
+
+    fn step_image() -> Image<u8, Gray> {
+        let mut image = Image::<u8, Gray>::from_shape_data(8, 8, vec![50; 64]);
+        image.data.slice_mut(s![.., 4.., ..]).fill(200);
+        image
+    }
+
+    #[test]
+    fn unsharp_increases_contrast() {
+        let image = step_image();
+        let sharp = image
+            .unsharp_mask(1.0, 1.0, 0.0, SharpenChannels::All)
+            .unwrap();
+        for r in 0..8 {
+            assert!(sharp.data[[r, 3, 0]] < 50);
+            assert!(sharp.data[[r, 4, 0]] > 200);
+            // Flat regions away from the edge are untouched
+            assert_eq!(sharp.data[[r, 0, 0]], 50);
+        }
+
+        // A large amount saturates at the pixel bounds instead of wrapping
+        let sharp = image
+            .unsharp_mask(20.0, 1.0, 0.0, SharpenChannels::All)
+            .unwrap();
+        assert_eq!(sharp.data[[0, 3, 0]], 0);
+        assert_eq!(sharp.data[[0, 4, 0]], 255);
+
+        // Thresholding above the edge contrast does nothing
+        let sharp = image
+            .unsharp_mask(1.0, 1.0, 0.9, SharpenChannels::All)
+            .unwrap();
+        assert_eq!(sharp, image);
+    }
+
+    #[test]
+    fn laplacian_increases_contrast() {
+        let image = step_image();
+        for laplace in &[LaplaceType::Standard, LaplaceType::Diagonal] {
+            let sharp = image
+                .laplacian_sharpen(0.5, *laplace, SharpenChannels::All)
+                .unwrap();
+            for r in 0..8 {
+                assert!(sharp.data[[r, 3, 0]] < 50);
+                assert!(sharp.data[[r, 4, 0]] > 200);
+                assert_eq!(sharp.data[[r, 0, 0]], 50);
+                assert_eq!(sharp.data[[r, 7, 0]], 200);
+            }
+        }
+    }
+
+    #[test]
+    fn luminance_sharpening() {
+        let mut image = Image::<f64, RGB>::new(6, 6);
+        image
+            .data
+            .slice_mut(s![.., ..3, ..])
+            .assign(&arr1(&[0.2, 0.3, 0.4]));
+        image
+            .data
+            .slice_mut(s![.., 3.., ..])
+            .assign(&arr1(&[0.6, 0.5, 0.4]));
+        let sharp = image
+            .unsharp_mask(1.0, 1.0, 0.0, SharpenChannels::Luminance)
+            .unwrap();
+        // Every channel is shifted by the same amount keeping the chroma
+        for r in 0..6 {
+            for c in 0..6 {
+                let before = image.pixel(r, c);
+                let after = sharp.pixel(r, c);
+                let delta = after[0] - before[0];
+                assert!((after[1] - before[1] - delta).abs() < 1e-10);
+                assert!((after[2] - before[2] - delta).abs() < 1e-10);
+            }
+        }
+        assert!(sharp.data[[0, 2, 0]] < 0.2);
+        assert!(sharp.data[[0, 3, 0]] > 0.6);
+
+        let gray = Image::<f64, Gray>::new(6, 6);
+        assert_eq!(
+            gray.unsharp_mask(1.0, 1.0, 0.0, SharpenChannels::Luminance),
+            Err(Error::InvalidParameter)
+        );
+        assert_eq!(
+            gray.data
+                .unsharp_mask(1.0, 1.0, 0.0, SharpenChannels::Luminance),
+            Err(Error::ChannelDimensionMismatch)
+        );
+        let hsv = Image::<f64, HSV>::new(6, 6);
+        assert_eq!(
+            hsv.laplacian_sharpen(0.5, LaplaceType::Standard, SharpenChannels::Luminance),
+            Err(Error::InvalidParameter)
+        );
+        assert!(hsv
+            .laplacian_sharpen(0.5, LaplaceType::Standard, SharpenChannels::All)
+            .is_ok());
+    }
+
+    #[test]
+    fn sharpen_bad_parameters() {
+        let image = step_image();
+        for (amount, sigma, threshold) in &[
+            (1.0, 0.0, 0.0),
+            (1.0, 1.0, -0.1),
+            (1.0, f64::NAN, 0.0),
+            (1.0, 1.0, f64::NAN),
+            (f64::NAN, 1.0, 0.0),
+        ] {
+            assert_eq!(
+                image.unsharp_mask(*amount, *sigma, *threshold, SharpenChannels::All),
+                Err(Error::InvalidParameter)
+            );
+        }
+        assert_eq!(
+            image.laplacian_sharpen(f64::NAN, LaplaceType::Standard, SharpenChannels::All),
+            Err(Error::InvalidParameter)
+        );
+    }
+}
@@ -2,9 +2,6 @@ use crate::core::PixelBound;
 use crate::core::{ColourModel, Image, ImageBase};
 use crate::processing::*;
 use ndarray::{prelude::*, Data};
-use ndarray_stats::histogram::{Bins, Edges, Grid};
-use ndarray_stats::HistogramExt;
-use ndarray_stats::QuantileExt;
 use num_traits::cast::FromPrimitive;
 use num_traits::cast::ToPrimitive;
 use num_traits::{Num, NumAssignOps};
@@ -107,64 +104,72 @@ where
     }
 }
 
-/// Calculates Otsu's threshold.
+/// Calculates Otsu's threshold for a single channel image, this is the value
+/// used by `ThresholdOtsuExt`. For the thresholds of each channel of a
+/// multi-channel image use `ThresholdChannelsExt::threshold_values`.
 ///
-/// Works per channel, but currently assumes greyscale.
+/// # Errors
 ///
-/// See the Errors section for the `ThresholdOtsuExt` trait if the number of
-/// channels is greater than one (1), i.e., single channel; otherwise, we would
-/// need to output all three threshold values.
-///
-/// TODO: Add optional nbins
-fn calculate_threshold_otsu<T, U>(mat: &ArrayBase<U, Ix3>) -> Result<f64, Error>
+/// Returns a `ChannelDimensionMismatch` error if more than one channel
+/// exists and an `InvalidDimensions` error if the image is empty.
+pub fn calculate_threshold_otsu<T, U>(mat: &ArrayBase<U, Ix3>) -> Result<f64, Error>
 where
     U: Data<Elem = T>,
     T: Copy + Clone + Ord + Num + NumAssignOps + ToPrimitive + FromPrimitive,
 {
-    let mut threshold = 0.0;
-    let n_bins = 255;
-    for c in mat.axis_iter(Axis(2)) {
-        let scale_factor = (n_bins) as f64 / (c.max().unwrap().to_f64().unwrap());
-        let edges_vec: Vec<u8> = (0..n_bins).collect();
-        let grid = Grid::from(vec![Bins::new(Edges::from(edges_vec))]);
-
-        // get the histogram
-        let flat = Array::from_iter(c.iter()).insert_axis(Axis(1));
-        let flat2 = flat.mapv(|x| ((*x).to_f64().unwrap() * scale_factor).to_u8().unwrap());
-        let hist = flat2.histogram(grid);
-        // Straight out of wikipedia:
-        let counts = hist.counts();
-        let total = counts.sum().to_f64().unwrap();
-        let counts = Array::from_iter(counts.iter());
-        // NOTE: Could use the cdf generation for skimage-esque implementation
-        // which entails a cumulative sum of the standard histogram
-        let mut sum_b = 0.0;
-        let mut weight_b = 0.0;
-        let mut maximum = 0.0;
-        let mut level = 0.0;
-        let mut sum_intensity = 0.0;
-        for (index, count) in counts.indexed_iter() {
-            sum_intensity += (index as f64) * (*count).to_f64().unwrap();
+    let values = single_channel_values(mat)?;
+    if values.is_empty() {
+        return Err(Error::InvalidDimensions);
+    }
+    Ok(otsu_threshold(&values))
+}
+
+/// Otsu's threshold using a 256 bin histogram from zero to the maximum value,
+/// for 8 bit images with a maximum of 255 each bin is one grey level which
+/// matches `skimage.filters.threshold_otsu`. The threshold is the lower edge
+/// of the foreground class so pixels greater than or equal to it are
+/// foreground
+fn otsu_threshold(values: &[f64]) -> f64 {
+    const BINS: usize = 256;
+    let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+    let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+    if max <= 0.0 || max <= min || !max.is_finite() {
+        // Nothing to split, every pixel is foreground
+        return min;
+    }
+    let scale_factor = (BINS - 1) as f64 / max;
+    let mut counts = [0.0; BINS];
+    for x in values {
+        // Values below zero are outside of the histogram
+        if *x >= 0.0 {
+            counts[((x * scale_factor) as usize).min(BINS - 1)] += 1.0;
         }
-        for (index, count) in counts.indexed_iter() {
-            weight_b += count.to_f64().unwrap();
-            sum_b += (index as f64) * count.to_f64().unwrap();
-            let weight_f = total - weight_b;
-            if (weight_b > 0.0) && (weight_f > 0.0) {
-                let mean_f = (sum_intensity - sum_b) / weight_f;
-                let val = weight_b
-                    * weight_f
-                    * ((sum_b / weight_b) - mean_f)
-                    * ((sum_b / weight_b) - mean_f);
-                if val > maximum {
-                    level = 1.0 + (index as f64);
-                    maximum = val;
-                }
+    }
+    // Straight out of wikipedia:
+    let total = counts.iter().sum::<f64>();
+    let sum_intensity = counts
+        .iter()
+        .enumerate()
+        .map(|(i, c)| i as f64 * c)
+        .sum::<f64>();
+    let mut sum_b = 0.0;
+    let mut weight_b = 0.0;
+    let mut maximum = 0.0;
+    let mut level = 0.0;
+    for (index, count) in counts.iter().enumerate() {
+        weight_b += count;
+        sum_b += index as f64 * count;
+        let weight_f = total - weight_b;
+        if weight_b > 0.0 && weight_f > 0.0 {
+            let mean_f = (sum_intensity - sum_b) / weight_f;
+            let val = weight_b * weight_f * (sum_b / weight_b - mean_f).powi(2);
+            if val > maximum {
+                level = 1.0 + index as f64;
+                maximum = val;
             }
         }
-        threshold = level / scale_factor;
     }
-    Ok(threshold)
+    level / scale_factor
 }
 
 impl<T, U, C> ThresholdMeanExt<T> for ImageBase<U, C>
@@ -202,12 +207,24 @@ where
     }
 }
 
-fn calculate_threshold_mean<T, U>(array: &ArrayBase<U, Ix3>) -> Result<f64, Error>
+/// Calculates the mean of all the values in the array, this is the value used
+/// by `ThresholdMeanExt`.
+///
+/// # Errors
+///
+/// Returns a `NumericError` if a value can't be converted to `f64`.
+pub fn calculate_threshold_mean<T, U>(array: &ArrayBase<U, Ix3>) -> Result<f64, Error>
 where
     U: Data<Elem = T>,
     T: Copy + Clone + Num + NumAssignOps + ToPrimitive + FromPrimitive,
 {
-    Ok(array.sum().to_f64().unwrap() / array.len() as f64)
+    // Summing as f64 so small integer types don't overflow
+    let sum = array
+        .iter()
+        .map(|x| x.to_f64())
+        .sum::<Option<f64>>()
+        .ok_or(Error::NumericError)?;
+    Ok(sum / array.len() as f64)
 }
 
 impl<T, U, C> ThresholdApplyExt<T> for ImageBase<U, C>
@@ -254,9 +271,610 @@ where
     data.mapv(|x| x.to_f64().unwrap() >= lower && x.to_f64().unwrap() <= upper)
 }
 
+/// Histogram based methods for selecting a global threshold
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ThresholdMethod {
+    /// Otsu's method, maximises the variance between the two classes
+    Otsu,
+    /// The mean pixel value
+    Mean,
+    /// The triangle method, the point furthest below the line from the
+    /// histogram peak to the far end of the longest tail. Suited to unimodal
+    /// histograms
+    Triangle,
+    /// Li's iterative minimum cross entropy method
+    Li,
+    /// Yen's method, maximises the entropic correlation of the two classes
+    Yen,
+    /// The isodata (Ridler-Calvard) method, the threshold is midway between
+    /// the means of the two classes
+    Isodata,
+    /// The minimum between the two peaks of the histogram after smoothing it
+    /// until it's bimodal
+    Minimum,
+}
+
+/// Computes a global threshold with a histogram based method and applies it.
+pub trait ThresholdMethodExt<T> {
+    /// The thresholding output is a binary image.
+    type Output;
+
+    /// Calculates the threshold value in the units of the image. Values are
+    /// binned into a 256 bin histogram spanning the range of the image.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ChannelDimensionMismatch` error if more than one channel
+    /// exists, an `InvalidDimensions` error if the image is empty and a
+    /// `NumericError` if the `Minimum` method can't smooth the histogram into
+    /// two peaks.
+    fn threshold_value(&self, method: ThresholdMethod) -> Result<f64, Error>;
+
+    /// Calculates the threshold and applies it, pixels greater than or equal
+    /// to the threshold are foreground.
+    ///
+    /// # Errors
+    ///
+    /// See `threshold_value`.
+    fn threshold(&self, method: ThresholdMethod) -> Result<Self::Output, Error>;
+}
+
+/// Splits an image into several classes with multi-level Otsu thresholding.
+pub trait ThresholdMultiOtsuExt<T> {
+    /// The output is an image of class labels.
+    type Output;
+
+    /// Calculates `n` thresholds in increasing order which maximise the
+    /// variance between the `n + 1` classes.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ChannelDimensionMismatch` error if more than one channel
+    /// exists, an `InvalidDimensions` error if the image has fewer than two
+    /// distinct values and an `InvalidParameter` error if `n` is zero or there
+    /// are too many classes for the 256 bin histogram.
+    fn multi_otsu_thresholds(&self, n: usize) -> Result<Vec<f64>, Error>;
+
+    /// Calculates `n` thresholds and labels each pixel with its class, the
+    /// number of thresholds it's greater than or equal to.
+    ///
+    /// # Errors
+    ///
+    /// See `multi_otsu_thresholds`.
+    fn threshold_multi_otsu(&self, n: usize) -> Result<(Vec<f64>, Self::Output), Error>;
+}
+
+/// Number of bins used by the histogram based threshold methods
+const HISTOGRAM_BINS: usize = 256;
+
+/// A histogram spanning the range of the values
+struct ValueHistogram {
+    counts: Vec<f64>,
+    min: f64,
+    width: f64,
+}
+
+impl ValueHistogram {
+    fn new(values: &[f64]) -> Self {
+        let min = values.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = values.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let width = (max - min) / HISTOGRAM_BINS as f64;
+        let mut hist = Self {
+            counts: vec![0.0; HISTOGRAM_BINS],
+            min,
+            width,
+        };
+        for x in values {
+            let bin = hist.bin(*x);
+            hist.counts[bin] += 1.0;
+        }
+        hist
+    }
+
+    fn bin(&self, x: f64) -> usize {
+        (((x - self.min) / self.width) as usize).min(HISTOGRAM_BINS - 1)
+    }
+
+    fn centre(&self, bin: usize) -> f64 {
+        self.min + (bin as f64 + 0.5) * self.width
+    }
+
+    /// The threshold which puts bins up to and including `bin` in the lower
+    /// class
+    fn split_after(&self, bin: usize) -> f64 {
+        self.min + (bin + 1) as f64 * self.width
+    }
+
+    /// Count and sum of the values in a range of bins
+    fn moments(&self, bins: std::ops::Range<usize>) -> (f64, f64) {
+        bins.fold((0.0, 0.0), |(w, s), i| {
+            (w + self.counts[i], s + self.counts[i] * self.centre(i))
+        })
+    }
+
+    fn triangle(&self) -> usize {
+        let nonzero = |i: &usize| self.counts[*i] > 0.0;
+        let first = (0..HISTOGRAM_BINS).find(nonzero).unwrap_or(0);
+        let last = (0..HISTOGRAM_BINS).rev().find(nonzero).unwrap_or(0);
+        let mut peak = 0;
+        for i in 0..HISTOGRAM_BINS {
+            if self.counts[i] > self.counts[peak] {
+                peak = i;
+            }
+        }
+        // Search along the longer tail, the line joins the peak to the bin
+        // past the end of the tail
+        let (end, tail): (f64, Vec<usize>) = if peak - first > last - peak {
+            (first as f64 - 1.0, (first..peak).collect())
+        } else {
+            (last as f64 + 1.0, (peak + 1..=last).collect())
+        };
+        let height = self.counts[peak];
+        // The vertical distance below the line is proportional to the
+        // perpendicular distance
+        let below_line =
+            |i: usize| height * (end - i as f64) / (end - peak as f64) - self.counts[i];
+        let mut best = (f64::NEG_INFINITY, peak);
+        for i in tail {
+            let d = below_line(i);
+            if d > best.0 {
+                best = (d, i);
+            }
+        }
+        best.1
+    }
+
+    fn yen(&self) -> usize {
+        let (total, _) = self.moments(0..HISTOGRAM_BINS);
+        let p = self.counts.iter().map(|c| c / total).collect::<Vec<_>>();
+        let squares_total = p.iter().map(|x| x * x).sum::<f64>();
+        let (mut cumulative, mut squares) = (0.0, 0.0);
+        let mut best = (f64::NEG_INFINITY, 0);
+        for (i, x) in p.iter().enumerate().take(HISTOGRAM_BINS - 1) {
+            cumulative += x;
+            squares += x * x;
+            let upper = squares_total - squares;
+            if cumulative > 0.0 && cumulative < 1.0 && squares > 0.0 && upper > 0.0 {
+                let criterion =
+                    -(squares * upper).ln() + 2.0 * (cumulative * (1.0 - cumulative)).ln();
+                if criterion > best.0 {
+                    best = (criterion, i);
+                }
+            }
+        }
+        best.1
+    }
+
+    fn isodata(&self, mean: f64) -> usize {
+        let mut split = self.bin(mean).min(HISTOGRAM_BINS - 2);
+        for _ in 0..HISTOGRAM_BINS {
+            let (w0, s0) = self.moments(0..split + 1);
+            let (w1, s1) = self.moments(split + 1..HISTOGRAM_BINS);
+            if w0 == 0.0 || w1 == 0.0 {
+                break;
+            }
+            let midpoint = (s0 / w0 + s1 / w1) / 2.0;
+            let next = self.bin(midpoint).min(HISTOGRAM_BINS - 2);
+            if next == split {
+                break;
+            }
+            split = next;
+        }
+        split
+    }
+
+    fn li(&self, mean: f64) -> f64 {
+        // Li's method works on positive values so the histogram is shifted to
+        // start at zero
+        let tolerance = self.width / 2.0;
+        let mut threshold = mean - self.min;
+        for _ in 0..1000 {
+            let split = self.bin(threshold + self.min);
+            let (w0, s0) = self.moments(0..split + 1);
+            let (w1, s1) = self.moments(split + 1..HISTOGRAM_BINS);
+            if w0 == 0.0 || w1 == 0.0 {
+                break;
+            }
+            let back = s0 / w0 - self.min;
+            let fore = s1 / w1 - self.min;
+            let next = if back > 0.0 {
+                (fore - back) / (fore.ln() - back.ln())
+            } else {
+                fore / 2.0
+            };
+            let converged = (next - threshold).abs() < tolerance;
+            threshold = next;
+            if converged {
+                break;
+            }
+        }
+        threshold + self.min
+    }
+
+    fn minimum(&self) -> Result<usize, Error> {
+        // Bins outside the histogram are empty
+        let get = |h: &[f64], i: isize| {
+            if i < 0 || i >= HISTOGRAM_BINS as isize {
+                0.0
+            } else {
+                h[i as usize]
+            }
+        };
+        let mut smoothed = self.counts.clone();
+        for _ in 0..10_000 {
+            let peaks = (0..HISTOGRAM_BINS as isize)
+                .filter(|i| {
+                    let x = get(&smoothed, *i);
+                    get(&smoothed, i - 1) < x && x >= get(&smoothed, i + 1)
+                })
+                .map(|i| i as usize)
+                .collect::<Vec<_>>();
+            if peaks.len() == 2 {
+                let minimum = (peaks[0]..=peaks[1])
+                    .min_by(|a, b| smoothed[*a].partial_cmp(&smoothed[*b]).unwrap())
+                    .unwrap_or(peaks[0]);
+                return Ok(minimum);
+            } else if peaks.len() < 2 {
+                break;
+            }
+            let previous = smoothed.clone();
+            for (i, x) in smoothed.iter_mut().enumerate() {
+                let i = i as isize;
+                *x = (get(&previous, i - 1) + get(&previous, i) + get(&previous, i + 1)) / 3.0;
+            }
+        }
+        Err(Error::NumericError)
+    }
+
+    /// Boundaries of the classes which maximise the between class variance
+    /// found with dynamic programming
+    fn multi_otsu(&self, n: usize) -> Vec<usize> {
+        let classes = n + 1;
+        let mut weights = vec![0.0; HISTOGRAM_BINS + 1];
+        let mut sums = vec![0.0; HISTOGRAM_BINS + 1];
+        for i in 0..HISTOGRAM_BINS {
+            weights[i + 1] = weights[i] + self.counts[i];
+            sums[i + 1] = sums[i] + self.counts[i] * self.centre(i);
+        }
+        let score = |a: usize, b: usize| {
+            let w = weights[b] - weights[a];
+            if w > 0.0 {
+                (sums[b] - sums[a]).powi(2) / w
+            } else {
+                0.0
+            }
+        };
+        // best[k][i] is the best score for splitting bins 0..i into k + 1
+        // classes and start[k][i] the first bin of the last class
+        let mut best = vec![vec![f64::NEG_INFINITY; HISTOGRAM_BINS + 1]; classes];
+        let mut start = vec![vec![0; HISTOGRAM_BINS + 1]; classes];
+        for (i, x) in best[0].iter_mut().enumerate().skip(1) {
+            *x = score(0, i);
+        }
+        for k in 1..classes {
+            for i in (k + 1)..=HISTOGRAM_BINS {
+                for s in k..i {
+                    let candidate = best[k - 1][s] + score(s, i);
+                    if candidate > best[k][i] {
+                        best[k][i] = candidate;
+                        start[k][i] = s;
+                    }
+                }
+            }
+        }
+        let mut boundaries = vec![0; n];
+        let mut end = HISTOGRAM_BINS;
+        for k in (1..classes).rev() {
+            end = start[k][end];
+            boundaries[k - 1] = end;
+        }
+        boundaries
+    }
+}
+
+/// Converts a single channel to `f64` values
+fn single_channel_values<T, U>(data: &ArrayBase<U, Ix3>) -> Result<Vec<f64>, Error>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    if data.shape()[2] > 1 {
+        return Err(Error::ChannelDimensionMismatch);
+    }
+    data.iter()
+        .map(|x| x.to_f64())
+        .collect::<Option<Vec<_>>>()
+        .ok_or(Error::NumericError)
+}
+
+/// Calculates a threshold value from the values with the given method, Otsu
+/// and mean match `calculate_threshold_otsu` and `calculate_threshold_mean`
+fn calculate_threshold(values: &[f64], method: ThresholdMethod) -> Result<f64, Error> {
+    if values.is_empty() {
+        return Err(Error::InvalidDimensions);
+    }
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let hist = ValueHistogram::new(values);
+    if hist.width == 0.0 || !hist.width.is_finite() {
+        // Nothing to split, every pixel is foreground
+        return Ok(hist.min);
+    }
+    let value = match method {
+        ThresholdMethod::Mean => mean,
+        ThresholdMethod::Otsu => otsu_threshold(values),
+        ThresholdMethod::Triangle => hist.split_after(hist.triangle()),
+        ThresholdMethod::Yen => hist.split_after(hist.yen()),
+        ThresholdMethod::Isodata => hist.split_after(hist.isodata(mean)),
+        ThresholdMethod::Li => hist.li(mean),
+        ThresholdMethod::Minimum => hist.split_after(hist.minimum()?),
+    };
+    Ok(value)
+}
+
+impl<T, U> ThresholdMethodExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    type Output = Array3<bool>;
+
+    fn threshold_value(&self, method: ThresholdMethod) -> Result<f64, Error> {
+        let values = single_channel_values(self)?;
+        calculate_threshold(&values, method)
+    }
+
+    fn threshold(&self, method: ThresholdMethod) -> Result<Self::Output, Error> {
+        let value = self.threshold_value(method)?;
+        Ok(self.mapv(|x| x.to_f64().is_some_and(|x| x >= value)))
+    }
+}
+
+impl<T, U, C> ThresholdMethodExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    type Output = Image<bool, C>;
+
+    fn threshold_value(&self, method: ThresholdMethod) -> Result<f64, Error> {
+        self.data.threshold_value(method)
+    }
+
+    fn threshold(&self, method: ThresholdMethod) -> Result<Self::Output, Error> {
+        let data = self.data.threshold(method)?;
+        Ok(Image::from_data(data))
+    }
+}
+
+impl<T, U> ThresholdMultiOtsuExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+{
+    type Output = Array3<u32>;
+
+    fn multi_otsu_thresholds(&self, n: usize) -> Result<Vec<f64>, Error> {
+        if n == 0 || n >= HISTOGRAM_BINS {
+            return Err(Error::InvalidParameter);
+        }
+        let values = single_channel_values(self)?;
+        let hist = ValueHistogram::new(&values);
+        if values.is_empty() || hist.width == 0.0 || !hist.width.is_finite() {
+            return Err(Error::InvalidDimensions);
+        }
+        Ok(hist
+            .multi_otsu(n)
+            .into_iter()
+            .map(|b| hist.split_after(b - 1))
+            .collect())
+    }
+
+    fn threshold_multi_otsu(&self, n: usize) -> Result<(Vec<f64>, Self::Output), Error> {
+        let thresholds = self.multi_otsu_thresholds(n)?;
+        let labels = self.mapv(|x| {
+            let x = x.to_f64().unwrap_or(f64::NAN);
+            thresholds.iter().filter(|t| x >= **t).count() as u32
+        });
+        Ok((thresholds, labels))
+    }
+}
+
+impl<T, U, C> ThresholdMultiOtsuExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive,
+    C: ColourModel,
+{
+    type Output = Image<u32, C>;
+
+    fn multi_otsu_thresholds(&self, n: usize) -> Result<Vec<f64>, Error> {
+        self.data.multi_otsu_thresholds(n)
+    }
+
+    fn threshold_multi_otsu(&self, n: usize) -> Result<(Vec<f64>, Self::Output), Error> {
+        let (thresholds, labels) = self.data.threshold_multi_otsu(n)?;
+        Ok((thresholds, Image::from_data(labels)))
+    }
+}
+
+/// How pixels are changed by `ThresholdChannelsExt::threshold_typed`. A pixel
+/// is above the threshold if it's greater than or equal to it, matching the
+/// other thresholding traits.
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Debug)]
+pub enum ThresholdType {
+    /// Pixels above the threshold become the maximum pixel value and the rest
+    /// the minimum
+    Binary,
+    /// Pixels above the threshold become the minimum pixel value and the rest
+    /// the maximum
+    BinaryInverted,
+    /// Pixels above the threshold are set to the threshold, the rest are
+    /// unchanged
+    Truncate,
+    /// Pixels below the threshold become the minimum pixel value, the rest are
+    /// unchanged
+    ToZero,
+    /// Pixels above the threshold become the minimum pixel value, the rest are
+    /// unchanged
+    ToZeroInverted,
+}
+
+/// Thresholds each channel of an image independently, so unlike the other
+/// thresholding traits multi-channel images are accepted.
+pub trait ThresholdChannelsExt<T> {
+    /// Output type for thresholds that keep the pixel type
+    type Output;
+    /// Output type for binary thresholds
+    type BinaryOutput;
+
+    /// Calculates the threshold of each channel with the given method.
+    ///
+    /// # Errors
+    ///
+    /// See `ThresholdMethodExt::threshold_value`.
+    fn threshold_values(&self, method: ThresholdMethod) -> Result<Vec<f64>, Error>;
+
+    /// Thresholds each channel with its own threshold. Pixels greater than or
+    /// equal to their channel's threshold are foreground, unless `inverted` is
+    /// true in which case the pixels below it are foreground.
+    ///
+    /// # Errors
+    ///
+    /// See `ThresholdMethodExt::threshold_value`.
+    fn threshold_channels(
+        &self,
+        method: ThresholdMethod,
+        inverted: bool,
+    ) -> Result<Self::BinaryOutput, Error>;
+
+    /// Applies the given thresholds, one per channel or a single threshold
+    /// for all channels, changing the pixels as described by `kind`.
+    ///
+    /// # Errors
+    ///
+    /// Returns a `ChannelDimensionMismatch` error if the number of thresholds
+    /// doesn't match the number of channels and a `NumericError` if a
+    /// threshold can't be represented in the pixel type.
+    fn threshold_with(
+        &self,
+        thresholds: &[f64],
+        kind: ThresholdType,
+    ) -> Result<Self::Output, Error>;
+
+    /// Calculates the threshold of each channel and applies it as described
+    /// by `kind`.
+    ///
+    /// # Errors
+    ///
+    /// See `threshold_values` and `threshold_with`.
+    fn threshold_typed(
+        &self,
+        method: ThresholdMethod,
+        kind: ThresholdType,
+    ) -> Result<Self::Output, Error> {
+        let thresholds = self.threshold_values(method)?;
+        self.threshold_with(&thresholds, kind)
+    }
+}
+
+impl<T, U> ThresholdChannelsExt<T> for ArrayBase<U, Ix3>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive + FromPrimitive + PixelBound,
+{
+    type Output = Array3<T>;
+    type BinaryOutput = Array3<bool>;
+
+    fn threshold_values(&self, method: ThresholdMethod) -> Result<Vec<f64>, Error> {
+        self.axis_iter(Axis(2))
+            .map(|channel| channel.insert_axis(Axis(2)).threshold_value(method))
+            .collect()
+    }
+
+    fn threshold_channels(
+        &self,
+        method: ThresholdMethod,
+        inverted: bool,
+    ) -> Result<Self::BinaryOutput, Error> {
+        let thresholds = self.threshold_values(method)?;
+        Ok(Array3::from_shape_fn(self.dim(), |(r, c, chan)| {
+            let x = self[[r, c, chan]].to_f64().unwrap_or(f64::NAN);
+            (x >= thresholds[chan]) != inverted
+        }))
+    }
+
+    fn threshold_with(
+        &self,
+        thresholds: &[f64],
+        kind: ThresholdType,
+    ) -> Result<Self::Output, Error> {
+        let channels = self.shape()[2];
+        if thresholds.len() != channels && thresholds.len() != 1 {
+            return Err(Error::ChannelDimensionMismatch);
+        }
+        let mut result = self.to_owned();
+        for (chan, mut channel) in result.axis_iter_mut(Axis(2)).enumerate() {
+            let threshold = thresholds[chan.min(thresholds.len() - 1)];
+            let truncated = match kind {
+                ThresholdType::Truncate => Some(T::from_f64(threshold).ok_or(Error::NumericError)?),
+                _ => None,
+            };
+            for x in channel.iter_mut() {
+                let above = x.to_f64().ok_or(Error::NumericError)? >= threshold;
+                *x = match (kind, above) {
+                    (ThresholdType::Binary, true) | (ThresholdType::BinaryInverted, false) => {
+                        T::max_pixel()
+                    }
+                    (ThresholdType::Binary, false)
+                    | (ThresholdType::BinaryInverted, true)
+                    | (ThresholdType::ToZero, false)
+                    | (ThresholdType::ToZeroInverted, true) => T::min_pixel(),
+                    (ThresholdType::Truncate, true) => truncated.unwrap_or(*x),
+                    _ => *x,
+                };
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl<T, U, C> ThresholdChannelsExt<T> for ImageBase<U, C>
+where
+    U: Data<Elem = T>,
+    T: Copy + ToPrimitive + FromPrimitive + PixelBound,
+    C: ColourModel,
+{
+    type Output = Image<T, C>;
+    type BinaryOutput = Image<bool, C>;
+
+    fn threshold_values(&self, method: ThresholdMethod) -> Result<Vec<f64>, Error> {
+        self.data.threshold_values(method)
+    }
+
+    fn threshold_channels(
+        &self,
+        method: ThresholdMethod,
+        inverted: bool,
+    ) -> Result<Self::BinaryOutput, Error> {
+        let data = self.data.threshold_channels(method, inverted)?;
+        Ok(Image::from_data(data))
+    }
+
+    fn threshold_with(
+        &self,
+        thresholds: &[f64],
+        kind: ThresholdType,
+    ) -> Result<Self::Output, Error> {
+        let data = self.data.threshold_with(thresholds, kind)?;
+        Ok(Image::from_data(data))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::core::{Gray, RGB};
     use assert_approx_eq::assert_approx_eq;
     use ndarray::arr3;
     use noisy_float::types::n64;
@@ -338,6 +956,16 @@ mod tests {
         assert_approx_eq!(result, expected, 1e-16);
     }
 
+    #[test]
+    fn threshold_calculate_threshold_mean_no_overflow() {
+        // The sum of these is far beyond u8::MAX
+        let data = Array3::from_elem((4, 4, 1), 250u8);
+        assert_eq!(calculate_threshold_mean(&data), Ok(250.0));
+        let image = Image::<u8, Gray>::from_data(data);
+        assert_eq!(image.threshold_value(ThresholdMethod::Mean), Ok(250.0));
+        assert!(image.threshold_mean().unwrap().data.iter().all(|x| *x));
+    }
+
     #[test]
     fn threshold_calculate_threshold_mean_floats() {
         let data = arr3(&[
@@ -351,4 +979,194 @@ mod tests {
 
         assert_approx_eq!(result, expected, 1e-16);
     }
+
+    /// Two clusters of values, 40 pixels from 50 to 60 and 60 pixels from 195
+    /// to 205
+    fn bimodal() -> Image<u8, Gray> {
+        let data = (0..100)
+            .map(|i| if i < 40 { 50 + i % 11 } else { 195 + i % 11 })
+            .collect();
+        Image::from_shape_data(10, 10, data)
+    }
+
+    #[test]
+    fn threshold_methods_bimodal() {
+        let image = bimodal();
+        let expected = image.data.mapv(|x| x > 128);
+        for method in &[
+            ThresholdMethod::Otsu,
+            ThresholdMethod::Mean,
+            ThresholdMethod::Triangle,
+            ThresholdMethod::Li,
+            ThresholdMethod::Yen,
+            ThresholdMethod::Isodata,
+            ThresholdMethod::Minimum,
+        ] {
+            let value = image.threshold_value(*method).unwrap();
+            assert!(value > 60.0 && value <= 195.0, "{:?} {}", method, value);
+            assert_eq!(image.threshold(*method).unwrap().data, expected);
+        }
+
+        // A flat image has nothing to split
+        let flat = Image::<u8, Gray>::from_shape_data(2, 2, vec![7; 4]);
+        assert_eq!(flat.threshold_value(ThresholdMethod::Li), Ok(7.0));
+        let colour = Image::<u8, RGB>::new(2, 2);
+        assert_eq!(
+            colour.threshold_value(ThresholdMethod::Otsu),
+            Err(Error::ChannelDimensionMismatch)
+        );
+    }
+
+    #[test]
+    fn threshold_otsu_matches_skimage() {
+        let mut data = (0..256usize)
+            .map(|i| {
+                if i % 3 == 0 {
+                    40 + (i * 7) % 60
+                } else {
+                    150 + (i * 11) % 90
+                }
+            })
+            .map(|x| x as u8)
+            .collect::<Vec<_>>();
+        data[0] = 0;
+        data[255] = 255;
+        let image = Image::<u8, Gray>::from_shape_data(16, 16, data);
+        // skimage.filters.threshold_otsu gives 97 with pixels greater than it
+        // being foreground, here the threshold is the first foreground level
+        let otsu = calculate_threshold_otsu(&image.data).unwrap();
+        assert_eq!(otsu, 98.0);
+        assert_eq!(image.threshold_value(ThresholdMethod::Otsu), Ok(otsu));
+        assert_eq!(
+            image.threshold(ThresholdMethod::Otsu).unwrap().data,
+            image.data.mapv(|x| x > 97)
+        );
+        assert_eq!(
+            image.threshold_otsu().unwrap(),
+            image.threshold(ThresholdMethod::Otsu).unwrap()
+        );
+        assert_eq!(
+            image.threshold_value(ThresholdMethod::Mean),
+            calculate_threshold_mean(&image.data)
+        );
+    }
+
+    #[test]
+    fn threshold_triangle_tail() {
+        // A tall peak at 10 with a long tail of bright pixels
+        let mut data = vec![10.0f64; 200];
+        data.extend((0..50).map(|i| 20.0 + i as f64 * 4.0));
+        let image = Image::<f64, Gray>::from_shape_data(10, 25, data);
+        let value = image.threshold_value(ThresholdMethod::Triangle).unwrap();
+        assert!(value > 10.0 && value < 40.0, "{}", value);
+    }
+
+    #[test]
+    fn threshold_multi_otsu_classes() {
+        let data = (0..90)
+            .map(|i| match i % 3 {
+                0 => 20.0 + (i % 5) as f64,
+                1 => 120.0 + (i % 5) as f64,
+                _ => 220.0 + (i % 5) as f64,
+            })
+            .collect();
+        let image = Image::<f64, Gray>::from_shape_data(9, 10, data);
+        let (thresholds, labels) = image.threshold_multi_otsu(2).unwrap();
+        assert_eq!(thresholds.len(), 2);
+        assert!(thresholds[0] > 24.0 && thresholds[0] <= 120.0);
+        assert!(thresholds[1] > 124.0 && thresholds[1] <= 220.0);
+        for (i, label) in labels.data.iter().enumerate() {
+            assert_eq!(*label, (i % 3) as u32);
+        }
+
+        assert_eq!(image.multi_otsu_thresholds(0), Err(Error::InvalidParameter));
+        let flat = Image::<f64, Gray>::new(3, 3);
+        assert_eq!(flat.multi_otsu_thresholds(1), Err(Error::InvalidDimensions));
+    }
+
+    #[test]
+    fn threshold_per_channel() {
+        let mut image = Image::<u8, RGB>::new(10, 10);
+        let bimodal = bimodal();
+        image
+            .data
+            .slice_mut(s![.., .., 0])
+            .assign(&bimodal.data.index_axis(Axis(2), 0));
+        image
+            .data
+            .slice_mut(s![.., .., 1])
+            .assign(&bimodal.data.index_axis(Axis(2), 0).mapv(|x| x / 5));
+        image
+            .data
+            .slice_mut(s![.., .., 2])
+            .assign(&bimodal.data.index_axis(Axis(2), 0).mapv(|x| 255 - x));
+        let values = image.threshold_values(ThresholdMethod::Otsu).unwrap();
+        assert_eq!(values.len(), 3);
+        assert!(values[0] > 60.0 && values[0] <= 195.0);
+        assert!(values[1] > 12.0 && values[1] <= 39.0);
+        assert!(values[2] > 60.0 && values[2] <= 195.0);
+        for (chan, value) in values.iter().enumerate() {
+            let channel = image.data.slice(s![.., .., chan..=chan]);
+            assert_eq!(calculate_threshold_otsu(&channel), Ok(*value));
+        }
+        let means = image.threshold_values(ThresholdMethod::Mean).unwrap();
+        for (chan, value) in means.iter().enumerate() {
+            let channel = image.data.slice(s![.., .., chan..=chan]);
+            assert_eq!(calculate_threshold_mean(&channel), Ok(*value));
+        }
+
+        let foreground = image
+            .threshold_channels(ThresholdMethod::Otsu, false)
+            .unwrap();
+        let background = image
+            .threshold_channels(ThresholdMethod::Otsu, true)
+            .unwrap();
+        let expected = bimodal.data.index_axis(Axis(2), 0).mapv(|x| x > 128);
+        assert_eq!(foreground.data.index_axis(Axis(2), 0), expected);
+        assert_eq!(foreground.data.index_axis(Axis(2), 1), expected);
+        assert_eq!(
+            foreground.data.index_axis(Axis(2), 2),
+            expected.mapv(|x| !x)
+        );
+        assert_eq!(background.data, foreground.data.mapv(|x| !x));
+    }
+
+    #[test]
+    fn threshold_typed_outputs() {
+        let data = arr3(&[[[10u8], [100], [200]]]);
+        let apply = |kind| data.threshold_with(&[100.5], kind).unwrap();
+        assert_eq!(apply(ThresholdType::Binary), arr3(&[[[0], [0], [255]]]));
+        assert_eq!(
+            apply(ThresholdType::BinaryInverted),
+            arr3(&[[[255], [255], [0]]])
+        );
+        assert_eq!(
+            apply(ThresholdType::Truncate),
+            arr3(&[[[10], [100], [100]]])
+        );
+        assert_eq!(apply(ThresholdType::ToZero), arr3(&[[[0], [0], [200]]]));
+        assert_eq!(
+            apply(ThresholdType::ToZeroInverted),
+            arr3(&[[[10], [100], [0]]])
+        );
+        assert_eq!(
+            data.threshold_with(&[1.0, 2.0], ThresholdType::Binary),
+            Err(Error::ChannelDimensionMismatch)
+        );
+        let floats = arr3(&[[[0.2], [0.8]]]);
+        assert_eq!(
+            floats
+                .threshold_typed(ThresholdMethod::Mean, ThresholdType::Binary)
+                .unwrap(),
+            arr3(&[[[0.0], [1.0]]])
+        );
+
+        // The public threshold calculations don't overflow small types
+        let bright = Array3::<u8>::from_elem((4, 4, 1), 200);
+        assert_eq!(calculate_threshold_mean(&bright), Ok(200.0));
+        assert_eq!(
+            calculate_threshold_otsu(&Array3::<u8>::zeros((2, 2, 3))),
+            Err(Error::ChannelDimensionMismatch)
+        );
+    }
 }